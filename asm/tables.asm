@@ -0,0 +1,54 @@
+; lookup tables generated with .rept/.for loops and expression functions
+
+SCREEN = $0200
+WIDTH = 32
+ROWS = 32
+COLORS = 16
+
+  .org $0600
+
+start:
+  ldx #0
+.loop:
+  lda sine,x
+  lsr
+  lsr
+  lsr
+  tay
+  lda rows_lo,y
+  sta $00
+  lda rows_hi,y
+  sta $01
+  txa
+  and #WIDTH-1
+  tay
+  lda palette+(COLORS-1)
+  sta ($00),y
+  inx
+  bne .loop
+  brk
+
+; 128 + 127 * sin(2 * pi * i / 256)
+sine:
+  .for i = 0, 255
+  .byte 128+sin(i)
+  .endfor
+
+; screen row addresses split into low and high bytes
+rows_lo:
+  .for row = 0, ROWS-1
+  .byte lo(SCREEN + row*WIDTH)
+  .endfor
+rows_hi:
+  .for row = 0, ROWS-1
+  .byte hi(SCREEN + row*WIDTH)
+  .endfor
+
+palette:
+  .rept COLORS/2
+  .byte 1, 15
+  .endrept
+
+  .text "tables"
+length = strlen("tables")
+  .byte min(length, WIDTH), max(length, 1)
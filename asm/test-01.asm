@@ -23,14 +23,17 @@ secondloop:
   	bne secondloop ; loop two
 	kil
 
+	.segment "NMI"
 	.org $2000 ; NMI handler
 	inc $00
 	rti
 
+	.segment "IRQ"
 	.org $3000 ; IRQ handler
 	inc $01
 	rti
 
+	.segment "VECTORS"
 	.org $fffa ; vectors
 	.word $2000 ; NMI vector
 	.word $0800 ; RESET vector
@@ -1,12 +1,26 @@
 mod commands;
+mod debugger;
+mod inline_asm;
 mod view;
 
 use self::commands::Command;
-use crate::{emulator::Emulator, error::AppError, video};
+use crate::{
+    emulator::Emulator,
+    error::AppError,
+    mos6510::{
+        assembler::Segment,
+        disassembler::Annotations,
+        image::{detect_format, read_image, write_image, ImageFormat},
+    },
+    video,
+};
 use commands::CommandParser;
 use crossterm::event::{self, poll, Event, KeyCode, KeyEvent};
+use debugger::Debugger;
+use inline_asm::InlineAssembler;
 use std::{
-    path::PathBuf,
+    fs,
+    path::Path,
     sync::{
         atomic::{AtomicBool, AtomicPtr, Ordering},
         Arc,
@@ -27,10 +41,15 @@ pub struct Console {
     handle: Option<JoinHandle<Result<u8, AppError>>>,
     running: Arc<AtomicBool>,
     clock: f64,
+    debugger: Option<Debugger>,
+    inline_asm: Option<InlineAssembler>,
 }
 
 const STATUS_OK: &str = "Ok";
 const STATUS_IS_RUNNING: &str = "Emulation is running, press F5 to stop...";
+const MAX_LINE_STEPS: usize = 100_000;
+const SYMBOL_EXTENSIONS: [&str; 4] = ["sym", "lbl", "vs", "json"];
+const MAX_LISTED: usize = 12;
 
 impl Drop for Console {
     fn drop(&mut self) {
@@ -48,8 +67,10 @@ impl Console {
             handle: None,
             running: Arc::new(AtomicBool::new(false)),
             clock,
+            debugger: None,
+            inline_asm: None,
         };
-        console.view.update_size(&console.emulator, None, console.clock, true);
+        console.view.update_size(&console.emulator, None, None, console.clock, true);
         view::flush();
         console.processing_loop()
     }
@@ -70,10 +91,111 @@ impl Console {
     }
 
     fn print_dump(&self) {
-        self.view.print_dump(&self.emulator.memory, self.emulator.cpu.regs.pc);
+        self.view.print_dump(&self.emulator, self.debugger.as_ref());
+    }
+
+    /// Returns the number of bytes loaded and the address execution should start at.
+    fn load_image(&mut self, addr: Option<u16>, path: &Path, format: Option<String>) -> Result<(usize, u16), AppError> {
+        let data = fs::read(path)?;
+        let format = match format {
+            Some(format) => format.parse()?,
+            None => detect_format(path, &data),
+        };
+        let image = read_image(&data, format, addr)?;
+        let size = self.emulator.upload(&image)?;
+        let start = image
+            .start
+            .or(addr)
+            .or_else(|| image.blocks.first().map(|b| b.0))
+            .unwrap_or_default();
+        Ok((size, start))
+    }
+
+    /// Writes the range (inclusive) in the given format, by default the one of the file extension or raw.
+    fn save_image(&self, first: u16, last: u16, path: &Path, format: Option<String>) -> Result<usize, AppError> {
+        let format = match format {
+            Some(format) => format.parse()?,
+            None => path.extension().and_then(|e| e.to_str()?.parse().ok()).unwrap_or(ImageFormat::Raw),
+        };
+        let len = last as usize + 1 - first as usize;
+        let mut segment = Segment::new("CODE", first);
+        segment.code = self.emulator.memory.view(first, len).to_vec();
+        write_image(&mut fs::File::create(path)?, &[segment], format, 0, None)?;
+        Ok(len)
+    }
+
+    fn step_status(&self, status: Result<u8, AppError>) -> String {
+        let pc = self.emulator.cpu.regs.pc;
+        match self.debugger.as_ref().and_then(|d| d.location(pc)) {
+            Some(location) => format!("{:?} at {}", status, location),
+            None => format!("{:?}", status),
+        }
+    }
+
+    fn breakpoints_status(&self) -> String {
+        let addrs: Vec<String> = self.emulator.breakpoints.iter().map(|a| format!("{:04X}", a)).collect();
+        format!("breakpoints: {}", addrs.join(" "))
+    }
+
+    fn toggle_breakpoint(&mut self, addr: u16) {
+        if !self.emulator.breakpoints.remove(&addr) {
+            self.emulator.breakpoints.insert(addr);
+        }
+    }
+
+    /// Executes instructions until PC reaches code of another source line or a breakpoint.
+    fn step_line(&mut self) -> Result<u8, AppError> {
+        let debugger = match &self.debugger {
+            Some(debugger) => debugger,
+            None => return self.emulator.execute(Duration::from_micros(1)),
+        };
+        let line = debugger.line_at(self.emulator.cpu.regs.pc);
+        let mut cycles = 0;
+        for _ in 0..MAX_LINE_STEPS {
+            cycles = self.emulator.execute(Duration::from_micros(1))?;
+            let pc = self.emulator.cpu.regs.pc;
+            if debugger.line_at(pc).is_some_and(|l| Some(l) != line) || self.emulator.breakpoints.contains(&pc) {
+                break;
+            }
+        }
+        Ok(cycles)
+    }
+
+    fn assembly_status(&self) -> String {
+        match &self.inline_asm {
+            Some(inline_asm) => format!("assembling at {:04X}, empty line ends", inline_asm.address()),
+            None => String::from(STATUS_OK),
+        }
+    }
+
+    /// Lines typed in the inline assembly mode are assembled into memory until an empty line.
+    fn assemble_line(&mut self) {
+        let line = self.view.command.trim().to_string();
+        let status = match self.inline_asm.as_mut() {
+            Some(_) if line.is_empty() => {
+                self.inline_asm = None;
+                String::from(STATUS_OK)
+            }
+            Some(inline_asm) => match inline_asm.assemble(&line, &mut self.emulator.memory) {
+                Ok((addr, bytes)) => {
+                    self.print_mem_line();
+                    self.print_dump();
+                    let bytes: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                    format!("{:04X} {}, {}", addr, bytes.join(" "), self.assembly_status())
+                }
+                Err(err) => format!("error: {:?}, {}", err, self.assembly_status()),
+            },
+            None => return,
+        };
+        self.view.update_status(status);
+        self.view.command.clear();
+        self.view.print_command();
     }
 
     fn process_command(&mut self) {
+        if self.inline_asm.is_some() {
+            return self.assemble_line();
+        }
         let mut status = String::from(STATUS_OK);
         match self.parser.parse(&self.view.command) {
             Some(Command::SetPC(pc)) => {
@@ -131,11 +253,39 @@ impl Console {
                 self.print_mem_line();
                 self.print_dump();
             }
-            Some(Command::Load(addr, fpath)) => {
-                match self.emulator.upload(addr, PathBuf::from(fpath)) {
-                    Ok(size) => {
-                        self.emulator.cpu.regs.pc = addr;
-                        self.view.code_addr = addr;
+            Some(Command::Load(_, fpath, format))
+                if format.as_deref() == Some("sym")
+                    || (format.is_none()
+                        && Path::new(&fpath)
+                            .extension()
+                            .is_some_and(|e| SYMBOL_EXTENSIONS.iter().any(|s| e.eq_ignore_ascii_case(s)))) =>
+            {
+                match self.view.symbols.load_labels(&fpath) {
+                    Ok(count) => {
+                        self.print_dump();
+                        status = format!("loaded {} symbols", count);
+                    }
+                    Err(err) => {
+                        status = format!("error: {:?}", err);
+                    }
+                };
+            }
+            Some(Command::Load(_, fpath, Some(format))) if format == "reg" => {
+                match self.view.symbols.load_registers(&fpath) {
+                    Ok(count) => {
+                        self.print_dump();
+                        status = format!("loaded {} register names", count);
+                    }
+                    Err(err) => {
+                        status = format!("error: {:?}", err);
+                    }
+                };
+            }
+            Some(Command::Load(addr, fpath, format)) => {
+                match self.load_image(addr, Path::new(&fpath), format) {
+                    Ok((size, start)) => {
+                        self.emulator.cpu.regs.pc = start;
+                        self.view.code_addr = start;
                         self.print_cpu_line();
                         self.print_mem_line();
                         self.print_dump();
@@ -170,6 +320,96 @@ impl Console {
                 self.print_cpu_line();
                 self.print_dump();
             }
+            Some(Command::LoadDebugInfo(fpath)) => match Debugger::load(Path::new(&fpath)) {
+                Ok(debugger) => {
+                    status = format!(
+                        "loaded debug info for {} lines in {} files",
+                        debugger.info.lines.len(),
+                        debugger.info.files.len()
+                    );
+                    self.debugger = Some(debugger);
+                    self.view.show_source = true;
+                    self.print_dump();
+                }
+                Err(err) => {
+                    status = format!("error: {:?}", err);
+                }
+            },
+            Some(Command::ToggleBreakpoint(addr)) => {
+                self.toggle_breakpoint(addr);
+                self.print_dump();
+                status = self.breakpoints_status();
+            }
+            Some(Command::ToggleBreakpointAt(file, line)) => match self.debugger.as_ref().and_then(|d| d.info.address_of(&file, line)) {
+                Some(addr) => {
+                    self.toggle_breakpoint(addr);
+                    self.print_dump();
+                    status = self.breakpoints_status();
+                }
+                None => {
+                    status = format!("no code for {}:{}", file, line);
+                }
+            },
+            Some(Command::ClearBreakpoints) => {
+                self.emulator.breakpoints.clear();
+                self.print_dump();
+                status = self.breakpoints_status();
+            }
+            Some(Command::Annotations(letters)) => {
+                self.view.annotations = Annotations::from_letters(&letters);
+                self.view.clear_dump();
+                self.print_dump();
+            }
+            Some(Command::Assemble(addr)) => match InlineAssembler::new(addr, &self.view.symbols) {
+                Ok(inline_asm) => {
+                    self.inline_asm = Some(inline_asm);
+                    self.view.code_addr = addr;
+                    self.print_dump();
+                    status = self.assembly_status();
+                }
+                Err(err) => {
+                    status = format!("error: {:?}", err);
+                }
+            },
+            Some(Command::Fill(first, last, _))
+            | Some(Command::Transfer(first, last, _))
+            | Some(Command::Compare(first, last, _))
+            | Some(Command::Hunt(first, last, _))
+            | Some(Command::Save(first, last, _, _))
+                if first > last =>
+            {
+                status = format!("error: range {:04X}-{:04X} ends before start", first, last);
+            }
+            Some(Command::Fill(first, last, pattern)) => {
+                self.emulator.memory.fill(first, last, &pattern);
+                self.print_mem_line();
+                self.print_dump();
+            }
+            Some(Command::Transfer(first, last, dest)) => {
+                self.emulator.memory.transfer(first, last, dest);
+                self.print_mem_line();
+                self.print_dump();
+            }
+            Some(Command::Compare(first, last, other)) => {
+                let mismatches = self.emulator.memory.compare(first, last, other);
+                status = format!("{} mismatches{}", mismatches.len(), listed(&mismatches));
+            }
+            Some(Command::Hunt(first, last, pattern)) => {
+                let matches = self.emulator.memory.find(first, last, &pattern);
+                status = format!("{} matches{}", matches.len(), listed(&matches));
+            }
+            Some(Command::Save(first, last, fpath, format)) => match self.save_image(first, last, Path::new(&fpath), format) {
+                Ok(size) => {
+                    status = format!("saved {} bytes", size);
+                }
+                Err(err) => {
+                    status = format!("error: {:?}", err);
+                }
+            },
+            Some(Command::ToggleSource) => {
+                self.view.show_source = !self.view.show_source;
+                self.print_dump();
+            }
             None => {
                 status = format!("invalid command: {}", &self.view.command);
             }
@@ -222,6 +462,13 @@ impl Console {
         if !idle {
             self.print_cpu_line();
             self.print_mem_line();
+        } else if let Some(handle) = self.handle.take() {
+            let result = handle.join().unwrap();
+            self.print_cpu_line();
+            self.print_mem_line();
+            self.print_dump();
+            self.view.update_status(format!("{:?}", result));
+            view::flush();
         }
         if poll(Duration::from_millis(2)).unwrap() {
             match event::read() {
@@ -249,7 +496,7 @@ impl Console {
                     if idle {
                         view::print_help();
                         self.wait_for_key();
-                        self.view.print_all(&self.emulator, self.clock, idle);
+                        self.view.print_all(&self.emulator, self.debugger.as_ref(), self.clock, idle);
                     }
                 }
                 Ok(Key(KeyEvent { code: F(2), .. })) => {
@@ -275,11 +522,23 @@ impl Console {
                         self.print_cpu_line();
                         self.print_mem_line();
                         self.print_dump();
-                        self.view.update_status(format!("{:?}", status));
+                        self.view.update_status(self.step_status(status));
+                    }
+                }
+                Ok(Key(KeyEvent { code: F(7), .. })) => {
+                    if idle {
+                        self.emulator.trap_on();
+                        let status = self.step_line();
+                        self.print_cpu_line();
+                        self.print_mem_line();
+                        self.print_dump();
+                        self.view.update_status(self.step_status(status));
                     }
                 }
                 Ok(Resize(cols, rows)) => {
-                    self.view.update_size(&self.emulator, Some((cols, rows)), self.clock, idle);
+                    let debugger = self.debugger.as_ref();
+                    self.view
+                        .update_size(&self.emulator, debugger, Some((cols, rows)), self.clock, idle);
                 }
                 Ok(event) => {
                     self.view.update_status(format!("unhandled event: {:?}", event));
@@ -293,3 +552,15 @@ impl Console {
         true
     }
 }
+
+/// Addresses following a colon, up to `MAX_LISTED` of them.
+fn listed(addrs: &[u16]) -> String {
+    let mut text: String = addrs.iter().take(MAX_LISTED).map(|a| format!(" {:04X}", a)).collect();
+    if addrs.len() > MAX_LISTED {
+        text.push_str(" ...");
+    }
+    match addrs.is_empty() {
+        true => text,
+        false => format!(":{}", text),
+    }
+}
@@ -14,12 +14,24 @@ pub enum Command {
     SetFlagC(bool),
     SetByte(u16, u8),
     SetWord(u16, u16),
-    Load(u16, String),
+    Load(Option<u16>, String, Option<String>),
     Disassemble(u16),
     MemoryDump(u16),
     Reset,
     Nmi,
     Irq,
+    LoadDebugInfo(String),
+    ToggleBreakpoint(u16),
+    ToggleBreakpointAt(String, usize),
+    ClearBreakpoints,
+    ToggleSource,
+    Annotations(String),
+    Assemble(u16),
+    Fill(u16, u16, Vec<u8>),
+    Transfer(u16, u16, u16),
+    Compare(u16, u16, u16),
+    Hunt(u16, u16, Vec<u8>),
+    Save(u16, u16, String, Option<String>),
 }
 
 type Parser = fn(&Captures) -> Command;
@@ -48,6 +60,17 @@ fn hex(captures: &Captures, i: usize) -> u16 {
     u16::from_str_radix(&arg(captures, i), 16).unwrap()
 }
 
+fn opt_hex(captures: &Captures, i: usize) -> Option<u16> {
+    captures.get(i).map(|m| u16::from_str_radix(m.as_str(), 16).unwrap())
+}
+
+fn bytes(captures: &Captures, i: usize) -> Vec<u8> {
+    arg(captures, i)
+        .split_whitespace()
+        .map(|b| u8::from_str_radix(b, 16).unwrap())
+        .collect()
+}
+
 fn bin(captures: &Captures) -> bool {
     u16::from_str_radix(&arg(captures, 2), 16).unwrap() != 0
 }
@@ -56,6 +79,38 @@ impl CommandParser {
     pub fn new() -> Self {
         Self {
             parsers: vec![
+                (rx("^dbg\\s+(\\S+)$"), |c| Command::LoadDebugInfo(arg(c, 1))),
+                (rx("^b\\s+([0-9a-f]{1,4})$"), |c| Command::ToggleBreakpoint(hex(c, 1))),
+                (rx("^b\\s+(\\S+):(\\d{1,9})$"), |c| {
+                    Command::ToggleBreakpointAt(arg(c, 1), arg(c, 2).parse().unwrap())
+                }),
+                (rx("^bc$"), |_| Command::ClearBreakpoints),
+                (rx("^src$"), |_| Command::ToggleSource),
+                (rx("^cols(?:\\s+([cfea]+))?$"), |c| {
+                    Command::Annotations(c.get(1).map_or(String::new(), |m| String::from(m.as_str())))
+                }),
+                (rx("^a\\s+([0-9a-f]{1,4})$"), |c| Command::Assemble(hex(c, 1))),
+                (
+                    rx("^f\\s+([0-9a-f]{1,4})\\s+([0-9a-f]{1,4})\\s+([0-9a-f]{1,2}(?:\\s+[0-9a-f]{1,2})*)$"),
+                    |c| Command::Fill(hex(c, 1), hex(c, 2), bytes(c, 3)),
+                ),
+                (rx("^t\\s+([0-9a-f]{1,4})\\s+([0-9a-f]{1,4})\\s+([0-9a-f]{1,4})$"), |c| {
+                    Command::Transfer(hex(c, 1), hex(c, 2), hex(c, 3))
+                }),
+                (rx("^c\\s+([0-9a-f]{1,4})\\s+([0-9a-f]{1,4})\\s+([0-9a-f]{1,4})$"), |c| {
+                    Command::Compare(hex(c, 1), hex(c, 2), hex(c, 3))
+                }),
+                (
+                    rx("^h\\s+([0-9a-f]{1,4})\\s+([0-9a-f]{1,4})\\s+(?:\"([^\"]+)\"|([0-9a-f]{1,2}(?:\\s+[0-9a-f]{1,2})*))$"),
+                    |c| match c.get(3) {
+                        Some(text) => Command::Hunt(hex(c, 1), hex(c, 2), text.as_str().as_bytes().to_vec()),
+                        None => Command::Hunt(hex(c, 1), hex(c, 2), bytes(c, 4)),
+                    },
+                ),
+                (
+                    rx("^s\\s+([0-9a-f]{1,4})\\s+([0-9a-f]{1,4})\\s+(\\S+)(?:\\s+(raw|bin|prg|ihex|hex|srec))?$"),
+                    |c| Command::Save(hex(c, 1), hex(c, 2), arg(c, 3), c.get(4).map(|m| m.as_str().to_lowercase())),
+                ),
                 (set("pc", 4), |c| Command::SetPC(hex(c, 2))),
                 (set("sp", 2), |c| Command::SetSP(hex(c, 2) as u8)),
                 (set("a", 2), |c| Command::SetA(hex(c, 2) as u8)),
@@ -73,7 +128,10 @@ impl CommandParser {
                 (rx("sw\\s*([0-9a-f]{1,4})\\s+([0-9a-f]{1,4})"), |c| {
                     Command::SetWord(hex(c, 1), hex(c, 2))
                 }),
-                (rx("l\\s*([0-9a-f]{1,4})\\s+(\\S+)"), |c| Command::Load(hex(c, 1), arg(c, 2))),
+                (
+                    rx("^l\\s*(?:([0-9a-f]{1,4})\\s+)?(\\S+)(?:\\s+(raw|bin|prg|ihex|hex|srec|sym|reg))?$"),
+                    |c| Command::Load(opt_hex(c, 1), arg(c, 2), c.get(3).map(|m| m.as_str().to_lowercase())),
+                ),
                 (rx("d\\s*([0-9a-f]{1,4})"), |c| Command::Disassemble(hex(c, 1))),
                 (rx("m\\s*([0-9a-f]{1,4})"), |c| Command::MemoryDump(hex(c, 1))),
                 (rx("reset"), |_| Command::Reset),
@@ -92,3 +150,28 @@ impl CommandParser {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_commands() {
+        let parser = CommandParser::new();
+        assert!(matches!(parser.parse("f 0d00 0dff ea 0d"), Some(Command::Fill(0x0d00, 0x0dff, p)) if p == [0xea, 0x0d]));
+        assert!(matches!(
+            parser.parse("t 0600 06ff 0d00"),
+            Some(Command::Transfer(0x0600, 0x06ff, 0x0d00))
+        ));
+        assert!(matches!(
+            parser.parse("c 0600 06ff 0d00"),
+            Some(Command::Compare(0x0600, 0x06ff, 0x0d00))
+        ));
+        assert!(matches!(parser.parse("h 0 ffff \"hi there\""), Some(Command::Hunt(0, 0xffff, p)) if p == b"hi there"));
+        assert!(matches!(parser.parse("h 0 ffff a9 0d"), Some(Command::Hunt(0, 0xffff, p)) if p == [0xa9, 0x0d]));
+        assert!(matches!(parser.parse("s 0600 06ff out.prg"), Some(Command::Save(0x0600, 0x06ff, f, None)) if f == "out.prg"));
+        assert!(matches!(parser.parse("s 0600 06ff out PRG"), Some(Command::Save(_, _, _, Some(f))) if f == "prg"));
+        assert!(matches!(parser.parse("c=1"), Some(Command::SetFlagC(true))));
+        assert!(matches!(parser.parse("a 0d00"), Some(Command::Assemble(0x0d00))));
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{error::AppError, mos6510::debug_info::DebugInfo};
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+};
+
+/// Debug info together with source files it refers to.
+pub struct Debugger {
+    pub info: DebugInfo,
+    sources: Vec<Vec<String>>,
+}
+
+impl Debugger {
+    /// Source files are looked up relative to the current directory first, then to the debug info file.
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let info = DebugInfo::read(BufReader::new(File::open(path)?))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let sources = info
+            .files
+            .iter()
+            .map(|f| {
+                fs::read_to_string(f)
+                    .or_else(|_| fs::read_to_string(dir.join(f)))
+                    .map(|text| text.lines().map(String::from).collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+        Ok(Self { info, sources })
+    }
+
+    /// File and line number of the code at given address.
+    pub fn line_at(&self, addr: u16) -> Option<(usize, usize)> {
+        self.info.line_at(addr).map(|l| (l.file, l.line))
+    }
+
+    /// Source location and enclosing scope of the code at given address.
+    pub fn location(&self, addr: u16) -> Option<String> {
+        let location = self.info.location(self.info.line_at(addr)?);
+        Some(match self.info.scope_at(addr) {
+            Some(scope) => format!("{} in {}", location, scope.name),
+            None => location,
+        })
+    }
+
+    /// Text of the source line starting at given address.
+    pub fn source_at(&self, addr: u16) -> Option<String> {
+        let info = self.info.line_at(addr).filter(|l| l.address == addr)?;
+        let text = self.sources[info.file]
+            .get(info.line - 1)
+            .map(|s| s.trim_end().replace('\t', "    "));
+        Some(match text {
+            Some(text) => format!("{:5} {}", info.line, text),
+            None => self.info.location(info),
+        })
+    }
+}
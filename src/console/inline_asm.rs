@@ -0,0 +1,131 @@
+use crate::{
+    error::AppError,
+    mos6510::{assembler::Assembler, disassembler::SymbolMap, memory::Memory},
+};
+
+/// Assembles lines typed in the console one by one straight into memory.
+pub struct InlineAssembler {
+    assembler: Assembler,
+    /// Number of bytes of each segment already written to memory.
+    written: Vec<usize>,
+}
+
+impl InlineAssembler {
+    /// Labels of `symbols` are known to the assembled lines.
+    pub fn new(addr: u16, symbols: &SymbolMap) -> Result<Self, AppError> {
+        let mut assembler = Assembler::new();
+        symbols.labels().for_each(|(address, name)| assembler.define(name, address as i32));
+        assembler.init_pass(true);
+        assembler.set_location_counter(addr)?;
+        Ok(Self {
+            assembler,
+            written: Vec::new(),
+        })
+    }
+
+    pub fn address(&self) -> u16 {
+        self.assembler.location_counter()
+    }
+
+    /// Returns the address and bytes emitted by the line. Space skipped by `.org` or `.res` keeps its contents,
+    /// directives which need other lines, files or segments are rejected.
+    pub fn assemble(&mut self, line: &str, memory: &mut Memory) -> Result<(u16, Vec<u8>), AppError> {
+        let address = self.address();
+        let segment = self.assembler.current_segment();
+        let emitted = self.emitted();
+        let result = self.assembler.process_line(line);
+        if let Some(directive) = self.assembler.take_pending() {
+            return Err(AppError::NotInline(directive));
+        }
+        if self.assembler.current_segment() != segment {
+            self.assembler.select_segment(segment);
+            return Err(AppError::NotInline(String::from(".segment")));
+        }
+        match result {
+            // blocks are never open, so it is an end of block
+            Err(AppError::UnbalancedBlock(directive)) => return Err(AppError::NotInline(directive)),
+            result => result?,
+        }
+        let moved_only = self.emitted() == emitted;
+        let mut bytes = Vec::new();
+        for (index, segment) in self.assembler.segments().iter().enumerate() {
+            if self.written.len() <= index {
+                self.written.push(0);
+            }
+            let new = &segment.code[self.written[index]..];
+            if !moved_only {
+                memory.set_block(segment.start().wrapping_add(self.written[index] as u16), new);
+                bytes.extend_from_slice(new);
+            }
+            self.written[index] = segment.code.len();
+        }
+        Ok((address, bytes))
+    }
+
+    fn emitted(&self) -> usize {
+        self.assembler.segments().iter().map(|s| s.emitted()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mos6510::symbols::{Symbol, SymbolKind};
+
+    #[test]
+    fn lines_into_memory() {
+        let mut symbols = SymbolMap::default();
+        symbols.add_labels(&[Symbol {
+            name: String::from("border"),
+            value: 0xd020,
+            kind: SymbolKind::Constant,
+            line: None,
+            file: None,
+        }]);
+        let mut memory = Memory::new();
+        let mut inline_asm = InlineAssembler::new(0x0600, &symbols).unwrap();
+        assert_eq!(
+            inline_asm.assemble("loop: inc border", &mut memory).unwrap(),
+            (0x0600, vec![0xee, 0x20, 0xd0])
+        );
+        assert_eq!(
+            inline_asm.assemble("  jmp loop", &mut memory).unwrap(),
+            (0x0603, vec![0x4c, 0x00, 0x06])
+        );
+        assert!(inline_asm.assemble("  lda (", &mut memory).is_err());
+        assert_eq!(inline_asm.address(), 0x0606);
+        assert_eq!(memory.view(0x0600, 6), &[0xee, 0x20, 0xd0, 0x4c, 0x00, 0x06]);
+        // skipped space keeps its contents
+        memory.set_block(0x0606, &[0xff; 0x10]);
+        assert_eq!(inline_asm.assemble("  .org $0610", &mut memory).unwrap(), (0x0606, vec![]));
+        assert_eq!(inline_asm.assemble("  .res 2", &mut memory).unwrap(), (0x0610, vec![]));
+        assert_eq!(inline_asm.assemble("  rts", &mut memory).unwrap(), (0x0612, vec![0x60]));
+        assert_eq!(memory.view(0x0606, 0x0c), &[0xff; 0x0c]);
+        memory.set_block(0x0613, &[0xff; 4]);
+        assert_eq!(inline_asm.assemble("@loop: .res 2", &mut memory).unwrap(), (0x0613, vec![]));
+        assert_eq!(inline_asm.assemble("- .res 2", &mut memory).unwrap(), (0x0615, vec![]));
+        assert_eq!(memory.view(0x0613, 4), &[0xff; 4]);
+        assert_eq!(inline_asm.assemble("  bne @loop", &mut memory).unwrap(), (0x0617, vec![0xd0, 0xfa]));
+    }
+
+    #[test]
+    fn rejected_directives() {
+        let mut memory = Memory::new();
+        let mut inline_asm = InlineAssembler::new(0x0600, &SymbolMap::default()).unwrap();
+        for (line, directive) in [
+            ("  .segment \"DATA\"", ".segment"),
+            ("  .include \"lib.asm\"", ".include"),
+            ("  .rept 2", ".rept"),
+            ("  .for i = 1, 2", ".for"),
+            ("  .endrept", ".endrept"),
+            ("  .endfor", ".endfor"),
+        ] {
+            assert!(matches!(
+                inline_asm.assemble(line, &mut memory),
+                Err(AppError::NotInline(d)) if d == directive
+            ));
+            assert_eq!(inline_asm.assemble("  nop", &mut memory).unwrap().1, &[0xea]);
+        }
+        assert_eq!(memory.view(0x0600, 5), &[0xea; 5]);
+    }
+}
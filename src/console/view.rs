@@ -1,10 +1,15 @@
 use crate::{
     emulator::Emulator,
-    mos6510::{cpu::flags::Flags, disassembler::disassemble, memory::Memory},
+    mos6510::{
+        cpu::flags::Flags,
+        disassembler::{arrows, disassemble, label_row, Annotations, SymbolMap},
+        memory::Memory,
+        operation::Operation,
+    },
 };
 use crate::{mos6510::cpu::Cpu, terminal};
 
-use super::STATUS_OK;
+use super::{debugger::Debugger, STATUS_OK};
 
 #[derive(Default)]
 pub struct View {
@@ -12,6 +17,9 @@ pub struct View {
     pub status: String,
     pub code_addr: u16,
     pub dump_addr: u16,
+    pub show_source: bool,
+    pub symbols: SymbolMap,
+    pub annotations: Annotations,
 
     title: String,
     cols: u16,
@@ -20,7 +28,6 @@ pub struct View {
     command_row: u16,
     status_row: u16,
     shortcuts_row: u16,
-    bytes_per_row: u16,
 }
 
 const PROMPT: &str = "> ";
@@ -81,7 +88,7 @@ impl View {
         view
     }
 
-    pub fn update_size(&mut self, backend: &Emulator, size: Option<(u16, u16)>, req_clock: f64, idle: bool) {
+    pub fn update_size(&mut self, backend: &Emulator, debugger: Option<&Debugger>, size: Option<(u16, u16)>, req_clock: f64, idle: bool) {
         let (cols, rows) = size.unwrap_or_else(terminal::size);
 
         #[cfg(target_os = "windows")]
@@ -95,38 +102,60 @@ impl View {
             self.command_row = self.rows - 3;
             self.status_row = self.rows - 2;
             self.shortcuts_row = self.rows - 1;
-            self.bytes_per_row = (cols - DUMP_COL - 8) / 3;
-            self.print_all(backend, req_clock, idle);
+            self.print_all(backend, debugger, req_clock, idle);
         }
     }
 
-    pub fn print_all(&self, backend: &Emulator, req_clock: f64, idle: bool) {
+    pub fn print_all(&self, backend: &Emulator, debugger: Option<&Debugger>, req_clock: f64, idle: bool) {
         terminal::clear();
         print_cpu_line(&backend.cpu, backend.trap(), backend.clock(), req_clock);
         print_mem_line(&backend.memory);
         if idle {
-            self.print_dump(&backend.memory, backend.cpu.regs.pc);
+            self.print_dump(backend, debugger);
         }
         self.print_command();
         self.print_status();
         self.print_shortcuts();
     }
 
-    pub fn print_dump(&self, memory: &Memory, pc: u16) {
+    /// Source lines are shown instead of the hex dump when debug info is loaded and source view is on.
+    pub fn print_dump(&self, backend: &Emulator, debugger: Option<&Debugger>) {
+        let (memory, pc) = (&backend.memory, backend.cpu.regs.pc);
+        let debugger = debugger.filter(|_| self.show_source);
+        let rows = self.code_rows(memory);
+        let margins = match self.annotations.arrows {
+            true => arrows(
+                memory,
+                &rows
+                    .iter()
+                    .map(|(addr, label)| label.is_none().then_some(*addr))
+                    .collect::<Vec<_>>(),
+            ),
+            false => vec![String::new(); rows.len()],
+        };
+        let dump_col = self.dump_col(margins.first().map_or(0, String::len));
         terminal::hide_cursor();
         terminal::set_cursor_pos(0, self.dump_row);
-        let mut code = self.code_addr;
         let mut dump = self.dump_addr;
-        for _ in self.dump_row..self.command_row {
+        for ((code, label), margin) in rows.into_iter().zip(margins) {
             terminal::clear_line();
-            let highlight = code == pc;
-            let columns = disassemble(memory, &mut code);
+            let highlight = code == pc && label.is_none();
+            let source = debugger.and_then(|d| d.source_at(code));
+            let mark = if backend.breakpoints.contains(&code) && label.is_none() {
+                '*'
+            } else {
+                ' '
+            };
+            let columns = match label {
+                Some(label) => label_row(label),
+                None => disassemble(memory, &mut { code }, Some(&self.symbols)),
+            };
             if highlight {
                 terminal::normal()
             } else {
                 terminal::dim();
             }
-            let left = &format!("{} {} ", columns.0, columns.1);
+            let left = &format!("{}{}{} {} ", margin, columns.0.trim_end(), mark, columns.1);
             terminal::print(left);
             if highlight {
                 terminal::highlight()
@@ -134,12 +163,25 @@ impl View {
                 terminal::normal();
             }
             terminal::print(&columns.2);
-            terminal::set_cursor_col(DUMP_COL);
+            if label.is_none() && self.annotations.width() > 0 {
+                let regs = Some(&backend.cpu.regs).filter(|_| code == pc);
+                terminal::set_cursor_col(dump_col - self.annotations.width() as u16 - 1);
+                terminal::dim();
+                terminal::print(&self.annotations.columns(memory, code, regs));
+            }
+            terminal::set_cursor_col(dump_col);
             terminal::dim();
             terminal::print(" │ ");
+            if debugger.is_some() {
+                let width = self.cols.saturating_sub(dump_col + 4) as usize;
+                terminal::normal();
+                terminal::print(&source.unwrap_or_default().chars().take(width).collect::<String>());
+                terminal::newline();
+                continue;
+            }
             terminal::print(&format!("{:04X}", dump));
             terminal::normal();
-            for _ in 0..self.bytes_per_row {
+            for _ in 0..self.cols.saturating_sub(dump_col + 8) / 3 {
                 terminal::print(&format!(" {:02X}", memory[dump]));
                 dump = dump.wrapping_add(1);
             }
@@ -148,6 +190,30 @@ impl View {
         terminal::show_cursor();
     }
 
+    /// Address of each row of the disassembly view and the label shown in it, the row of the instruction follows.
+    fn code_rows(&self, memory: &Memory) -> Vec<(u16, Option<&str>)> {
+        let mut rows = Vec::new();
+        let mut code = self.code_addr;
+        let mut labelled = None;
+        for _ in self.dump_row..self.command_row {
+            let label = self.symbols.label(code).filter(|_| labelled != Some(code));
+            labelled = label.map(|_| code);
+            rows.push((code, label));
+            if label.is_none() {
+                code = code.wrapping_add(Operation::get(memory[code]).len() as u16);
+            }
+        }
+        rows
+    }
+
+    /// The dump moves right to make room for the arrows margin and annotation columns.
+    fn dump_col(&self, margin: usize) -> u16 {
+        match self.annotations.width() {
+            0 => DUMP_COL + margin as u16,
+            width => DUMP_COL + margin as u16 + width as u16 + 1,
+        }
+    }
+
     pub fn clear_dump(&self) {
         terminal::hide_cursor();
         for row in self.dump_row..self.command_row {
@@ -188,6 +254,7 @@ impl View {
         print_shortcut(" F2", "Clear Stats.");
         print_shortcut(" F5", "Run/Stop");
         print_shortcut(" F6", "Step");
+        print_shortcut(" F7", "Step Line");
         print_shortcut(" Esc", "Quit");
     }
 
@@ -249,12 +316,25 @@ pub fn print_help() {
     print_help_line("n|v|v|i|z|c = bin-value", "assign 0 or 1 value to a CPU flag");
     print_help_line("sb hex-addr hex-byte", "assign byte value to a memory location");
     print_help_line("sw hex-addr hex-word", "assign word value to a memory location");
-    print_help_line("l hex-addr file-path", "load binary file to memory at given location");
+    print_help_line(
+        "l [hex-addr] file-path [fmt]",
+        "load raw, prg, ihex, srec or sym file (raw needs address)",
+    );
+    print_help_line("a hex-addr", "assemble lines typed into memory, empty line ends");
+    print_help_line("f start end bytes", "fill memory range with repeated bytes");
+    print_help_line("t start end dest", "transfer memory range to destination");
+    print_help_line("c start end other", "compare memory range with another one");
+    print_help_line("h start end bytes|\"text\"", "hunt for bytes or text in memory range");
+    print_help_line("s start end file-path [fmt]", "save memory range as raw, prg, ihex or srec file");
     print_help_line("d hex-addr", "set start address of disassembly view");
     print_help_line("m hex-addr", "set start address of hex dump view");
     print_help_line("reset", "simulate CPU reset");
     print_help_line("nmi", "simulate NMI request");
     print_help_line("irq", "simulate IRQ request");
+    print_help_line("dbg file-path", "load debug info, show source lines next to disassembly");
+    print_help_line("src", "toggle between source lines and hex dump view");
+    print_help_line("b hex-addr | b file:line", "toggle breakpoint");
+    print_help_line("bc", "clear all breakpoints");
     terminal::newline();
     terminal::dim();
     terminal::print("key shortcuts:");
@@ -263,6 +343,7 @@ pub fn print_help() {
     print_help_line("F2", "clear runtime statistics");
     print_help_line("F5", "start/stop continuous execution at requested speed");
     print_help_line("F6", "execute single instruction");
+    print_help_line("F7", "execute instructions of a single source line");
     print_help_line("Esc", "quit application");
     terminal::newline();
     terminal::dim();
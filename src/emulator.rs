@@ -1,7 +1,5 @@
 use std::{
-    fs::File,
-    io::Read,
-    path::PathBuf,
+    collections::BTreeSet,
     sync::atomic::AtomicBool,
     sync::atomic::{AtomicU64, Ordering::Relaxed},
     time::{Duration, Instant},
@@ -9,12 +7,13 @@ use std::{
 
 use crate::{
     error::AppError,
-    mos6510::{cpu::Cpu, memory::Memory},
+    mos6510::{cpu::Cpu, image::Image, memory::Memory},
 };
 
 pub struct Emulator {
     pub memory: Memory,
     pub cpu: Cpu,
+    pub breakpoints: BTreeSet<u16>,
     trap: AtomicBool,
     cycles: AtomicU64,
     duration_ns: AtomicU64,
@@ -24,6 +23,7 @@ impl Emulator {
     pub fn new() -> Self {
         let mut backend = Self {
             cpu: Cpu::new(),
+            breakpoints: BTreeSet::new(),
             memory: Memory::new(),
             trap: AtomicBool::new(true),
             cycles: AtomicU64::new(0),
@@ -46,14 +46,14 @@ impl Emulator {
         self.cycles.load(Relaxed) as f64 * 1e9 / self.duration_ns.load(Relaxed) as f64
     }
 
-    pub fn upload(&mut self, addr: u16, fpath: PathBuf) -> Result<usize, AppError> {
+    pub fn upload(&mut self, image: &Image) -> Result<usize, AppError> {
         if !self.trap.load(Relaxed) {
             return Err(AppError::EmulatorAlreadyRunning);
         }
-        let mut buf = Vec::new();
-        let size = File::open(&fpath)?.read_to_end(&mut buf)?;
-        self.memory.set_block(addr, &buf);
-        Ok(size)
+        for (addr, data) in &image.blocks {
+            self.memory.set_block(*addr, data);
+        }
+        Ok(image.size())
     }
 
     pub fn execute(&mut self, period: Duration) -> Result<u8, AppError> {
@@ -69,6 +69,10 @@ impl Emulator {
             if self.trap.load(Relaxed) {
                 return Ok(cycles);
             }
+            if self.breakpoints.contains(&self.cpu.regs.pc) {
+                self.trap.store(true, Relaxed);
+                return Err(AppError::Breakpoint(self.cpu.regs.pc));
+            }
             let t1 = t0 + Duration::from_nanos(period_ns * cycles as u64);
             while Instant::now() < t1 {}
             self.duration_ns.fetch_add((Instant::now() - t0).as_nanos() as u64, Relaxed);
@@ -100,4 +104,18 @@ mod tests {
         let b = Emulator::new();
         assert!(b.trap.load(Relaxed));
     }
+
+    #[test]
+    fn breakpoint() {
+        let mut b = Emulator::new();
+        b.memory.set_block(0x0600, &[0xe8, 0xe8, 0x4c, 0x00, 0x06]);
+        b.cpu.regs.pc = 0x0600;
+        b.breakpoints.insert(0x0601);
+        b.trap_off();
+        assert!(matches!(b.execute(Duration::from_nanos(1)), Err(AppError::Breakpoint(0x0601))));
+        assert!(b.trap());
+        b.trap_off();
+        assert!(matches!(b.execute(Duration::from_nanos(1)), Err(AppError::Breakpoint(0x0601))));
+        assert_eq!(b.cpu.regs.x, 3);
+    }
 }
@@ -1,4 +1,4 @@
-use crate::mos6510::{addrmode::AddrMode, instruction::Instruction};
+use crate::mos6510::{addrmode::AddrMode, assembler::Diagnostic, instruction::Instruction};
 use std::fmt::Display;
 
 #[derive(Debug)]
@@ -18,12 +18,98 @@ pub enum AppError {
     EmulatorNotRunning,
     InvalidOpCode(u16, u8),
     MiniFbError(minifb::Error),
-    AsmLineError(usize, Box<AppError>),
+    AsmErrors(Vec<Diagnostic>),
+    UnbalancedScope,
+    InvalidString(String),
+    InvalidCharmap(String),
+    InvalidCount(i32),
+    InvalidFormat(String),
+    InvalidCpu(String),
+    IncludeNotFound(String),
+    IncludeTooDeep(String),
+    ImageTooLarge(usize, usize),
+    SegmentOverlap(String, String),
+    InvalidImage(usize),
+    InvalidSymbols(usize),
+    NotRelocatable(String),
+    AbsoluteImport(String),
+    InvalidObject(usize),
+    InvalidLayout(usize),
+    UnplacedSegment(String),
+    SegmentOverflow(String),
+    DuplicateExport(String),
+    UnresolvedImport(String),
+    RelocationOverflow(String, u16),
+    DivisionByZero,
+    InvalidFunction(String),
+    UnbalancedBlock(String),
+    PhaseError(String),
+    InvalidDebugInfo(usize),
+    Breakpoint(u16),
+    OperandOutOfRange(i32),
+    InvalidConfig(String),
+    NotInline(String),
 }
 
 impl Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", *self)
+        match self {
+            AppError::UndefinedSymbol(name) => write!(f, "undefined symbol: {}", name),
+            AppError::RedefinedSymbol(name, old, new) => {
+                write!(f, "symbol {} redefined from ${:04X} to ${:04X}", name, old, new)
+            }
+            AppError::MissingOperand => write!(f, "missing operand"),
+            AppError::NoOpCode(instruction, addrmode) => {
+                write!(f, "{} does not support {:?} addressing mode", instruction.mnemonic(), addrmode)
+            }
+            AppError::SyntaxError(_) => write!(f, "syntax error"),
+            AppError::OriginTooLow(addr, lc) => {
+                write!(f, "origin ${:04X} is below location counter ${:04X}", addr, lc)
+            }
+            AppError::BranchTooFar(diff) => write!(f, "branch target too far: {} bytes", diff),
+            AppError::InvalidMnemonic(mnemonic) => write!(f, "invalid mnemonic: {}", mnemonic),
+            AppError::ParseIntError(text, err) => write!(f, "invalid number {}: {}", text, err),
+            AppError::IoError(err) => write!(f, "{}", err),
+            AppError::MiniFbError(err) => write!(f, "{}", err),
+            AppError::AsmErrors(diagnostics) => {
+                for d in diagnostics {
+                    writeln!(f, "{}", d)?;
+                }
+                let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+                write!(f, "assembly failed with {} error(s)", errors)
+            }
+            AppError::UnbalancedScope => write!(f, "unbalanced scope"),
+            AppError::InvalidString(text) => write!(f, "invalid string: {}", text),
+            AppError::InvalidCharmap(name) => write!(f, "invalid character map: {}", name),
+            AppError::InvalidCount(count) => write!(f, "invalid count: {}", count),
+            AppError::InvalidFormat(format) => write!(f, "invalid format: {}", format),
+            AppError::InvalidCpu(cpu) => write!(f, "unsupported cpu: {}", cpu),
+            AppError::IncludeNotFound(name) => write!(f, "include file not found: {}", name),
+            AppError::IncludeTooDeep(name) => write!(f, "includes nested too deep (circular?): {}", name),
+            AppError::ImageTooLarge(len, size) => write!(f, "image of {} bytes does not fit in {} bytes", len, size),
+            AppError::SegmentOverlap(a, b) => write!(f, "segments {} and {} overlap", a, b),
+            AppError::InvalidImage(line) => write!(f, "invalid record at line {}", line),
+            AppError::InvalidSymbols(line) => write!(f, "invalid symbol definition at line {}", line),
+            AppError::NotRelocatable(what) => write!(f, "not allowed in relocatable object: {}", what),
+            AppError::AbsoluteImport(names) => write!(f, "imports need relocatable object: {}", names),
+            AppError::InvalidObject(line) => write!(f, "invalid object file at line {}", line),
+            AppError::InvalidLayout(line) => write!(f, "invalid memory layout at line {}", line),
+            AppError::UnplacedSegment(name) => write!(f, "segment {} is not placed in memory layout", name),
+            AppError::SegmentOverflow(name) => write!(f, "segment {} does not fit in its memory region", name),
+            AppError::DuplicateExport(name) => write!(f, "symbol {} exported more than once", name),
+            AppError::UnresolvedImport(name) => write!(f, "unresolved import: {}", name),
+            AppError::RelocationOverflow(segment, value) => {
+                write!(f, "relocated value ${:04X} does not fit in a byte in segment {}", value, segment)
+            }
+            AppError::DivisionByZero => write!(f, "division by zero"),
+            AppError::InvalidFunction(name) => write!(f, "unknown function or wrong arguments: {}", name),
+            AppError::UnbalancedBlock(directive) => write!(f, "unbalanced block: {}", directive),
+            AppError::PhaseError(name) => write!(f, "value of symbol {} does not stabilise between passes", name),
+            AppError::OperandOutOfRange(value) => write!(f, "operand ${:X} does not fit in zero page", value),
+            AppError::InvalidConfig(message) => write!(f, "invalid configuration: {}", message),
+            AppError::NotInline(directive) => write!(f, "{} is not available in inline assembly", directive),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
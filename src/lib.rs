@@ -0,0 +1,4 @@
+//! Assembler, disassembler and emulator core of 6502 family processors.
+
+pub mod error;
+pub mod mos6510;
@@ -1,18 +1,35 @@
 mod console;
 mod emulator;
-mod error;
-mod mos6510;
 mod terminal;
 mod video;
 
 use console::Console;
 use error::AppError;
-use mos6510::{assembler, disassembler::disassemble_file};
-use std::io::Write;
-use std::{fs::File, path::PathBuf};
+use mo65x_rs::{error, mos6510};
+use mos6510::{
+    assembler,
+    diff::diff_lines,
+    disassembler::{
+        disassemble_range, disassemble_traced, json_range, json_traced, linear, load_file, load_image, trace, write_source, xrefs,
+        Annotations, Config, Hints, SymbolMap,
+    },
+    dump::hex_dump,
+    image::{write_image, ImageFormat},
+    linker::{link, read_layout},
+    object::Object,
+    operation::CpuVariant,
+    symbols::{write_symbols, Symbol, SymbolFormat},
+};
+use std::io::{BufReader, Write};
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+};
 use structopt::StructOpt;
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
+/// Largest gap between segments of a raw or PRG image filled unless `--flat` is given.
+const MAX_GAP: usize = 0x1000;
 
 #[derive(Debug, StructOpt)]
 #[structopt(about = "My Own 65xx emulator, assembler and disassembler")]
@@ -22,32 +39,215 @@ struct CliOpt {
     mode: Option<Mode>,
 }
 
+#[derive(Debug, StructOpt)]
+struct AsmOpt {
+    /// Source file path
+    #[structopt(parse(from_os_str))]
+    src: PathBuf,
+    /// Binary file path
+    #[structopt(short = "o", parse(from_os_str))]
+    bin: Option<PathBuf>,
+    /// Output format: raw, prg, ihex or srec
+    #[structopt(long = "format", default_value = "raw")]
+    format: ImageFormat,
+    /// Pad raw image to given size (hex), e.g. 2000 for 8 KiB ROM
+    #[structopt(long = "pad", parse(try_from_str = parse_hex_size))]
+    pad: Option<usize>,
+    /// Byte (hex) filling gaps between segments and padding
+    #[structopt(long = "fill", default_value = "0", parse(try_from_str = parse_hex_byte))]
+    fill: u8,
+    /// Dump symbol table
+    #[structopt(short = "s")]
+    dump_symbols: bool,
+    /// Write each segment to a separate file
+    #[structopt(long = "split")]
+    split: bool,
+    /// Merge segments into a single raw or PRG image however far apart they are
+    #[structopt(long = "flat")]
+    flat: bool,
+    /// Listing file path
+    #[structopt(short = "l", parse(from_os_str))]
+    listing: Option<PathBuf>,
+    /// Symbol file path
+    #[structopt(long = "symbols", parse(from_os_str))]
+    symbols: Option<PathBuf>,
+    /// Symbol file format: plain, vice or json
+    #[structopt(long = "symbol-format", default_value = "plain")]
+    symbol_format: SymbolFormat,
+    /// Debug info file path
+    #[structopt(long = "debug", parse(from_os_str))]
+    debug: Option<PathBuf>,
+    /// Stop after given number of errors
+    #[structopt(long = "max-errors", default_value = "20")]
+    max_errors: usize,
+    /// Expand out of range conditional branches into inverted branch over JMP
+    #[structopt(long = "long-branches")]
+    long_branches: bool,
+    /// Warn about labels which are never referenced
+    #[structopt(long = "warn-unused")]
+    warn_unused: bool,
+    /// Processor: 6502, 6502x (with undocumented opcodes), 65c02 or 65ce02
+    #[structopt(long = "cpu", default_value = "6502")]
+    cpu: CpuVariant,
+    /// Write relocatable object file for the linker instead of memory image
+    #[structopt(short = "c", long = "object")]
+    object: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct LinkOpt {
+    /// Object file paths
+    #[structopt(parse(from_os_str), required = true)]
+    objects: Vec<PathBuf>,
+    /// Memory layout file path
+    #[structopt(long = "config", parse(from_os_str))]
+    config: PathBuf,
+    /// Binary file path
+    #[structopt(short = "o", parse(from_os_str))]
+    bin: Option<PathBuf>,
+    /// Output format: raw, prg, ihex or srec
+    #[structopt(long = "format", default_value = "raw")]
+    format: ImageFormat,
+    /// Pad raw image to given size (hex)
+    #[structopt(long = "pad", parse(try_from_str = parse_hex_size))]
+    pad: Option<usize>,
+    /// Byte (hex) filling gaps between segments and padding
+    #[structopt(long = "fill", default_value = "0", parse(try_from_str = parse_hex_byte))]
+    fill: u8,
+    /// Symbol file path
+    #[structopt(long = "symbols", parse(from_os_str))]
+    symbols: Option<PathBuf>,
+    /// Symbol file format: plain, vice or json
+    #[structopt(long = "symbol-format", default_value = "plain")]
+    symbol_format: SymbolFormat,
+    /// Map file path
+    #[structopt(long = "map", parse(from_os_str))]
+    map: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct DasmOpt {
+    /// Binary file path
+    #[structopt(parse(from_os_str))]
+    bin: PathBuf,
+    /// Start address
+    #[structopt(parse(try_from_str = parse_hex))]
+    start_addr: u16,
+    /// End address
+    #[structopt(parse(try_from_str = parse_hex))]
+    end_addr: Option<u16>,
+    /// Separate code from data by following execution from entry points and vectors
+    #[structopt(long = "trace")]
+    trace: bool,
+    /// Entry point (hex) of traced code, start address when none is given and there are no vectors
+    #[structopt(long = "entry", parse(try_from_str = parse_hex))]
+    entries: Vec<u16>,
+    /// Address range (hex) holding data, e.g. 0700-07ff
+    #[structopt(long = "data", parse(try_from_str = parse_hex_range))]
+    data: Vec<(u16, u16)>,
+    /// Write source file which assembles back to the same binary
+    #[structopt(long = "source", parse(from_os_str))]
+    source: Option<PathBuf>,
+    /// Symbol file naming addresses: written by asm --symbols (any format), output of asm -s or VICE labels
+    #[structopt(long = "symbols", parse(from_os_str))]
+    symbols: Option<PathBuf>,
+    /// Hardware register names of the machine, e.g. sym/c64.sym
+    #[structopt(long = "registers", parse(from_os_str))]
+    registers: Option<PathBuf>,
+    /// Show cycles of instructions, `*` marks extra cycle on page crossing or taken branch
+    #[structopt(long = "cycles")]
+    cycles: bool,
+    /// Show flags modified by instructions
+    #[structopt(long = "flags")]
+    flags: bool,
+    /// Draw arrows from branches and jumps to their targets in the margin
+    #[structopt(long = "arrows")]
+    arrows: bool,
+    /// TOML file declaring entry points, regions (code, bytes, words, pointers, text, rts_table), labels and comments
+    #[structopt(long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Print one JSON object per instruction: address, bytes, mnemonic, mode, operand, target, cycles and length
+    #[structopt(long = "json")]
+    json: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct XrefOpt {
+    /// Binary file path
+    #[structopt(parse(from_os_str))]
+    bin: PathBuf,
+    /// Start address
+    #[structopt(parse(try_from_str = parse_hex))]
+    start_addr: u16,
+    /// End address
+    #[structopt(parse(try_from_str = parse_hex))]
+    end_addr: Option<u16>,
+    /// Entry point (hex) of traced code, start address when none is given and there are no vectors
+    #[structopt(long = "entry", parse(try_from_str = parse_hex))]
+    entries: Vec<u16>,
+    /// Address range (hex) holding data, e.g. 0700-07ff
+    #[structopt(long = "data", parse(try_from_str = parse_hex_range))]
+    data: Vec<(u16, u16)>,
+    /// TOML file declaring entry points, regions, labels and comments, see dasm
+    #[structopt(long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Symbol file naming addresses
+    #[structopt(long = "symbols", parse(from_os_str))]
+    symbols: Option<PathBuf>,
+    /// Hardware register names of the machine, e.g. sym/c64.sym
+    #[structopt(long = "registers", parse(from_os_str))]
+    registers: Option<PathBuf>,
+    /// Write graph of subroutine calls in Graphviz DOT format
+    #[structopt(long = "dot", parse(from_os_str))]
+    dot: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct DumpOpt {
+    /// Binary file path
+    #[structopt(parse(from_os_str))]
+    bin: PathBuf,
+    /// Start address the file is loaded at
+    #[structopt(default_value = "0", parse(try_from_str = parse_hex))]
+    start_addr: u16,
+    /// End address
+    #[structopt(parse(try_from_str = parse_hex))]
+    end_addr: Option<u16>,
+    /// Decode characters as PETSCII instead of ASCII
+    #[structopt(long = "petscii")]
+    petscii: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct DiffOpt {
+    /// First binary file path
+    #[structopt(parse(from_os_str))]
+    first: PathBuf,
+    /// Second binary file path
+    #[structopt(parse(from_os_str))]
+    second: PathBuf,
+    /// Address both files are loaded at
+    #[structopt(long = "base", default_value = "0", parse(try_from_str = parse_hex))]
+    base: u16,
+    /// Separate code from data by following execution from the base address or vectors
+    #[structopt(long = "trace")]
+    trace: bool,
+}
+
 #[derive(Debug, StructOpt)]
 enum Mode {
     /// Assemble source to machine code
-    Asm {
-        /// Source file path
-        #[structopt(parse(from_os_str))]
-        src: PathBuf,
-        /// Binary file path
-        #[structopt(short = "o", parse(from_os_str))]
-        bin: Option<PathBuf>,
-        /// Dump symbol table
-        #[structopt(short = "s")]
-        dump_symbols: bool,
-    },
+    Asm(AsmOpt),
+    /// Link relocatable object files
+    Link(LinkOpt),
     /// Disassemble machine code
-    Dasm {
-        /// Binary file path
-        #[structopt(parse(from_os_str))]
-        bin: PathBuf,
-        /// Start address
-        #[structopt(parse(try_from_str = parse_hex))]
-        start_addr: u16,
-        /// End address
-        #[structopt(parse(try_from_str = parse_hex))]
-        end_addr: Option<u16>,
-    },
+    Dasm(DasmOpt),
+    /// Cross references of traced code and call graph
+    Xref(XrefOpt),
+    /// Hex and character dump of a binary
+    Dump(DumpOpt),
+    /// Byte runs differing between binaries with disassembly side by side
+    Diff(DiffOpt),
     /// Interactive console
     Console {
         /// Frequency of CPU clock in MHz
@@ -60,49 +260,290 @@ fn parse_hex(hex: &str) -> Result<u16, AppError> {
     u16::from_str_radix(hex, 16).map_err(|e| AppError::ParseIntError(String::from(hex), e))
 }
 
-fn assemble(src: PathBuf, bin: Option<PathBuf>, dump_symbols: bool) -> Result<(), AppError> {
+fn parse_hex_range(text: &str) -> Result<(u16, u16), AppError> {
+    let (first, last) = text.split_once('-').ok_or_else(|| AppError::SyntaxError(String::from(text)))?;
+    Ok((parse_hex(first)?, parse_hex(last)?))
+}
+
+fn parse_hex_size(hex: &str) -> Result<usize, AppError> {
+    usize::from_str_radix(hex, 16).map_err(|e| AppError::ParseIntError(String::from(hex), e))
+}
+
+fn parse_hex_byte(hex: &str) -> Result<u8, AppError> {
+    u8::from_str_radix(hex, 16).map_err(|e| AppError::ParseIntError(String::from(hex), e))
+}
+
+fn assemble(opt: AsmOpt) -> Result<(), AppError> {
+    let src = opt.src;
     println!("source file {:?}, assembling ...", src);
-    let (origin, code, symbols) = assembler::assemble_file(&src)?;
-    println!("code: {} B [{:04X}-{:04X}]", code.len(), origin, origin as usize + code.len() - 1);
-    let bin = bin.unwrap_or({
+    let assembly = assembler::assemble_file(&src, opt.max_errors, opt.long_branches, opt.warn_unused, opt.cpu, opt.object)?;
+    assembly.diagnostics.iter().for_each(|d| println!("{}", d));
+    let segments = assembly.segments;
+    let symbols = assembly.symbols;
+    for segment in &segments {
+        match assembly.object {
+            Some(_) => println!("{}: {} B", segment.name, segment.code.len()),
+            None => println!(
+                "{}: {} B [{:04X}-{:04X}]",
+                segment.name,
+                segment.code.len(),
+                segment.start(),
+                segment.end() - 1
+            ),
+        }
+    }
+    let bin = opt.bin.unwrap_or({
         let mut path = PathBuf::new();
         path.set_file_name(src.file_name().unwrap());
-        path.set_extension("bin");
+        path.set_extension(if opt.object { "obj" } else { opt.format.extension() });
         path
     });
-    println!("writing file {:#?} ...", bin);
-    File::create(&bin)?.write_all(&code)?;
+    if let Some(object) = &assembly.object {
+        println!("writing object {:#?} ...", bin);
+        object.write(&mut File::create(&bin)?)?;
+    } else if opt.split || !opt.flat && far_apart(&segments, opt.format, opt.pad) {
+        if !opt.split {
+            println!("segments more than {} B apart, writing separate files (--flat to merge)", MAX_GAP);
+        }
+        for segment in &segments {
+            let mut path = bin.clone();
+            path.set_extension(format!("{}.{}", segment.name.to_lowercase(), opt.format.extension()));
+            let mut image = Vec::new();
+            write_image(&mut image, std::slice::from_ref(segment), opt.format, opt.fill, opt.pad)?;
+            println!("writing file {:#?} ...", path);
+            File::create(&path)?.write_all(&image)?;
+        }
+    } else {
+        let (origin, code) = assembler::flatten(&segments, opt.fill)?;
+        println!("code: {} B [{:04X}-{:04X}]", code.len(), origin, origin as usize + code.len() - 1);
+        let mut image = Vec::new();
+        write_image(&mut image, &segments, opt.format, opt.fill, opt.pad)?;
+        println!("writing file {:#?} ...", bin);
+        File::create(&bin)?.write_all(&image)?;
+    }
+
+    if let Some(listing) = opt.listing {
+        println!("writing listing {:#?} ...", listing);
+        assembly.listing.write(&mut File::create(&listing)?)?;
+    }
+
+    if let Some(path) = opt.symbols {
+        println!("writing symbols {:#?} ...", path);
+        write_symbols(&mut File::create(&path)?, &symbols, opt.symbol_format)?;
+    }
 
-    if dump_symbols {
+    if let Some(path) = opt.debug {
+        println!("writing debug info {:#?} ...", path);
+        assembly.debug_info.write(&mut File::create(&path)?)?;
+    }
+
+    if opt.dump_symbols {
         println!("symbol table ({} items):", symbols.len());
-        symbols.iter().for_each(|(k, v)| println!("\"{}\" = {:04X}", *k, *v as u16));
+        print_symbol_tree(&symbols);
     }
 
     Ok(())
 }
 
-fn print_disassembly_line(columns: &(String, String, String)) {
-    println!("{}{}{}", columns.0, columns.1, columns.2)
+/// Raw and PRG images would be filled between segments with a gap larger than `MAX_GAP`.
+fn far_apart(segments: &[assembler::Segment], format: ImageFormat, pad: Option<usize>) -> bool {
+    matches!(format, ImageFormat::Raw | ImageFormat::Prg) && pad.is_none() && assembler::largest_gap(segments) > MAX_GAP
 }
 
-fn disassemble(start_addr: u16, end_addr: Option<u16>, bin: PathBuf) -> Result<(), AppError> {
-    print!("binary file {:?}, disassemble from address {:04X} ", bin, start_addr);
-    match end_addr {
-        Some(addr) => println!("to {:04X} ...", addr),
-        None => println!("..."),
+fn link_objects(opt: LinkOpt) -> Result<(), AppError> {
+    let mut objects = Vec::new();
+    for path in &opt.objects {
+        println!("reading object {:?} ...", path);
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        objects.push((name, Object::read(BufReader::new(File::open(path)?))?));
     }
-    disassemble_file(start_addr, end_addr, bin)?.iter().for_each(print_disassembly_line);
+    let linked = link(&objects, &read_layout(&fs::read_to_string(&opt.config)?)?)?;
+    for segment in &linked.segments {
+        println!(
+            "{}: {} B [{:04X}-{:04X}]",
+            segment.name,
+            segment.code.len(),
+            segment.start(),
+            segment.end() - 1
+        );
+    }
+    let default_bin = opt.objects[0].with_extension(opt.format.extension());
+    let bin = opt.bin.unwrap_or(default_bin);
+    let mut image = Vec::new();
+    write_image(&mut image, &linked.segments, opt.format, opt.fill, opt.pad)?;
+    println!("writing file {:#?} ...", bin);
+    File::create(&bin)?.write_all(&image)?;
+    if let Some(path) = opt.symbols {
+        println!("writing symbols {:#?} ...", path);
+        write_symbols(&mut File::create(&path)?, &linked.symbols, opt.symbol_format)?;
+    }
+    if let Some(path) = opt.map {
+        println!("writing map {:#?} ...", path);
+        linked.write_map(&mut File::create(&path)?)?;
+    }
+    Ok(())
+}
+
+fn symbol_path(name: &str) -> Vec<String> {
+    let mut path: Vec<String> = name.split("::").map(String::from).collect();
+    if let Some(local) = path.last().and_then(|last| last.find('.')) {
+        let last = path.pop().unwrap();
+        path.push(String::from(&last[..local]));
+        path.push(String::from(&last[local..]));
+    }
+    path
+}
+
+fn print_symbol_tree(symbols: &[Symbol]) {
+    let mut sorted: Vec<(Vec<String>, i32)> = symbols.iter().map(|s| (symbol_path(&s.name), s.value)).collect();
+    sorted.sort();
+    let mut printed: Vec<String> = Vec::new();
+    for (path, value) in sorted {
+        let common = printed.iter().zip(path.iter()).take_while(|(a, b)| a == b).count();
+        for (depth, scope) in path.iter().enumerate().take(path.len() - 1).skip(common) {
+            println!("{:1$}{2}::", "", depth * 2, scope);
+        }
+        println!(
+            "{:1$}\"{2}\" = {3:04X}",
+            "",
+            (path.len() - 1) * 2,
+            path.last().unwrap(),
+            value as u16
+        );
+        printed = path;
+    }
+}
+
+fn symbol_map(symbols: Option<PathBuf>, registers: Option<PathBuf>, config: Option<&Config>) -> Result<Option<SymbolMap>, AppError> {
+    if symbols.is_none() && registers.is_none() && config.is_none() {
+        return Ok(None);
+    }
+    let mut map = SymbolMap::default();
+    if let Some(path) = symbols {
+        map.load_labels(path)?;
+    }
+    if let Some(path) = registers {
+        map.load_registers(path)?;
+    }
+    if let Some(config) = config {
+        config.apply(&mut map);
+    }
+    Ok(Some(map))
+}
+
+/// Entry points and data ranges given on command line, with those of the configuration.
+fn trace_hints(entries: Vec<u16>, data: Vec<(u16, u16)>, config: Option<&Config>) -> Hints {
+    let mut hints = Hints {
+        entries,
+        data,
+        regions: Vec::new(),
+    };
+    if let Some(config) = config {
+        hints.entries.extend(&config.hints.entries);
+        hints.regions.extend(&config.hints.regions);
+    }
+    hints
+}
+
+fn cross_reference(opt: XrefOpt) -> Result<(), AppError> {
+    println!(
+        "binary file {:?}, cross references from address {:04X} ...",
+        opt.bin, opt.start_addr
+    );
+    let config = opt.config.map(Config::read).transpose()?;
+    let hints = trace_hints(opt.entries, opt.data, config.as_ref());
+    let symbols = symbol_map(opt.symbols, opt.registers, config.as_ref())?;
+    let (memory, end_addr) = load_file(opt.start_addr, opt.end_addr, opt.bin)?;
+    let len = end_addr.saturating_sub(opt.start_addr) as usize;
+    let xrefs = xrefs(&memory, &trace(&memory, opt.start_addr, len, &hints), &hints.entries);
+    xrefs.report(symbols.as_ref()).iter().for_each(|line| println!("{}", line));
+    if let Some(path) = opt.dot {
+        println!("writing call graph {:?} ...", path);
+        File::create(&path)?.write_all(xrefs.call_graph(symbols.as_ref()).as_bytes())?;
+    }
+    Ok(())
+}
+
+fn dump(opt: DumpOpt) -> Result<(), AppError> {
+    let (memory, end) = load_image(opt.start_addr, opt.bin)?;
+    hex_dump(&memory, opt.start_addr, opt.end_addr.map_or(end, usize::from), opt.petscii)
+        .iter()
+        .for_each(|line| println!("{}", line));
+    Ok(())
+}
+
+fn compare(opt: DiffOpt) -> Result<(), AppError> {
+    println!(
+        "comparing {:?} with {:?} loaded at address {:04X} ...",
+        opt.first, opt.second, opt.base
+    );
+    let lines = diff_lines(&fs::read(&opt.first)?, &fs::read(&opt.second)?, opt.base, opt.trace);
+    match lines.is_empty() {
+        true => println!("files are identical"),
+        false => lines.iter().for_each(|line| println!("{}", line)),
+    }
+    Ok(())
+}
+
+fn disassemble(opt: DasmOpt) -> Result<(), AppError> {
+    if !opt.json {
+        print!("binary file {:?}, disassemble from address {:04X} ", opt.bin, opt.start_addr);
+        match opt.end_addr {
+            Some(addr) => println!("to {:04X} ...", addr),
+            None => println!("..."),
+        }
+    }
+    let config = opt.config.map(Config::read).transpose()?;
+    let traced = opt.trace || !opt.entries.is_empty() || !opt.data.is_empty() || config.is_some();
+    let hints = trace_hints(opt.entries, opt.data, config.as_ref());
+    let symbols = symbol_map(opt.symbols, opt.registers, config.as_ref())?;
+    let symbols = symbols.as_ref();
+    let (memory, end_addr) = load_file(opt.start_addr, opt.end_addr, opt.bin)?;
+    let len = end_addr.saturating_sub(opt.start_addr) as usize;
+    if let Some(path) = opt.source {
+        let trace = match traced {
+            true => trace(&memory, opt.start_addr, len, &hints),
+            false => linear(&memory, opt.start_addr, len),
+        };
+        println!("writing source {:#?} ...", path);
+        return Ok(write_source(&mut File::create(&path)?, &memory, &trace, symbols)?);
+    }
+    if opt.json {
+        let lines = match traced {
+            true => json_traced(&memory, &trace(&memory, opt.start_addr, len, &hints), symbols),
+            false => json_range(&memory, opt.start_addr, end_addr, symbols),
+        };
+        lines.iter().for_each(|line| println!("{}", line));
+        return Ok(());
+    }
+    let lines = match traced {
+        true => disassemble_traced(&memory, &trace(&memory, opt.start_addr, len, &hints), symbols),
+        false => disassemble_range(&memory, opt.start_addr, end_addr, symbols),
+    };
+    let annotations = Annotations {
+        cycles: opt.cycles,
+        flags: opt.flags,
+        effective: false,
+        arrows: opt.arrows,
+    };
+    annotations.lines(&memory, &lines).iter().for_each(|line| println!("{}", line));
     Ok(())
 }
 
 fn main() {
     let cliopt = CliOpt::from_args();
     let result = match cliopt.mode.unwrap_or(Mode::Console { clock_mhz: 1.0 }) {
-        Mode::Asm { src, bin, dump_symbols } => assemble(src, bin, dump_symbols),
-        Mode::Dasm { start_addr, end_addr, bin } => disassemble(start_addr, end_addr, bin),
+        Mode::Asm(opt) => assemble(opt),
+        Mode::Link(opt) => link_objects(opt),
+        Mode::Dasm(opt) => disassemble(opt),
+        Mode::Xref(opt) => cross_reference(opt),
+        Mode::Dump(opt) => dump(opt),
+        Mode::Diff(opt) => compare(opt),
         Mode::Console { clock_mhz } => Console::start(APP_NAME, clock_mhz * 1e6),
     };
-    if let Err(apperr) = result {
-        println!("\nerror: {:?}", apperr)
+    match result {
+        Err(apperr @ AppError::AsmErrors(_)) => println!("{}", apperr),
+        Err(apperr) => println!("\nerror: {}", apperr),
+        Ok(_) => {}
     }
 }
@@ -1,7 +1,14 @@
 pub mod addrmode;
 pub mod assembler;
 pub mod cpu;
+pub mod debug_info;
+pub mod diff;
 pub mod disassembler;
+pub mod dump;
+pub mod image;
 pub mod instruction;
+pub mod linker;
 pub mod memory;
+pub mod object;
 pub mod operation;
+pub mod symbols;
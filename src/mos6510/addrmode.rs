@@ -14,14 +14,18 @@ pub enum AddrMode {
     Absolute,
     AbsoluteX,
     AbsoluteY,
+    ZeroPageIndirect,
+    AbsoluteIndexedIndirect,
+    ZeroPageRelative,
 }
 
 impl AddrMode {
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> u8 {
         match self {
             Implied => 0,
-            Relative | Immediate | ZeroPage | ZeroPageX | ZeroPageY | IndexedIndirectX | IndirectIndexedY => 1,
-            Indirect | Absolute | AbsoluteX | AbsoluteY => 2,
+            Relative | Immediate | ZeroPage | ZeroPageX | ZeroPageY | IndexedIndirectX | IndirectIndexedY | ZeroPageIndirect => 1,
+            Indirect | Absolute | AbsoluteX | AbsoluteY | AbsoluteIndexedIndirect | ZeroPageRelative => 2,
         }
     }
 
@@ -29,12 +33,19 @@ impl AddrMode {
         if !(0..=255).contains(&opvalue) {
             *self
         } else {
-            match self {
-                Absolute => ZeroPage,
-                AbsoluteX => ZeroPageX,
-                AbsoluteY => ZeroPageY,
-                _ => *self,
-            }
+            self.zero_page()
+        }
+    }
+
+    /// Zero page counterpart of absolute addressing mode.
+    pub fn zero_page(&self) -> AddrMode {
+        match self {
+            Absolute => ZeroPage,
+            AbsoluteX => ZeroPageX,
+            AbsoluteY => ZeroPageY,
+            Indirect => ZeroPageIndirect,
+            AbsoluteIndexedIndirect => IndexedIndirectX,
+            _ => *self,
         }
     }
 }
@@ -1,55 +1,191 @@
+mod builder;
+mod charmap;
+mod diagnostic;
+mod expression;
+mod listing;
 mod operand;
 mod patterns;
+mod segment;
 mod tokens;
 
 #[cfg(test)]
 mod assembler_tests;
 
-use self::operand::Operand;
-use super::{addrmode::*, instruction::Instruction, operation::find_opcode};
-use crate::error::AppError;
+use self::{
+    charmap::Charmap,
+    diagnostic::{find_span, statement_span},
+    operand::{Operand, OperandSize},
+};
+use super::{
+    addrmode::*,
+    instruction::Instruction,
+    operation::{find_opcode, CpuVariant},
+};
+use crate::{
+    error::AppError,
+    mos6510::{
+        debug_info::{DebugInfo, LineInfo, ScopeInfo},
+        object::{Export, Object, ObjectSegment, RelocKind, Relocation, Target},
+        symbols::{Symbol, SymbolKind},
+    },
+};
+pub use builder::AssemblerBuilder;
+pub use diagnostic::{Diagnostic, Severity};
+pub use listing::Listing;
 use operand::Resolver;
 use regex::Regex;
+pub use segment::{flatten, largest_gap, Segment};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::io::Read;
-use std::{collections::HashMap, fs::File, path::Path};
+use std::ops::Range;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
 use tokens::Tokens;
 use AddrMode::Implied;
-use Instruction::{Jmp, Jsr};
+use Instruction::{Bcc, Bcs, Beq, Bmi, Bne, Bpl, Bra, Bvc, Bvs, Jmp};
 
 type Handler = fn(&mut Assembler, tokens: Tokens) -> Result<(), AppError>;
 
 const DEFAULT_LOCATION_COUNTER: u16 = 0;
+const MAX_PASSES: usize = 16;
+pub const MAX_ERRORS: usize = 20;
+const JMP_SIZE: u8 = 3;
+const SIZE_SUFFIX: char = '.';
+const MAX_INCLUDE_DEPTH: usize = 16;
+const MAX_REPEAT: i32 = 0x10000;
+
+/// Block of lines repeated by `.rept` or `.for`, loop variable takes consecutive values.
+struct Repeat {
+    directive: String,
+    variable: Option<String>,
+    values: Range<i32>,
+}
 
 pub struct Assembler {
     handlers: Vec<(Regex, Handler)>,
     resolver: Resolver,
-    origin: Option<u16>,
-    code: Vec<u8>,
+    segments: Vec<Segment>,
+    segment: usize,
     generate_code: bool,
-    location_counter: u16,
     op_list_separator: Regex,
+    charmap: Charmap,
+    listing: Listing,
+    last_opcode: Option<u8>,
+    debug_info: DebugInfo,
+    open_scopes: Vec<(String, usize, u16)>,
+    span: Option<(usize, usize)>,
+    warnings: Vec<(String, Option<(usize, usize)>)>,
+    diagnostics: Vec<Diagnostic>,
+    max_errors: usize,
+    long_branches: bool,
+    long_branch: usize,
+    expanded: HashSet<usize>,
+    warn_unused: bool,
+    cpu: CpuVariant,
+    default_cpu: CpuVariant,
+    predefined: Vec<(String, i32)>,
+    includes: HashMap<String, String>,
+    include_dir: Option<PathBuf>,
+    pending_include: Option<String>,
+    file: usize,
+    include_depth: usize,
+    main_line: usize,
+    relocatable: bool,
+    relocations: Vec<Relocation>,
+    exports: Vec<Export>,
+    block_begin: Regex,
+    block_end: Regex,
+    pending_block: Option<Repeat>,
+    open_blocks: usize,
+    loop_depth: usize,
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Result of assembly, `debug_info` maps emitted bytes back to source files and lines.
+pub struct Assembly {
+    pub segments: Vec<Segment>,
+    pub symbols: Vec<Symbol>,
+    pub listing: Listing,
+    pub debug_info: DebugInfo,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Relocatable object, when assembled with `set_relocatable`.
+    pub object: Option<Object>,
 }
 
 impl Assembler {
     pub fn new() -> Assembler {
+        let p = patterns::AsmPatterns::new();
         Assembler {
-            code: Vec::new(),
-            origin: None,
-            location_counter: 0,
+            segments: vec![Segment::new(segment::DEFAULT_SEGMENT, DEFAULT_LOCATION_COUNTER)],
+            segment: 0,
             generate_code: false,
             resolver: Resolver::new(),
             op_list_separator: Regex::new("(?:\\s*,\\s*)|(?:\\s+)").unwrap(),
+            charmap: Charmap::ascii(),
+            listing: Listing::default(),
+            last_opcode: None,
+            debug_info: DebugInfo::default(),
+            open_scopes: Vec::new(),
+            span: None,
+            warnings: Vec::new(),
+            diagnostics: Vec::new(),
+            max_errors: MAX_ERRORS,
+            long_branches: false,
+            long_branch: 0,
+            expanded: HashSet::new(),
+            warn_unused: false,
+            cpu: CpuVariant::default(),
+            default_cpu: CpuVariant::default(),
+            predefined: Vec::new(),
+            includes: HashMap::new(),
+            include_dir: None,
+            pending_include: None,
+            file: 0,
+            include_depth: 0,
+            main_line: 0,
+            relocatable: false,
+            relocations: Vec::new(),
+            exports: Vec::new(),
+            block_begin: p.block_begin,
+            block_end: p.cmd_end_block.clone(),
+            pending_block: None,
+            open_blocks: 0,
+            loop_depth: 0,
             handlers: {
-                let p = patterns::AsmPatterns::new();
                 vec![
                     (p.empty_line, Assembler::handle_empty_line),
                     (p.cmd_set_location_counter, Assembler::handle_set_location_counter),
                     (p.cmd_emit_bytes, Assembler::handle_emit_bytes),
                     (p.cmd_emit_words, Assembler::handle_emit_words),
+                    (p.cmd_emit_dbytes, Assembler::handle_emit_dbytes),
+                    (p.cmd_emit_dwords, Assembler::handle_emit_dwords),
+                    (p.cmd_emit_text, Assembler::handle_emit_text),
+                    (p.cmd_charmap, Assembler::handle_charmap),
+                    (p.cmd_fill, Assembler::handle_fill),
+                    (p.cmd_define_constant, Assembler::handle_define_constant),
+                    (p.cmd_segment, Assembler::handle_segment),
+                    (p.cmd_begin_scope, Assembler::handle_begin_scope),
+                    (p.cmd_end_scope, Assembler::handle_end_scope),
+                    (p.cmd_cpu, Assembler::handle_cpu),
+                    (p.cmd_include, Assembler::handle_include),
+                    (p.cmd_export, Assembler::handle_export),
+                    (p.cmd_import, Assembler::handle_import),
+                    (p.cmd_rept, Assembler::handle_rept),
+                    (p.cmd_for, Assembler::handle_for),
+                    (p.cmd_end_block, Assembler::handle_end_block),
                     (p.ins_implied, Assembler::handle_implied),
                     (p.ins_immediate, Assembler::handle_immediate),
                     (p.ins_branch, Assembler::handle_relative),
+                    (p.ins_long_branch, Assembler::handle_long_branch),
+                    (p.ins_zero_page_relative, Assembler::handle_zero_page_relative),
                     (p.ins_absolute, Assembler::handle_absolute),
                     (p.ins_absolute_indexed_x, Assembler::handle_absolute_indexed_x),
                     (p.ins_absolute_indexed_y, Assembler::handle_absolute_indexed_y),
@@ -61,26 +197,62 @@ impl Assembler {
         }
     }
 
+    pub fn builder() -> AssemblerBuilder {
+        AssemblerBuilder::new()
+    }
+
+    fn into_assembly(mut self) -> Assembly {
+        Assembly {
+            object: self.relocatable.then(|| self.object()),
+            segments: self.segments().iter().filter(|s| s.is_initialized()).cloned().collect(),
+            symbols: self.resolver.symbol_table(&self.debug_info.files),
+            listing: std::mem::take(&mut self.listing),
+            debug_info: std::mem::take(&mut self.debug_info),
+            diagnostics: std::mem::take(&mut self.diagnostics),
+        }
+    }
+
+    /// All segments are kept, so relocations and exports can refer to them by index.
+    fn object(&mut self) -> Object {
+        Object {
+            segments: self
+                .segments
+                .iter()
+                .map(|s| ObjectSegment {
+                    name: s.name.clone(),
+                    data: s.code.clone(),
+                    reserved: !s.is_initialized() && !s.code.is_empty(),
+                })
+                .collect(),
+            imports: self.resolver.imports().clone(),
+            exports: std::mem::take(&mut self.exports),
+            relocations: std::mem::take(&mut self.relocations),
+        }
+    }
+
     pub fn process_line(&mut self, line: &str) -> Result<(), AppError> {
+        self.span = None;
         for (regex, handler) in self.handlers.iter() {
-            if let Some(captures) = regex.captures(&line) {
+            if let Some(captures) = regex.captures(line) {
                 let tokens = Tokens::new(captures);
                 if let Some(label) = tokens.label() {
-                    self.resolver.define_symbol(label, self.location_counter as i32)?;
+                    self.span = tokens.span(1);
+                    self.resolver.define_symbol(label, self.location_counter() as i32)?;
                 };
+                self.span = tokens.span(3).or_else(|| tokens.span(2));
                 return handler(self, tokens);
             }
         }
         Err(AppError::SyntaxError(String::from(line)))
     }
 
-    fn parse_operand_list(&self, oplist: Option<&str>) -> Result<Vec<i32>, AppError> {
+    fn parse_operand_list(&mut self, oplist: Option<&str>) -> Result<Vec<Operand>, AppError> {
         match oplist {
             Some(oplist) => {
-                let mut values: Vec<i32> = Vec::new();
-                for opstr in self.op_list_separator.split(oplist) {
+                let mut values: Vec<Operand> = Vec::new();
+                for opstr in operand::split_list(oplist) {
                     match self.resolver.resolve(opstr, self.generate_code) {
-                        Ok(operand) => values.push(operand.value),
+                        Ok(operand) => values.push(operand),
                         Err(err) => return Err(err),
                     }
                 }
@@ -97,21 +269,69 @@ impl Assembler {
             let opstr = opstr.ok_or(AppError::MissingOperand)?;
             let mut operand = self.resolver.resolve(opstr, self.generate_code)?;
             if self.generate_code && addrmode == AddrMode::Relative && operand.symbolic {
-                let diff = operand.value - self.location_counter as i32 - 2;
+                if !self.is_local(&operand) {
+                    return Err(AppError::NotRelocatable(String::from("branch to another segment")));
+                }
+                operand.reloc = None;
+                let diff = operand.value - self.location_counter() as i32 - 2;
                 let displacement = i8::try_from(diff).map_err(|_| AppError::BranchTooFar(diff))?;
+                if displacement == 0 {
+                    self.warn(String::from("branch to the next instruction"));
+                }
                 operand.value = displacement as i32;
             }
             Ok(operand)
         }
     }
 
+    /// Relative jumps can reach only targets in the same segment of relocatable object.
+    fn is_local(&self, operand: &Operand) -> bool {
+        operand.reloc.is_none_or(|(target, _)| target == Target::Segment(self.segment))
+    }
+
+    /// Instruction of the mnemonic, provided it is available for the current processor.
+    fn parse_instruction(&self, mnemonic: &str) -> Result<Instruction, AppError> {
+        let instruction = Instruction::parse(mnemonic)?;
+        match self.cpu.supports(instruction) {
+            true => Ok(instruction),
+            false => Err(AppError::InvalidMnemonic(String::from(mnemonic))),
+        }
+    }
+
     fn assemble(&mut self, addrmode: AddrMode, tokens: Tokens) -> Result<(), AppError> {
         let operand = self.prepare_operand(addrmode, tokens.operand())?;
         let mnemonic = tokens.operation().ok_or(AppError::SyntaxError(tokens.to_string()))?;
-        let instruction = Instruction::parse(mnemonic)?;
-        let addrmode = optimize_addrmode(instruction, addrmode, operand);
-        let opcode = find_opcode(instruction, addrmode)?;
+        let (mnemonic, size) = split_size(mnemonic);
+        let instruction = self.parse_instruction(mnemonic)?;
+        let size = if size == OperandSize::Auto { operand.size } else { size };
+        if self.generate_code && size == OperandSize::ZeroPage && !(0..=255).contains(&operand.value) {
+            return Err(AppError::OperandOutOfRange(operand.value));
+        }
+        let optimized = optimize_addrmode(self.cpu, instruction, addrmode, operand, size);
+        if size == OperandSize::Auto
+            && operand.symbolic
+            && operand.reloc.is_none()
+            && matches!(addrmode, AddrMode::Absolute | AddrMode::AbsoluteX | AddrMode::AbsoluteY)
+        {
+            if optimized == addrmode && (0..=255).contains(&operand.value) {
+                self.warn(String::from("no zero page mode for this instruction, absolute addressing used"));
+            } else if optimized != addrmode && operand.forward {
+                self.warn(String::from(
+                    "forward reference assembled as zero page, use .z suffix to make it explicit",
+                ));
+            }
+        }
+        if self.cpu.has_jmp_bug() && instruction == Jmp && optimized == AddrMode::Indirect && operand.value & 0xff == 0xff {
+            self.warn(format!(
+                "indirect jump vector at page boundary, high byte is fetched from ${:04X}",
+                operand.value & 0xff00
+            ));
+        }
+        let addrmode = optimized;
+        let opcode = find_opcode(self.cpu, instruction, addrmode)?;
+        self.last_opcode = Some(opcode);
         self.emit_byte(opcode);
+        self.relocate(operand, addrmode.len());
         match addrmode.len() {
             1 => self.emit_byte(operand.value as u8),
             2 => self.emit_word(operand.value as u16),
@@ -122,21 +342,57 @@ impl Assembler {
 
     pub fn init_pass(&mut self, generate_code: bool) {
         self.generate_code = generate_code;
-        self.origin = None;
-        self.location_counter = DEFAULT_LOCATION_COUNTER;
-        self.code.clear();
+        self.segments = vec![Segment::new(segment::DEFAULT_SEGMENT, DEFAULT_LOCATION_COUNTER)];
+        self.segment = 0;
+        self.charmap = Charmap::ascii();
+        self.cpu = self.default_cpu;
+        self.listing = Listing::default();
+        self.debug_info.lines.clear();
+        self.debug_info.scopes.clear();
+        self.open_scopes.clear();
+        self.diagnostics.clear();
+        self.relocations.clear();
+        self.exports.clear();
+        self.pending_block = None;
+        self.open_blocks = 0;
+        self.long_branch = 0;
+        self.resolver.init_pass();
+        self.resolver.set_segment(self.relocatable.then_some(0));
+        self.resolver.set_line(0);
+        self.resolver.set_site(0, 0);
+        for (name, value) in &self.predefined {
+            // predefined names are unique, so it can not fail
+            let _ = self.resolver.define_constant(name, *value);
+        }
+    }
+
+    pub fn segments(&self) -> &Vec<Segment> {
+        &self.segments
     }
 
-    pub fn code(&self) -> &Vec<u8> {
-        &self.code
+    pub fn location_counter(&self) -> u16 {
+        self.segments[self.segment].location_counter
     }
 
-    pub fn symbols(&self) -> &HashMap<String, i32> {
-        self.resolver.symbols()
+    /// Index of the segment in `segments` lines are assembled into.
+    pub fn current_segment(&self) -> usize {
+        self.segment
     }
 
-    pub fn origin(&self) -> u16 {
-        self.origin.unwrap_or(self.location_counter)
+    pub fn select_segment(&mut self, index: usize) {
+        self.segment = index;
+        self.resolver.set_segment(self.relocatable.then_some(index));
+    }
+
+    /// Directive of the last line which needs another file or the following lines, it is dropped as `process_line`
+    /// handles a single line, see `process_lines`.
+    pub fn take_pending(&mut self) -> Option<String> {
+        if self.pending_include.take().is_some() {
+            return Some(String::from(".include"));
+        }
+        let block = self.pending_block.take()?;
+        self.open_blocks -= 1;
+        Some(block.directive)
     }
 
     fn handle_empty_line(&mut self, _: Tokens) -> Result<(), AppError> {
@@ -144,6 +400,9 @@ impl Assembler {
     }
 
     fn handle_set_location_counter(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        if self.relocatable {
+            return Err(AppError::NotRelocatable(String::from("setting location counter")));
+        }
         let str = tokens.operand().ok_or(AppError::MissingOperand)?;
         let operand = self.resolver.resolve(str, false)?;
         self.set_location_counter(operand.value as u16)
@@ -151,13 +410,248 @@ impl Assembler {
 
     fn handle_emit_bytes(&mut self, tokens: Tokens) -> Result<(), AppError> {
         let values = self.parse_operand_list(tokens.operand())?;
-        values.iter().for_each(|v| self.emit_byte(*v as u8));
+        values.iter().for_each(|v| {
+            self.relocate(*v, 1);
+            self.emit_byte(v.value as u8)
+        });
         Ok(())
     }
 
     fn handle_emit_words(&mut self, tokens: Tokens) -> Result<(), AppError> {
         let values = self.parse_operand_list(tokens.operand())?;
-        values.iter().for_each(|v| self.emit_word(*v as u16));
+        values.iter().for_each(|v| {
+            self.relocate(*v, 2);
+            self.emit_word(v.value as u16)
+        });
+        Ok(())
+    }
+
+    fn handle_emit_dbytes(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let values = self.parse_operand_list(tokens.operand())?;
+        if values.iter().any(|v| v.reloc.is_some()) {
+            return Err(AppError::NotRelocatable(String::from("big endian address")));
+        }
+        values.iter().for_each(|v| self.emit_word((v.value as u16).swap_bytes()));
+        Ok(())
+    }
+
+    fn handle_emit_dwords(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let values = self.parse_operand_list(tokens.operand())?;
+        values.iter().for_each(|v| {
+            self.relocate(*v, 2);
+            self.emit_word(v.value as u16);
+            self.emit_word((v.value >> 16) as u16);
+        });
+        Ok(())
+    }
+
+    fn handle_emit_text(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let list = tokens.operand().ok_or(AppError::MissingOperand)?;
+        for item in operand::split_list(list) {
+            if item.starts_with('"') {
+                self.charmap.encode(item)?.iter().for_each(|b| self.emit_byte(*b));
+            } else {
+                let operand = self.resolver.resolve(item, self.generate_code)?;
+                self.relocate(operand, 1);
+                self.emit_byte(operand.value as u8);
+            }
+        }
+        if tokens.operation().is_some_and(|op| op.eq_ignore_ascii_case(".asciiz")) {
+            self.emit_byte(0);
+        }
+        Ok(())
+    }
+
+    fn handle_charmap(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let list = tokens.operand().ok_or(AppError::MissingOperand)?;
+        match operand::split_list(list).as_slice() {
+            [name] if name.starts_with('"') => {
+                self.charmap = Charmap::named(name.trim_matches('"'))?;
+                Ok(())
+            }
+            [code, value] => {
+                let code = self.resolver.resolve(code, true)?.value;
+                let value = self.resolver.resolve(value, true)?.value;
+                self.charmap.set(code as u8, value as u8);
+                Ok(())
+            }
+            _ => Err(AppError::SyntaxError(tokens.to_string())),
+        }
+    }
+
+    fn handle_fill(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let mut args = Vec::new();
+        for opstr in operand::split_list(tokens.operand().ok_or(AppError::MissingOperand)?) {
+            args.push(self.resolver.resolve(opstr, true)?.value);
+        }
+        let (count, fill) = match args.as_slice() {
+            [count] => (*count, 0),
+            [count, fill] => (*count, *fill),
+            _ => return Err(AppError::SyntaxError(tokens.to_string())),
+        };
+        let count = match tokens.operation() {
+            Some(op) if op.eq_ignore_ascii_case(".align") => {
+                if count <= 0 {
+                    return Err(AppError::InvalidCount(count));
+                }
+                (count - self.location_counter() as i32 % count) % count
+            }
+            _ => count,
+        };
+        if count < 0 {
+            return Err(AppError::InvalidCount(count));
+        }
+        match tokens.operation() {
+            Some(op) if op.eq_ignore_ascii_case(".res") => {
+                self.segments[self.segment].reserve(count as usize, fill as u8, self.generate_code);
+            }
+            _ => (0..count).for_each(|_| self.emit_byte(fill as u8)),
+        }
+        Ok(())
+    }
+
+    fn handle_define_constant(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let name = tokens.operation().ok_or(AppError::SyntaxError(tokens.to_string()))?;
+        let opstr = tokens.operand().ok_or(AppError::MissingOperand)?;
+        let operand = self.resolver.resolve(opstr, self.generate_code)?;
+        self.resolver.define_alias(name, operand)
+    }
+
+    fn handle_segment(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let name = tokens.operand().ok_or(AppError::MissingOperand)?.trim_matches('"');
+        let index = match self.segments.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.segments.push(Segment::new(name, DEFAULT_LOCATION_COUNTER));
+                self.segments.len() - 1
+            }
+        };
+        self.select_segment(index);
+        Ok(())
+    }
+
+    fn handle_begin_scope(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let name = tokens.operand().ok_or(AppError::MissingOperand)?;
+        if tokens.operation().is_some_and(|op| op.eq_ignore_ascii_case(".proc")) {
+            self.resolver.define_symbol(name, self.location_counter() as i32)?;
+        }
+        self.resolver.begin_scope(name);
+        self.open_scopes
+            .push((self.resolver.scope(), self.segment, self.location_counter()));
+        Ok(())
+    }
+
+    fn handle_end_scope(&mut self, _: Tokens) -> Result<(), AppError> {
+        self.resolver.end_scope()?;
+        if let Some((name, segment, address)) = self.open_scopes.pop() {
+            let size = match segment == self.segment {
+                true => self.location_counter().wrapping_sub(address),
+                false => 0,
+            };
+            self.debug_info.scopes.push(ScopeInfo { name, address, size });
+        }
+        Ok(())
+    }
+
+    fn handle_cpu(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        self.cpu = tokens.operand().ok_or(AppError::MissingOperand)?.trim_matches('"').parse()?;
+        Ok(())
+    }
+
+    /// File is processed after the directive line, see `process_lines`.
+    fn handle_include(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let name = tokens.operand().ok_or(AppError::MissingOperand)?.trim_matches('"');
+        self.pending_include = Some(String::from(name));
+        Ok(())
+    }
+
+    /// Exported values are taken in the final pass, when all symbols are known.
+    fn handle_export(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let list = tokens.operand().ok_or(AppError::MissingOperand)?;
+        let names: Vec<String> = self.op_list_separator.split(list).map(String::from).collect();
+        for name in names {
+            let operand = self.resolver.resolve(&name, self.generate_code)?;
+            let segment = match operand.reloc {
+                None => None,
+                Some((Target::Segment(segment), RelocKind::Word)) => Some(segment),
+                Some(_) => return Err(AppError::NotRelocatable(format!("export of {}", name))),
+            };
+            if self.generate_code {
+                self.exports.push(Export {
+                    name,
+                    value: operand.value as u16,
+                    segment,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_import(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let list = tokens.operand().ok_or(AppError::MissingOperand)?;
+        if !self.relocatable {
+            return Err(AppError::AbsoluteImport(String::from(list)));
+        }
+        for name in self.op_list_separator.split(list) {
+            self.resolver.import(name);
+        }
+        Ok(())
+    }
+
+    /// Lines up to the matching `.endrept` are processed after the directive line, see `process_block`.
+    fn handle_rept(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        self.begin_block(tokens.operation(), None);
+        let count = self
+            .resolver
+            .resolve(tokens.operand().ok_or(AppError::MissingOperand)?, self.generate_code)?
+            .value;
+        self.repeat_values(0..count)
+    }
+
+    /// Loop variable takes values from the first to the last one inclusive, it is not a symbol.
+    fn handle_for(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let operand = tokens.operand().ok_or(AppError::MissingOperand)?;
+        let (name, range) = operand.split_once('=').ok_or(AppError::SyntaxError(tokens.to_string()))?;
+        self.begin_block(tokens.operation(), Some(name.trim()));
+        let mut bounds = Vec::new();
+        for opstr in operand::split_list(range) {
+            bounds.push(self.resolver.resolve(opstr, self.generate_code)?.value);
+        }
+        match bounds.as_slice() {
+            [first, last] if last < first => self.repeat_values(0..0),
+            [first, last] => self.repeat_values(*first..last.saturating_add(1)),
+            _ => Err(AppError::SyntaxError(tokens.to_string())),
+        }
+    }
+
+    fn handle_end_block(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        match self.open_blocks.checked_sub(1) {
+            Some(open) => {
+                self.open_blocks = open;
+                Ok(())
+            }
+            None => Err(AppError::UnbalancedBlock(tokens.operation().unwrap_or_default().to_string())),
+        }
+    }
+
+    /// Block with no iterations is skipped when the directive fails.
+    fn begin_block(&mut self, directive: Option<&str>, variable: Option<&str>) {
+        self.open_blocks += 1;
+        self.pending_block = Some(Repeat {
+            directive: directive.unwrap_or_default().to_string(),
+            variable: variable.map(String::from),
+            values: 0..0,
+        });
+    }
+
+    fn repeat_values(&mut self, values: Range<i32>) -> Result<(), AppError> {
+        let count = values.end.saturating_sub(values.start);
+        if !(0..=MAX_REPEAT).contains(&count) {
+            return Err(AppError::InvalidCount(count));
+        }
+        if let Some(block) = self.pending_block.as_mut() {
+            block.values = values;
+        }
         Ok(())
     }
 
@@ -170,9 +664,91 @@ impl Assembler {
     }
 
     fn handle_relative(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let mnemonic = tokens.operation().ok_or(AppError::SyntaxError(tokens.to_string()))?;
+        let instruction = self.parse_instruction(mnemonic)?;
+        if self.long_branches && instruction != Bra {
+            return self.assemble_long_branch(instruction, tokens.operand());
+        }
         self.assemble(AddrMode::Relative, tokens)
     }
 
+    /// `jeq`, `jne`, ... are branches expanded when needed into inverted branch over `jmp`.
+    fn handle_long_branch(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let mnemonic = tokens.operation().ok_or(AppError::SyntaxError(tokens.to_string()))?;
+        let instruction = Instruction::parse(&format!("b{}", &mnemonic[1..]))?;
+        self.assemble_long_branch(instruction, tokens.operand())
+    }
+
+    /// `BBRn`/`BBSn zp,target` test a bit of zero page location, displacement is counted from the next instruction.
+    fn handle_zero_page_relative(&mut self, tokens: Tokens) -> Result<(), AppError> {
+        let mnemonic = tokens.operation().ok_or(AppError::SyntaxError(tokens.to_string()))?;
+        let instruction = self.parse_instruction(mnemonic)?;
+        let zp = self.prepare_operand(AddrMode::ZeroPage, tokens.operand())?;
+        if self.generate_code && !(0..=255).contains(&zp.value) {
+            return Err(AppError::OperandOutOfRange(zp.value));
+        }
+        let target = self
+            .resolver
+            .resolve(tokens.target().ok_or(AppError::MissingOperand)?, self.generate_code)?;
+        if self.generate_code && !self.is_local(&target) {
+            return Err(AppError::NotRelocatable(String::from("branch to another segment")));
+        }
+        let diff = match target.symbolic {
+            true => target.value - self.location_counter() as i32 - 3,
+            false => target.value,
+        };
+        let displacement = match self.generate_code {
+            true => i8::try_from(diff).map_err(|_| AppError::BranchTooFar(diff))?,
+            false => 0,
+        };
+        let opcode = find_opcode(self.cpu, instruction, AddrMode::ZeroPageRelative)?;
+        self.last_opcode = Some(opcode);
+        self.emit_byte(opcode);
+        self.relocate(zp, 1);
+        self.emit_byte(zp.value as u8);
+        self.emit_byte(displacement as u8);
+        Ok(())
+    }
+
+    /// Target not defined yet is assumed to be in range, so branches grow only when needed in the next passes.
+    /// Once expanded, the branch stays long in the following passes, so that passes can not oscillate.
+    fn assemble_long_branch(&mut self, instruction: Instruction, opstr: Option<&str>) -> Result<(), AppError> {
+        let index = self.long_branch;
+        self.long_branch += 1;
+        let opstr = opstr.ok_or(AppError::MissingOperand)?;
+        let operand = self.resolver.resolve(opstr, self.generate_code)?;
+        let diff = match (operand.symbolic, operand.defined) {
+            _ if !self.is_local(&operand) => i32::MAX,
+            (true, true) => operand.value - self.location_counter() as i32 - 2,
+            (true, false) => 0,
+            (false, _) => operand.value,
+        };
+        let displacement = i8::try_from(diff).ok().filter(|_| !self.expanded.contains(&index));
+        match displacement {
+            Some(displacement) => {
+                if displacement == 0 && operand.symbolic {
+                    self.warn(String::from("branch to the next instruction"));
+                }
+                let opcode = find_opcode(self.cpu, instruction, AddrMode::Relative)?;
+                self.last_opcode = Some(opcode);
+                self.emit_byte(opcode);
+                self.emit_byte(displacement as u8);
+            }
+            None if !operand.symbolic => return Err(AppError::BranchTooFar(diff)),
+            None => {
+                self.expanded.insert(index);
+                let opcode = find_opcode(self.cpu, inverted(instruction), AddrMode::Relative)?;
+                self.last_opcode = Some(opcode);
+                self.emit_byte(opcode);
+                self.emit_byte(JMP_SIZE);
+                self.emit_byte(find_opcode(self.cpu, Jmp, AddrMode::Absolute)?);
+                self.relocate(operand, 2);
+                self.emit_word(operand.value as u16);
+            }
+        }
+        Ok(())
+    }
+
     fn handle_absolute(&mut self, tokens: Tokens) -> Result<(), AppError> {
         self.assemble(AddrMode::Absolute, tokens)
     }
@@ -185,23 +761,38 @@ impl Assembler {
         self.assemble(AddrMode::AbsoluteY, tokens)
     }
 
+    /// `(zp)` of 65C02 is the zero page counterpart of `JMP (abs)`.
     fn handle_indirect(&mut self, tokens: Tokens) -> Result<(), AppError> {
         self.assemble(AddrMode::Indirect, tokens)
     }
 
+    /// `(zp,x)` is the zero page counterpart of 65C02 `JMP (abs,x)`.
     fn handle_indexed_indirect_x(&mut self, tokens: Tokens) -> Result<(), AppError> {
-        self.assemble(AddrMode::IndexedIndirectX, tokens)
+        self.assemble(AddrMode::AbsoluteIndexedIndirect, tokens)
     }
 
     fn handle_indirect_indexed_y(&mut self, tokens: Tokens) -> Result<(), AppError> {
         self.assemble(AddrMode::IndirectIndexedY, tokens)
     }
 
-    fn emit_byte(&mut self, byte: u8) {
-        if self.generate_code {
-            self.code.push(byte);
+    /// Records relocation of the operand about to be emitted, full address in a single byte needs zero page target.
+    fn relocate(&mut self, operand: Operand, len: u8) {
+        if let (Some((target, kind)), true) = (operand.reloc, self.generate_code) {
+            let kind = match (kind, len) {
+                (RelocKind::Word, 1) => RelocKind::Byte,
+                (kind, _) => kind,
+            };
+            self.relocations.push(Relocation {
+                segment: self.segment,
+                offset: self.code_len(),
+                kind,
+                target,
+            });
         }
-        self.location_counter = self.location_counter.wrapping_add(1);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        self.segments[self.segment].emit(byte, self.generate_code);
     }
 
     fn emit_word(&mut self, word: u16) {
@@ -210,46 +801,393 @@ impl Assembler {
     }
 
     pub fn set_location_counter(&mut self, addr: u16) -> Result<(), AppError> {
-        if self.origin.is_none() {
-            self.origin = Some(addr);
-            self.location_counter = addr;
-            Ok(())
-        } else if addr >= self.location_counter {
-            let lc = self.location_counter;
-            self.location_counter = addr;
-            if self.generate_code {
-                for _ in lc..self.location_counter {
-                    self.code.push(0)
+        self.segments[self.segment].set_location_counter(addr, self.generate_code)
+    }
+
+    pub fn set_max_errors(&mut self, max_errors: usize) {
+        self.max_errors = max_errors;
+    }
+
+    /// Out of range conditional branches are expanded like `jeq`, `jne`, ... pseudo instructions.
+    pub fn set_long_branches(&mut self, long_branches: bool) {
+        self.long_branches = long_branches;
+    }
+
+    /// Labels which are never referenced are reported as warnings.
+    pub fn set_warn_unused(&mut self, warn_unused: bool) {
+        self.warn_unused = warn_unused;
+    }
+
+    /// Constant defined at the beginning of each pass, as if it was defined in the source.
+    pub fn define(&mut self, name: &str, value: i32) {
+        self.predefined.retain(|(n, _)| n != name);
+        self.predefined.push((String::from(name), value));
+    }
+
+    /// Virtual file used by `.include`, it takes precedence over files in the include directory.
+    pub fn add_include(&mut self, name: &str, text: &str) {
+        self.includes.insert(String::from(name), String::from(text));
+    }
+
+    /// Directory `.include` looks for files not added with `add_include`, there is no file system access without it.
+    pub fn set_include_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.include_dir = Some(dir.as_ref().to_path_buf());
+    }
+
+    /// Assembles relocatable object, see `Assembly::object`. Segments start at 0 and can not set location counter.
+    pub fn set_relocatable(&mut self, relocatable: bool) {
+        self.relocatable = relocatable;
+    }
+
+    /// Processor assumed at the beginning of each pass, `.cpu` directive changes it until the end of the pass.
+    pub fn set_cpu(&mut self, cpu: CpuVariant) {
+        self.default_cpu = cpu;
+    }
+
+    fn warn(&mut self, message: String) {
+        if self.generate_code {
+            self.warnings.push((message, self.span));
+        }
+    }
+
+    fn report(&mut self, severity: Severity, message: String, line: usize, source: &str, span: (usize, usize)) {
+        self.report_in(self.file, severity, message, line, source, span);
+    }
+
+    fn report_in(&mut self, file: usize, severity: Severity, message: String, line: usize, source: &str, span: (usize, usize)) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            message,
+            file: self.debug_info.files.get(file).cloned().unwrap_or_default(),
+            line,
+            column: span.0,
+            len: span.1,
+            source: String::from(source),
+        });
+    }
+
+    fn error_count(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.is_error()).count()
+    }
+
+    /// Errors do not stop processing until their number reaches the limit, the failing line emits no code.
+    fn process_file(&mut self, generate_code: bool, strbuf: &str) {
+        self.init_pass(generate_code);
+        self.process_lines(strbuf);
+        let lines: Vec<&str> = strbuf.lines().collect();
+        let r = &self.resolver;
+        self.listing.set_xref(r.symbols(), r.definitions(), r.references());
+        if self.resolver.in_scope() {
+            let last = lines.last().copied().unwrap_or_default();
+            let message = AppError::UnbalancedScope.to_string();
+            self.report(Severity::Error, message, lines.len(), last, statement_span(last));
+        }
+        if generate_code && self.warn_unused {
+            self.warn_unused_labels(&lines);
+            self.diagnostics.sort_by_key(|d| d.line);
+        }
+    }
+
+    /// Lines of included files are attributed to the `.include` line in the symbol cross-reference and listing.
+    fn process_lines(&mut self, text: &str) {
+        let lines: Vec<&str> = text.lines().collect();
+        self.process_block(&lines, 0);
+    }
+
+    /// Lines of the block are numbered from `first + 1`, bodies of `.rept` and `.for` are processed recursively.
+    fn process_block(&mut self, lines: &[&str], first: usize) {
+        let mut index = 0;
+        while index < lines.len() {
+            let (num, line) = (first + index, lines[index]);
+            if self.error_count() >= self.max_errors {
+                break;
+            }
+            if self.include_depth == 0 {
+                self.main_line = num + 1;
+                self.resolver.set_line(num + 1);
+            }
+            self.resolver.set_site(self.file, num + 1);
+            let (segment, offset, address) = (self.segment, self.code_len(), self.location_counter());
+            if let Err(err) = self.process_line(line) {
+                let span = error_span(line, &err).or(self.span).unwrap_or_else(|| statement_span(line));
+                self.report(Severity::Error, err.to_string(), num + 1, line, span);
+            }
+            for (message, span) in std::mem::take(&mut self.warnings) {
+                self.report(
+                    Severity::Warning,
+                    message,
+                    num + 1,
+                    line,
+                    span.unwrap_or_else(|| statement_span(line)),
+                );
+            }
+            self.list_line(num + 1, line, segment, offset, address);
+            if let Some(name) = self.pending_include.take() {
+                if let Err(err) = self.include(&name) {
+                    let span = find_span(line, &name).unwrap_or_else(|| statement_span(line));
+                    self.report(Severity::Error, err.to_string(), num + 1, line, span);
                 }
             }
-            Ok(())
-        } else {
-            Err(AppError::OriginTooLow(addr, self.location_counter))
+            if let Some(block) = self.pending_block.take() {
+                let body = &lines[index + 1..];
+                match self.block_len(body) {
+                    Some(len) => {
+                        self.repeat(&body[..len], num + 1, block);
+                        index += len;
+                    }
+                    None => {
+                        let message = AppError::UnbalancedBlock(block.directive).to_string();
+                        self.report(Severity::Error, message, num + 1, line, statement_span(line));
+                        self.open_blocks -= 1;
+                        index = lines.len();
+                    }
+                }
+            }
+            index += 1;
         }
     }
 
-    fn process_file(&mut self, generate_code: bool, strbuf: &String) -> Result<(), AppError> {
-        self.init_pass(generate_code);
-        for (num, line) in strbuf.lines().enumerate() {
-            self.process_line(line).map_err(|e| AppError::AsmLineError(num + 1, Box::from(e)))?;
+    /// Number of lines up to the end of the block, nested blocks included.
+    fn block_len(&self, lines: &[&str]) -> Option<usize> {
+        let mut depth = 0;
+        lines.iter().position(|line| {
+            if self.block_begin.is_match(line) {
+                depth += 1;
+            } else if self.block_end.is_match(line) {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            false
+        })
+    }
+
+    /// Loop variable shadows outer one of the same name only within the block.
+    fn repeat(&mut self, body: &[&str], first: usize, block: Repeat) {
+        let outer = block.variable.as_ref().and_then(|name| self.resolver.variable(name));
+        self.loop_depth += 1;
+        for value in block.values {
+            if let Some(name) = &block.variable {
+                self.resolver.set_variable(name, Some(value));
+            }
+            self.process_block(body, first);
+            if self.error_count() >= self.max_errors {
+                break;
+            }
         }
+        self.loop_depth -= 1;
+        if let Some(name) = &block.variable {
+            self.resolver.set_variable(name, outer);
+        }
+    }
+
+    /// Files included from an included file are looked up relative to its directory.
+    fn include(&mut self, name: &str) -> Result<(), AppError> {
+        if self.include_depth == MAX_INCLUDE_DEPTH {
+            return Err(AppError::IncludeTooDeep(String::from(name)));
+        }
+        let parent = match self.include_depth {
+            0 => None,
+            _ => self.debug_info.files.get(self.file).and_then(|f| Path::new(f).parent()),
+        };
+        let name = &parent.map_or_else(|| String::from(name), |p| p.join(name).to_string_lossy().to_string());
+        let not_found = || AppError::IncludeNotFound(name.clone());
+        let text = match (self.includes.get(name), &self.include_dir) {
+            (Some(text), _) => text.clone(),
+            (None, Some(dir)) => {
+                let text = fs::read_to_string(dir.join(name)).map_err(|e| match e.kind() {
+                    std::io::ErrorKind::NotFound => not_found(),
+                    _ => AppError::from(e),
+                })?;
+                self.includes.insert(name.clone(), text.clone());
+                text
+            }
+            (None, None) => return Err(not_found()),
+        };
+        let file = match self.debug_info.files.iter().position(|f| f == name) {
+            Some(index) => index,
+            None => {
+                self.debug_info.files.push(String::from(name));
+                self.debug_info.files.len() - 1
+            }
+        };
+        let parent = std::mem::replace(&mut self.file, file);
+        self.include_depth += 1;
+        self.process_lines(&text);
+        self.include_depth -= 1;
+        self.file = parent;
         Ok(())
     }
+
+    /// Exported labels and names of `.proc` scopes are entry points, they are not reported.
+    fn warn_unused_labels(&mut self, lines: &[&str]) {
+        let references = self.resolver.references();
+        let sites = self.resolver.sites();
+        let entry = |name: &str| self.exports.iter().any(|e| e.name == name) || self.debug_info.scopes.iter().any(|s| s.name == name);
+        let mut unused: Vec<((usize, usize), String)> = self
+            .resolver
+            .symbol_table(&self.debug_info.files)
+            .into_iter()
+            .filter(|s| s.kind != SymbolKind::Constant && !references.contains_key(&s.name) && !entry(&s.name))
+            .filter_map(|s| sites.get(&s.name).filter(|(_, line)| *line > 0).map(|site| (*site, s.name)))
+            .collect();
+        unused.sort();
+        for ((file, line), name) in unused {
+            let text = match file {
+                0 => None,
+                _ => self.debug_info.files.get(file).and_then(|f| self.includes.get(f)).cloned(),
+            };
+            let source = match &text {
+                Some(text) => text.lines().nth(line - 1).unwrap_or_default(),
+                None => lines[line - 1],
+            };
+            let short = name.rsplit([':', '.']).next().unwrap_or(&name);
+            let span = find_span(source, short).unwrap_or_else(|| statement_span(source));
+            let message = format!("unused label: {}", name);
+            self.report_in(file, Severity::Warning, message, line, source, span);
+        }
+    }
+
+    /// Repeats passes until symbol values stabilise, then generates code in the final pass.
+    fn process_source(&mut self, src: &str) -> Result<(), AppError> {
+        if self.debug_info.files.is_empty() {
+            self.debug_info.files.push(String::new());
+        }
+        let mut passes = 0;
+        loop {
+            let symbols = self.resolver.symbols().clone();
+            self.process_file(false, src);
+            passes += 1;
+            match first_change(&symbols, self.resolver.symbols()) {
+                None => break,
+                Some(name) if passes == MAX_PASSES => return Err(AppError::PhaseError(name)),
+                Some(_) => {}
+            }
+        }
+        self.process_file(true, src);
+        if self.error_count() > 0 {
+            return Err(AppError::AsmErrors(std::mem::take(&mut self.diagnostics)));
+        }
+        match self.resolver.changed() {
+            Some(name) => Err(AppError::PhaseError(name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn code_len(&self) -> usize {
+        self.segments[self.segment].code.len()
+    }
+
+    fn list_line(&mut self, num: usize, source: &str, segment: usize, offset: usize, address: u16) {
+        let opcode = self.last_opcode.take();
+        if !self.generate_code {
+            return;
+        }
+        let (address, bytes) = match segment == self.segment {
+            true if self.code_len() > offset => (address, self.segments[segment].code[offset..].to_vec()),
+            _ => (self.location_counter(), Vec::new()),
+        };
+        if !bytes.is_empty() {
+            self.debug_info.lines.push(LineInfo {
+                address,
+                size: bytes.len() as u16,
+                file: self.file,
+                line: num,
+            });
+        }
+        self.listing.lines.push(listing::ListingLine {
+            line: self.main_line,
+            address,
+            bytes,
+            opcode,
+            cpu: self.cpu,
+            source: String::from(source),
+            expanded: self.include_depth > 0 || self.loop_depth > 0,
+        });
+    }
+}
+
+/// Span of the text the error refers to, if it can be found in the line.
+fn error_span(line: &str, err: &AppError) -> Option<(usize, usize)> {
+    match err {
+        AppError::UndefinedSymbol(text)
+        | AppError::InvalidMnemonic(text)
+        | AppError::InvalidString(text)
+        | AppError::InvalidCharmap(text)
+        | AppError::ParseIntError(text, _) => find_span(line, text),
+        AppError::SyntaxError(_) => Some(statement_span(line)),
+        _ => None,
+    }
+}
+
+fn inverted(branch: Instruction) -> Instruction {
+    match branch {
+        Bcc => Bcs,
+        Bcs => Bcc,
+        Bne => Beq,
+        Beq => Bne,
+        Bmi => Bpl,
+        Bpl => Bmi,
+        Bvc => Bvs,
+        Bvs => Bvc,
+        other => other,
+    }
 }
 
-fn optimize_addrmode(instruction: Instruction, addrmode: AddrMode, operand: Operand) -> AddrMode {
-    if addrmode == Implied || instruction == Jsr || instruction == Jmp || operand.symbolic {
-        addrmode
-    } else {
-        addrmode.optimized(operand.value)
+/// Splits `.z` (zero page) or `.a` (absolute) size suffix off the mnemonic.
+fn split_size(mnemonic: &str) -> (&str, OperandSize) {
+    match mnemonic.split_once(SIZE_SUFFIX) {
+        Some((mnemonic, size)) if size.eq_ignore_ascii_case("z") => (mnemonic, OperandSize::ZeroPage),
+        Some((mnemonic, _)) => (mnemonic, OperandSize::Absolute),
+        None => (mnemonic, OperandSize::Auto),
     }
 }
 
-pub fn assemble_file<F: AsRef<Path>>(fname: F) -> Result<(u16, Vec<u8>, HashMap<String, i32>), AppError> {
+/// Zero page is chosen automatically for known values and when such opcode exists,
+/// symbols not defined yet are assumed to be absolute until the next pass unless the instruction has no such mode.
+/// Relocatable addresses are absolute unless zero page is requested.
+fn optimize_addrmode(cpu: CpuVariant, instruction: Instruction, addrmode: AddrMode, operand: Operand, size: OperandSize) -> AddrMode {
+    let fits = operand.defined && operand.reloc.is_none() && addrmode.optimized(operand.value) != addrmode;
+    match size {
+        _ if addrmode == Implied => addrmode,
+        OperandSize::Absolute => addrmode,
+        OperandSize::ZeroPage => addrmode.zero_page(),
+        OperandSize::Auto => match addrmode.zero_page() {
+            zero_page if (fits || find_opcode(cpu, instruction, addrmode).is_err()) && find_opcode(cpu, instruction, zero_page).is_ok() => {
+                zero_page
+            }
+            _ => addrmode,
+        },
+    }
+}
+
+/// Name of the first symbol, which value differs between two symbol tables.
+fn first_change(old: &HashMap<String, i32>, new: &HashMap<String, i32>) -> Option<String> {
+    let mut changed: Vec<&String> = new.iter().filter(|(k, v)| old.get(*k) != Some(v)).map(|(k, _)| k).collect();
+    changed.sort();
+    changed.first().map(|name| String::from(*name))
+}
+
+/// With `relocatable` the result holds an object for the linker, see `Assembly::object`.
+pub fn assemble_file<F: AsRef<Path>>(
+    fname: F,
+    max_errors: usize,
+    long_branches: bool,
+    warn_unused: bool,
+    cpu: CpuVariant,
+    relocatable: bool,
+) -> Result<Assembly, AppError> {
     let mut src = String::new();
     File::open(&fname)?.read_to_string(&mut src)?;
-    let mut asm = Assembler::new();
-    asm.process_file(false, &src)?;
-    asm.process_file(true, &src)?;
-    Ok((asm.origin(), asm.code().to_vec(), asm.symbols().clone()))
+    let fname = fname.as_ref();
+    Assembler::builder()
+        .source(&fname.to_string_lossy(), &src)
+        .include_dir(fname.parent().unwrap_or_else(|| Path::new("")))
+        .max_errors(max_errors)
+        .long_branches(long_branches)
+        .warn_unused(warn_unused)
+        .cpu(cpu)
+        .relocatable(relocatable)
+        .assemble()
 }
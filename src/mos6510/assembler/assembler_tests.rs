@@ -1,10 +1,25 @@
 use super::*;
+use std::collections::HashMap;
+
+impl Assembler {
+    pub fn code(&self) -> &Vec<u8> {
+        &self.segments[self.segment].code
+    }
+
+    pub fn origin(&self) -> u16 {
+        self.segments[self.segment].start()
+    }
+
+    pub fn symbols(&self) -> &HashMap<String, i32> {
+        self.resolver.symbols()
+    }
+}
 
 fn assert_next(asm: &mut Assembler, line: &str, expected: &[u8]) {
     let r = asm.process_line(line);
     assert!(r.is_ok(), "line \"{}\" : {:?}", line, r);
-    assert!(asm.code.len() >= expected.len(), "line \"{}\" : code too short", line);
-    let generated = &asm.code[(asm.code.len() - expected.len())..];
+    assert!(asm.code().len() >= expected.len(), "line \"{}\" : code too short", line);
+    let generated = &asm.code()[(asm.code().len() - expected.len())..];
     assert_eq!(generated, expected, "generated code {:?} differs from {:?}", generated, expected);
 }
 
@@ -19,7 +34,7 @@ fn assert_asm(line: &str, code: &[u8]) -> Assembler {
 fn init() {
     let asm = Assembler::new();
     assert_eq!(asm.generate_code, false);
-    assert_eq!(asm.location_counter, 0);
+    assert_eq!(asm.location_counter(), 0);
     assert_eq!(asm.resolver.symbols().len(), 0);
 }
 
@@ -99,7 +114,7 @@ fn scanning_phase_addmode_optimization_for_undefined_symbols() {
     let mut asm = Assembler::new();
     assert!(!asm.generate_code);
     assert!(asm.process_line("LDA etykieta,X").is_ok());
-    assert_eq!(asm.location_counter, 3);
+    assert_eq!(asm.location_counter(), 3);
 }
 
 #[test]
@@ -133,24 +148,24 @@ fn relative_mode_with_label() {
     let mut asm = assert_asm(".org $0607", &[]);
     assert!(asm.resolver.define_symbol("before", 0x0600).is_ok());
     assert!(asm.resolver.define_symbol("after", 0x0613).is_ok());
-    assert_eq!(asm.location_counter, 0x607);
+    assert_eq!(asm.location_counter(), 0x607);
     assert_next(&mut asm, "BEQ after", &[0xF0, 0x0A]);
-    assert_eq!(asm.location_counter, 0x0609);
+    assert_eq!(asm.location_counter(), 0x0609);
     assert_next(&mut asm, "BCC before", &[0x90, u8::from_ne_bytes((-11 as i8).to_ne_bytes())]);
-    assert_eq!(asm.location_counter, 0x060B);
+    assert_eq!(asm.location_counter(), 0x060B);
 }
 
 #[test]
 fn set_location_counter() {
     let mut asm = assert_asm("  .ORG $3000 ;origin", &[]);
-    assert_eq!(asm.origin.unwrap(), 0x3000);
-    assert_eq!(asm.location_counter, 0x3000);
+    assert_eq!(asm.origin(), 0x3000);
+    assert_eq!(asm.location_counter(), 0x3000);
     assert_next(&mut asm, "  .ORG $4000 ;origin", &[]);
-    assert_eq!(asm.location_counter, 0x4000);
-    assert_eq!(asm.code.len(), 0x1000);
+    assert_eq!(asm.location_counter(), 0x4000);
+    assert_eq!(asm.code().len(), 0x1000);
     assert_next(&mut asm, "  *= $5000 ;origin", &[]);
-    assert_eq!(asm.location_counter, 0x5000);
-    assert_eq!(asm.code.len(), 0x2000);
+    assert_eq!(asm.location_counter(), 0x5000);
+    assert_eq!(asm.code().len(), 0x2000);
 }
 
 #[test]
@@ -180,8 +195,8 @@ fn test_symbols() {
     assert_next(&mut asm, "c:lda dziabaDucha", &[0xad, 0x02, 0xaf]);
     assert_eq!(asm.resolver.symbols().get("TestLabel_01").unwrap(), &1000);
     assert_eq!(asm.resolver.symbols().get("TestLabel_02"), None);
-    assert_eq!(asm.code.len(), 4);
-    assert_eq!(asm.location_counter, 1004);
+    assert_eq!(asm.code().len(), 4);
+    assert_eq!(asm.location_counter(), 1004);
 }
 
 #[test]
@@ -226,5 +241,467 @@ fn define_symbol() {
     let mut asm = assert_asm(".org $1000", &[]);
     assert!(asm.resolver.define_symbol("init", 0x1234).is_ok());
     assert_next(&mut asm, "lda init", &[0xad, 0x34, 0x12]);
-    assert_eq!(asm.location_counter, 0x1003);
+    assert_eq!(asm.location_counter(), 0x1003);
+}
+
+fn assemble_source(src: &str) -> Assembler {
+    let mut asm = Assembler::new();
+    let r = asm.process_source(src);
+    assert!(r.is_ok(), "{:?}", r);
+    asm
+}
+
+#[test]
+fn local_labels() {
+    let asm =
+        assemble_source("  .org $0600\ndraw: ldx #0\n.loop: inx\n  bne .loop\nscroll: ldy #0\n@loop: iny\n  bne @loop\n  jmp draw.loop");
+    assert_eq!(
+        asm.code(),
+        &[0xa2, 0, 0xe8, 0xd0, 0xfd, 0xa0, 0, 0xc8, 0xd0, 0xfd, 0x4c, 0x02, 0x06]
+    );
+    assert_eq!(asm.symbols().get("draw.loop"), Some(&0x0602));
+    assert_eq!(asm.symbols().get("scroll.loop"), Some(&0x0607));
+}
+
+#[test]
+fn anonymous_labels() {
+    let asm = assemble_source("  .org $0600\n- inx\n  beq +\n  bne -\n+ rts");
+    assert_eq!(asm.code(), &[0xe8, 0xf0, 0x02, 0xd0, 0xfb, 0x60]);
+}
+
+#[test]
+fn scoped_labels() {
+    let asm = assemble_source("  .org $0600\n  jsr sprite::init\n  rts\n.proc sprite\ninit: lda #1\n  jmp init\n.endproc\ninit: rts");
+    assert_eq!(asm.code(), &[0x20, 0x04, 0x06, 0x60, 0xa9, 1, 0x4c, 0x04, 0x06, 0x60]);
+    assert_eq!(asm.symbols().get("sprite"), Some(&0x0604));
+    assert_eq!(asm.symbols().get("sprite::init"), Some(&0x0604));
+    assert_eq!(asm.symbols().get("init"), Some(&0x0609));
+}
+
+#[test]
+fn unbalanced_scope() {
+    let mut asm = Assembler::new();
+    match asm.process_source(".scope gfx") {
+        Err(AppError::AsmErrors(d)) => assert_eq!((d[0].line, d[0].message.as_str()), (1, "unbalanced scope")),
+        _ => panic!("unbalanced scope expected"),
+    }
+    match asm.process_source("  nop\n  .endscope") {
+        Err(AppError::AsmErrors(d)) => assert_eq!((d[0].line, d[0].column, d[0].len), (2, 3, 9)),
+        _ => panic!("unbalanced scope expected"),
+    }
+}
+
+#[test]
+fn emit_text() {
+    assert_asm(".text \"AB;c\", 13, \"\\x00\"", &[0x41, 0x42, 0x3b, 0x63, 13, 0]);
+    assert_asm(".asciiz \"hi\"", &[b'h', b'i', 0]);
+}
+
+#[test]
+fn charmap() {
+    let mut asm = assert_asm(".charmap \"screen\"", &[]);
+    assert_next(&mut asm, ".text \"Az\"", &[0x41, 0x1a]);
+    assert_next(&mut asm, ".charmap $7a, $ff", &[]);
+    assert_next(&mut asm, ".text \"Az\"", &[0x41, 0xff]);
+    assert_next(&mut asm, ".charmap \"petscii\"", &[]);
+    assert_next(&mut asm, ".asciiz \"Az\"", &[0xc1, 0x5a, 0]);
+}
+
+#[test]
+fn emit_dbytes_and_dwords() {
+    assert_asm(".dbyte $1234, 2", &[0x12, 0x34, 0x00, 0x02]);
+    assert_asm(".dword $12345678 $ffffffff", &[0x78, 0x56, 0x34, 0x12, 0xff, 0xff, 0xff, 0xff]);
+}
+
+#[test]
+fn fill_and_reserve() {
+    assert_asm(".fill 3, $ea", &[0xea, 0xea, 0xea]);
+    let mut asm = assert_asm(".res 2", &[0, 0]);
+    assert_eq!(asm.location_counter(), 2);
+    assert!(asm.resolver.define_symbol("size", 4).is_ok());
+    assert_next(&mut asm, ".fill size $ff", &[0xff, 0xff, 0xff, 0xff]);
+    assert!(matches!(asm.process_line(".fill later"), Err(AppError::UndefinedSymbol(_))));
+}
+
+#[test]
+fn align() {
+    let mut asm = assert_asm(".org $0601", &[]);
+    assert_next(&mut asm, ".align 4, $ff", &[0xff, 0xff, 0xff]);
+    assert_eq!(asm.location_counter(), 0x0604);
+    assert_next(&mut asm, ".align $100", &[]);
+    assert_eq!(asm.location_counter(), 0x0700);
+    assert_next(&mut asm, ".align $100", &[]);
+    assert_eq!(asm.location_counter(), 0x0700);
+    assert!(matches!(asm.process_line(".align 0"), Err(AppError::InvalidCount(0))));
+}
+
+#[test]
+fn segments() {
+    let asm = assemble_source(
+        ".segment \"ZP\"\n.org $10\nptr: .res 2\n.segment \"CODE\"\n.org $0600\nreset: lda ptr\n  rts\n\
+         .segment \"VECTORS\"\n.org $fffa\n.word reset, reset, reset\n.segment \"CODE\"\n  rts",
+    );
+    let segments = asm.segments();
+    assert_eq!(segments.len(), 3);
+    assert_eq!(
+        (segments[0].name.as_str(), segments[0].start(), segments[0].is_initialized()),
+        ("CODE", 0x0600, true)
+    );
+    assert_eq!(segments[0].code, &[0xa5, 0x10, 0x60, 0x60]);
+    assert_eq!(
+        (segments[1].name.as_str(), segments[1].start(), segments[1].is_initialized()),
+        ("ZP", 0x10, false)
+    );
+    assert_eq!(segments[2].start(), 0xfffa);
+    assert_eq!(segments[2].code, &[0x00, 0x06, 0x00, 0x06, 0x00, 0x06]);
+    assert_eq!(asm.symbols().get("ptr"), Some(&0x10));
+}
+
+#[test]
+fn listing() {
+    let asm = assemble_source("  .org $0600\nstart: lda $200,x\n\n  .segment \"DATA\"\n  .byte 1, 2\n  jmp start");
+    let lines = &asm.listing.lines;
+    assert_eq!(lines.len(), 6);
+    assert_eq!((lines[1].line, lines[1].address, lines[1].opcode), (2, 0x0600, Some(0xbd)));
+    assert_eq!(lines[1].bytes, &[0xbd, 0x00, 0x02]);
+    assert_eq!((lines[3].address, lines[3].bytes.len()), (0, 0));
+    assert_eq!((lines[4].address, lines[4].opcode), (0, None));
+    assert_eq!(lines[4].bytes, &[1, 2]);
+    assert_eq!(lines[5].address, 2);
+    assert_eq!((asm.segments()[1].start(), asm.segments()[1].end()), (0, 5));
+    let start = asm.listing.xref.iter().find(|x| x.name == "start").unwrap();
+    assert_eq!((start.value, start.defined), (0x0600, Some(2)));
+    assert_eq!(start.used, &[6]);
+}
+
+#[test]
+fn constants() {
+    let asm = assemble_source("  .org $0600\nSCREEN = $0200\nmessage .equ end\n  lda #<message\n  sta SCREEN\nend: rts");
+    assert_eq!(asm.code(), &[0xa9, 0x05, 0x8d, 0x00, 0x02, 0x60]);
+    assert_eq!(asm.symbols().get("SCREEN"), Some(&0x0200));
+    assert_eq!(asm.symbols().get("message"), Some(&0x0605));
+    assert!(asm.resolver.changed().is_none());
+    let asm = assemble_source("  .org $0600\n  lda #<message\nmessage = end\nend: rts");
+    assert_eq!(asm.code(), &[0xa9, 0x02, 0x60]);
+}
+
+#[test]
+fn debug_info() {
+    let asm = assemble_source("  .org $0600\n.proc main\n  lda #1\n\n.scope inner\n  sta $0200\n.endscope\n.endproc\n  rts");
+    let info = &asm.debug_info;
+    assert_eq!(info.line_at(0x0601).map(|l| l.line), Some(3));
+    assert_eq!(info.line_at(0x0604).map(|l| (l.address, l.size, l.line)), Some((0x0602, 3, 6)));
+    assert_eq!(info.line_at(0x0605).map(|l| l.line), Some(9));
+    assert_eq!(info.scope_at(0x0602).map(|s| s.name.as_str()), Some("main::inner"));
+    assert_eq!(info.scope_at(0x0600).map(|s| (s.name.as_str(), s.size)), Some(("main", 5)));
+    assert!(info.scope_at(0x0605).is_none());
+}
+
+#[test]
+fn zero_page_symbols() {
+    let asm = assemble_source("  .org $0600\n  lda ptr\n  sta ptr,x\n  lda ptr,y\n  jmp end\nend: rts\nptr = $10");
+    assert_eq!(asm.code(), &[0xa5, 0x10, 0x95, 0x10, 0xb9, 0x10, 0x00, 0x4c, 0x0a, 0x06, 0x60]);
+    assert_eq!(asm.symbols().get("end"), Some(&0x060a));
+}
+
+#[test]
+fn operand_size_overrides() {
+    let asm = assemble_source("ptr = $10\nvec = $1234\n  lda.a ptr\n  lda !ptr,x\n  LDA.Z $0012\n  lda <vec\n  lda.z >vec");
+    assert_eq!(
+        asm.code(),
+        &[0xad, 0x10, 0x00, 0xbd, 0x10, 0x00, 0xa5, 0x12, 0xa5, 0x34, 0xa5, 0x12]
+    );
+    let mut asm = Assembler::new();
+    match asm.process_source("  lda.z vec\nvec = $1234") {
+        Err(AppError::AsmErrors(d)) => assert_eq!(d[0].message, AppError::OperandOutOfRange(0x1234).to_string()),
+        _ => panic!("operand out of range expected"),
+    }
+}
+
+fn diagnostics(src: &str, max_errors: usize) -> Vec<Diagnostic> {
+    let mut asm = Assembler::new();
+    asm.set_max_errors(max_errors);
+    asm.set_warn_unused(true);
+    match asm.process_source(src) {
+        Err(AppError::AsmErrors(diagnostics)) => diagnostics,
+        Ok(_) => asm.diagnostics,
+        Err(err) => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn collect_errors() {
+    let d = diagnostics("  lda foo\n  nop\n  xyz #1\n  lda #\n  sta bar,x", 20);
+    let found: Vec<(usize, usize, usize)> = d.iter().map(|d| (d.line, d.column, d.len)).collect();
+    assert_eq!(found, vec![(1, 7, 3), (3, 3, 3), (4, 3, 5), (5, 7, 3)]);
+    assert!(d.iter().all(Diagnostic::is_error));
+    assert_eq!(d[0].message, "undefined symbol: foo");
+    assert_eq!(d[0].source, "  lda foo");
+    assert_eq!(diagnostics("  lda foo\n  nop\n  xyz #1\n  lda #\n  sta bar,x", 2).len(), 2);
+}
+
+#[test]
+fn warnings() {
+    let d = diagnostics(
+        "  .org $0600\nstart: bne next\nnext: jmp ($02ff)\n  lda ptr,y\n  lda zp\n  jmp start\nzp = $20\nptr = $10",
+        20,
+    );
+    let found: Vec<(usize, Severity)> = d.iter().map(|d| (d.line, d.severity)).collect();
+    assert_eq!(
+        found,
+        vec![
+            (2, Severity::Warning),
+            (3, Severity::Warning),
+            (4, Severity::Warning),
+            (5, Severity::Warning)
+        ]
+    );
+    assert_eq!(d[0].message, "branch to the next instruction");
+    assert_eq!(
+        d[1].message,
+        "indirect jump vector at page boundary, high byte is fetched from $0200"
+    );
+    assert_eq!((d[2].column, d[3].column), (7, 7));
+    assert!(d[3].message.starts_with("forward reference"));
+    let d = diagnostics("  nop\nunused: rts", 20);
+    assert_eq!((d[0].line, d[0].message.as_str()), (2, "unused label: unused"));
+    assert!(diagnostics("start: nop\n  .proc print\n  rts\n  .endproc", 20)
+        .iter()
+        .all(|d| d.message != "unused label: print"));
+    let assembly = Assembler::builder()
+        .source("main.asm", "start: nop\nunused: rts")
+        .assemble()
+        .unwrap();
+    assert!(assembly.diagnostics.is_empty());
+    let assembly = Assembler::builder()
+        .source("main.asm", "  .org $0600\n  .include \"io.inc\"\n  jsr print")
+        .include("io.inc", "print: rts\nspare: rts")
+        .warn_unused(true)
+        .assemble()
+        .unwrap();
+    let found: Vec<(&str, usize, &str)> = assembly
+        .diagnostics
+        .iter()
+        .map(|d| (d.file.as_str(), d.line, d.message.as_str()))
+        .collect();
+    assert_eq!(found, &[("io.inc", 2, "unused label: spare")]);
+    assert_eq!(assembly.diagnostics[0].source, "spare: rts");
+}
+
+#[test]
+fn long_branches() {
+    let asm = assemble_source("  .org $0600\nstart: jeq start\n  jne far\n  jcs +5\n  .fill 200\nfar: jmi start\n  jvs far");
+    assert_eq!(&asm.code()[..7], &[0xf0, 0xfe, 0xf0, 0x03, 0x4c, 0xd1, 0x06]);
+    assert_eq!(&asm.code()[7..9], &[0xb0, 0x05]);
+    assert_eq!(asm.symbols().get("far"), Some(&0x06d1));
+    assert_eq!(&asm.code()[209..], &[0x10, 0x03, 0x4c, 0x00, 0x06, 0x70, 0xf9]);
+    // the fill shrinks below the branch range once the branch is expanded
+    let asm = assemble_source("  .org $0600\nstart: jne target\nmid: .fill 160 - (mid - start) * 10\ntarget: rts");
+    assert_eq!(&asm.code()[..5], &[0xf0, 0x03, 0x4c, 0x73, 0x06]);
+    assert_eq!(asm.code().len(), 5 + 110 + 1);
+    let mut asm = Assembler::new();
+    asm.set_long_branches(true);
+    assert!(asm
+        .process_source("  .org $0600\n  bcc far\n  bcc far\n  .fill 126\nfar: rts")
+        .is_ok());
+    assert_eq!(&asm.code()[..7], &[0xb0, 0x03, 0x4c, 0x85, 0x06, 0x90, 0x7e]);
+}
+
+#[test]
+fn cpu_variants() {
+    let src = "  .org $0600\n  .cpu \"65c02\"\nstart: stz $10\n  lda ($10)\n  jmp (table,x)\n  bra start\n  bbr0 $10,start\n  inc\n  smb7 $10\ntable: .cpu 6502x\n  lax $10\n  dcp $1234,x";
+    let asm = assemble_source(src);
+    assert_eq!(
+        asm.code(),
+        &[0x64, 0x10, 0xb2, 0x10, 0x7c, 0x0f, 0x06, 0x80, 0xf7, 0x0f, 0x10, 0xf4, 0x1a, 0xf7, 0x10, 0xa7, 0x10, 0xdf, 0x34, 0x12]
+    );
+    let errors = diagnostics("  stz $10\n  lda ($10)\n  lax $10\n  .cpu 65c02\n  lax $10\n  .cpu z80", 20);
+    let messages: Vec<&str> = errors.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        &[
+            "invalid mnemonic: stz",
+            "LDA does not support Indirect addressing mode",
+            "invalid mnemonic: lax",
+            "invalid mnemonic: lax",
+            "unsupported cpu: z80"
+        ]
+    );
+    let mut asm = Assembler::new();
+    asm.set_cpu(CpuVariant::Csg65CE02);
+    assert!(asm.process_source("  ldz #1\n  inw $20\n  phz").is_ok());
+    assert_eq!(asm.code(), &[0xa3, 0x01, 0xe3, 0x20, 0xdb]);
+}
+
+#[test]
+fn builder() {
+    let assembly = Assembler::builder()
+        .source(
+            "main.asm",
+            "  .org $0600\n  .include \"io.inc\"\n  stz BORDER\n  lda #COLOR\ndone: rts",
+        )
+        .include("io.inc", "BORDER = $d020\n  .include \"zp.inc\"\nstore: sta ptr")
+        .include("zp.inc", "ptr = $fb")
+        .define("COLOR", 2)
+        .cpu(CpuVariant::Wdc65C02)
+        .assemble()
+        .unwrap();
+    assert_eq!(assembly.segments[0].code, &[0x85, 0xfb, 0x9c, 0x20, 0xd0, 0xa9, 0x02, 0x60]);
+    let info = &assembly.debug_info;
+    assert_eq!(info.files, &["main.asm", "io.inc", "zp.inc"]);
+    assert_eq!(info.line_at(0x0600).map(|l| (l.file, l.line)), Some((1, 3)));
+    assert_eq!(info.line_at(0x0602).map(|l| (l.file, l.line)), Some((0, 3)));
+    let color = assembly.symbols.iter().find(|s| s.name == "COLOR").unwrap();
+    assert_eq!((color.value, color.line), (2, None));
+    let sites: Vec<(&str, Option<&str>, Option<usize>)> = ["store", "ptr", "done"]
+        .iter()
+        .map(|name| assembly.symbols.iter().find(|s| s.name == *name).unwrap())
+        .map(|s| (s.name.as_str(), s.file.as_deref(), s.line))
+        .collect();
+    assert_eq!(
+        sites,
+        &[
+            ("store", Some("io.inc"), Some(3)),
+            ("ptr", Some("zp.inc"), Some(1)),
+            ("done", Some("main.asm"), Some(5))
+        ]
+    );
+    let line = &assembly.listing.lines[5];
+    assert_eq!((line.line, line.expanded, line.source.as_str()), (2, true, "store: sta ptr"));
+}
+
+#[test]
+fn include_errors() {
+    let result = Assembler::builder()
+        .source("main.asm", "  .include \"a.inc\"\n  .include \"none.inc\"")
+        .include("a.inc", "  nop\n  lda #undefined\n  .include \"a.inc\"")
+        .max_errors(3)
+        .assemble();
+    let diagnostics = match result {
+        Err(AppError::AsmErrors(diagnostics)) => diagnostics,
+        _ => panic!("errors expected"),
+    };
+    let first = &diagnostics[0];
+    assert_eq!(
+        (first.file.as_str(), first.line, first.message.as_str()),
+        ("a.inc", 2, "undefined symbol: undefined")
+    );
+    assert_eq!(diagnostics.len(), 3);
+    assert!(Assembler::builder()
+        .source("main.asm", "  .include \"none.inc\"")
+        .assemble()
+        .is_err_and(|err| err.to_string().contains("include file not found: none.inc")));
+    let dir = std::env::temp_dir().join("mo65x-include-test");
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("lib").join("io.inc"), "  .include \"zp.inc\"\n  .include \"none.inc\"").unwrap();
+    std::fs::write(dir.join("lib").join("zp.inc"), "ptr = $fb").unwrap();
+    let result = Assembler::builder()
+        .source("main.asm", "  .include \"lib/io.inc\"\n  sta ptr")
+        .include_dir(&dir)
+        .assemble();
+    let diagnostics = match result {
+        Err(AppError::AsmErrors(diagnostics)) => diagnostics,
+        _ => panic!("errors expected"),
+    };
+    let found: Vec<(&str, usize, &str)> = diagnostics.iter().map(|d| (d.file.as_str(), d.line, d.message.as_str())).collect();
+    let missing = format!("include file not found: {}", Path::new("lib").join("none.inc").display());
+    assert_eq!(found, &[("lib/io.inc", 2, missing.as_str())]);
+}
+
+#[test]
+fn relocatable_object() {
+    let src = ".import far\n.export start, ptr\nstart: lda table,x\n- dex\n bne -\n jmp -\n jeq far\n \
+               lda (ptr),y\n lda #>table\n.segment \"ZP\"\nptr: .res 2\n.segment \"DATA\"\ntable: .byte <far\n .word start";
+    let object = Assembler::builder()
+        .source("obj.asm", src)
+        .relocatable(true)
+        .assemble()
+        .unwrap()
+        .object
+        .unwrap();
+    let names: Vec<(&str, bool)> = object.segments.iter().map(|s| (s.name.as_str(), s.reserved)).collect();
+    assert_eq!(names, &[("CODE", false), ("ZP", true), ("DATA", false)]);
+    #[rustfmt::skip]
+    assert_eq!(object.segments[0].data, &[
+        0xbd, 0x00, 0x00, 0xca, 0xd0, 0xfd, 0x4c, 0x03, 0x00,
+        0xd0, 0x03, 0x4c, 0x00, 0x00, 0xb1, 0x00, 0xa9, 0x00,
+    ]);
+    let relocations: Vec<(usize, usize, RelocKind, Target)> =
+        object.relocations.iter().map(|r| (r.segment, r.offset, r.kind, r.target)).collect();
+    assert_eq!(
+        relocations,
+        &[
+            (0, 1, RelocKind::Word, Target::Segment(2)),
+            (0, 7, RelocKind::Word, Target::Segment(0)),
+            (0, 12, RelocKind::Word, Target::Import(0)),
+            (0, 15, RelocKind::Byte, Target::Segment(1)),
+            (0, 17, RelocKind::High(0), Target::Segment(2)),
+            (2, 0, RelocKind::Low, Target::Import(0)),
+            (2, 1, RelocKind::Word, Target::Segment(0)),
+        ]
+    );
+    assert_eq!(object.imports, &["far"]);
+    let exports: Vec<(&str, u16, Option<usize>)> = object.exports.iter().map(|e| (e.name.as_str(), e.value, e.segment)).collect();
+    assert_eq!(exports, &[("start", 0, Some(0)), ("ptr", 0, Some(1))]);
+}
+
+#[test]
+fn relocatable_errors() {
+    let errors = |src: &str, relocatable: bool| match Assembler::builder().source("e.asm", src).relocatable(relocatable).assemble() {
+        Err(AppError::AsmErrors(diagnostics)) => diagnostics.iter().map(|d| d.message.clone()).collect::<Vec<String>>(),
+        _ => panic!("errors expected"),
+    };
+    assert_eq!(
+        errors(
+            ".org $0600\n.import x\n bne x\n.segment \"B\"\ny: .dbyte y\n beq z\n.segment \"C\"\nz: nop",
+            true
+        ),
+        &[
+            "not allowed in relocatable object: setting location counter",
+            "not allowed in relocatable object: branch to another segment",
+            "not allowed in relocatable object: big endian address",
+            "not allowed in relocatable object: branch to another segment",
+        ]
+    );
+    assert_eq!(errors(".import x, y", false), &["imports need relocatable object: x, y"]);
+}
+
+#[test]
+fn expressions() {
+    let asm = assemble_source(
+        "SIZE = (end-table)/2\n  .org $0600\n  lda table+SIZE*2-1,x\n  ldy #<(table+$100)\n  sta (ptr+2),y\n\
+         table: .byte SIZE, -1, lo(end) hi(end)\n  .word table + 1 ; next\nend:\nptr = $10",
+    );
+    assert_eq!(
+        asm.code(),
+        &[0xbd, 0x0c, 0x06, 0xa0, 0x07, 0x91, 0x12, 0x03, 0xff, 0x0d, 0x06, 0x08, 0x06]
+    );
+}
+
+#[test]
+fn repeat_blocks() {
+    let asm = assemble_source(
+        "  .org $0600\n  .rept 2\n  .for i = 1, 3\n  .byte i*COUNT\n  .endfor\n- inx\n  .endrept\n\
+         .for COUNT = 4, 3\n  brk\n.endfor\nCOUNT = 2\n  .for i = 0, 1\n  .word i\n  .endfor",
+    );
+    assert_eq!(asm.code(), &[2, 4, 6, 0xe8, 2, 4, 6, 0xe8, 0, 0, 1, 0]);
+    let lines: Vec<(usize, bool)> = asm.listing.lines.iter().map(|l| (l.line, l.expanded)).take(7).collect();
+    assert_eq!(
+        lines,
+        &[(1, false), (2, false), (3, true), (4, true), (4, true), (4, true), (5, true)]
+    );
+    let d = diagnostics(
+        "  .rept 2\nx: nop\n  .endrept\n  .endfor\n  .for i = 0, 1\n  .rept -1\n  .endrept",
+        20,
+    );
+    let found: Vec<(usize, &str)> = d.iter().map(|d| (d.line, d.message.as_str())).collect();
+    assert_eq!(
+        found,
+        &[
+            (2, "symbol x redefined from $0000 to $0001"),
+            (2, "unused label: x"),
+            (4, "unbalanced block: .endfor"),
+            (5, "unbalanced block: .for"),
+        ]
+    );
 }
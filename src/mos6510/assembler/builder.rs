@@ -0,0 +1,109 @@
+use super::{Assembler, Assembly, MAX_ERRORS};
+use crate::{error::AppError, mos6510::operation::CpuVariant};
+use std::path::{Path, PathBuf};
+
+/// Assembles source held in memory, e.g. `Assembler::builder().source("demo.asm", src).cpu(cpu).assemble()`.
+pub struct AssemblerBuilder {
+    name: String,
+    source: String,
+    includes: Vec<(String, String)>,
+    include_dir: Option<PathBuf>,
+    symbols: Vec<(String, i32)>,
+    cpu: CpuVariant,
+    max_errors: usize,
+    long_branches: bool,
+    warn_unused: bool,
+    relocatable: bool,
+}
+
+impl AssemblerBuilder {
+    pub fn new() -> Self {
+        Self {
+            name: String::new(),
+            source: String::new(),
+            includes: Vec::new(),
+            include_dir: None,
+            symbols: Vec::new(),
+            cpu: CpuVariant::default(),
+            max_errors: MAX_ERRORS,
+            long_branches: false,
+            warn_unused: false,
+            relocatable: false,
+        }
+    }
+
+    /// Main source, `name` is reported in diagnostics and debug info.
+    pub fn source(mut self, name: &str, text: &str) -> Self {
+        self.name = String::from(name);
+        self.source = String::from(text);
+        self
+    }
+
+    /// Virtual file available to `.include "name"`.
+    pub fn include(mut self, name: &str, text: &str) -> Self {
+        self.includes.push((String::from(name), String::from(text)));
+        self
+    }
+
+    /// Directory searched for included files which were not given with `include`.
+    pub fn include_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.include_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Predefined constant, visible in the whole source.
+    pub fn define(mut self, name: &str, value: i32) -> Self {
+        self.symbols.push((String::from(name), value));
+        self
+    }
+
+    pub fn cpu(mut self, cpu: CpuVariant) -> Self {
+        self.cpu = cpu;
+        self
+    }
+
+    pub fn max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors = max_errors;
+        self
+    }
+
+    pub fn long_branches(mut self, long_branches: bool) -> Self {
+        self.long_branches = long_branches;
+        self
+    }
+
+    pub fn warn_unused(mut self, warn_unused: bool) -> Self {
+        self.warn_unused = warn_unused;
+        self
+    }
+
+    /// Produces relocatable object in `Assembly::object` instead of absolute code.
+    pub fn relocatable(mut self, relocatable: bool) -> Self {
+        self.relocatable = relocatable;
+        self
+    }
+
+    /// Fails with `AppError::AsmErrors` holding all diagnostics when there are any errors.
+    pub fn assemble(self) -> Result<Assembly, AppError> {
+        let mut asm = Assembler::new();
+        asm.debug_info.files.push(self.name);
+        self.includes.iter().for_each(|(name, text)| asm.add_include(name, text));
+        if let Some(dir) = self.include_dir {
+            asm.set_include_dir(dir);
+        }
+        self.symbols.iter().for_each(|(name, value)| asm.define(name, *value));
+        asm.set_cpu(self.cpu);
+        asm.set_max_errors(self.max_errors);
+        asm.set_long_branches(self.long_branches);
+        asm.set_warn_unused(self.warn_unused);
+        asm.set_relocatable(self.relocatable);
+        asm.process_source(&self.source)?;
+        Ok(asm.into_assembly())
+    }
+}
+
+impl Default for AssemblerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,132 @@
+use crate::error::AppError;
+use std::convert::TryFrom;
+
+const QUOTE: char = '"';
+const ESCAPE: char = '\\';
+
+#[derive(Clone)]
+pub struct Charmap {
+    table: [u8; 256],
+}
+
+impl Charmap {
+    pub fn ascii() -> Self {
+        let mut table = [0; 256];
+        table.iter_mut().enumerate().for_each(|(i, c)| *c = i as u8);
+        Self { table }
+    }
+
+    pub fn named(name: &str) -> Result<Self, AppError> {
+        let translate: fn(u8) -> u8 = match name.to_lowercase().as_str() {
+            "ascii" => |c| c,
+            "petscii" => petscii,
+            "screen" => screen_code,
+            _ => return Err(AppError::InvalidCharmap(String::from(name))),
+        };
+        let mut charmap = Self::ascii();
+        charmap.table.iter_mut().for_each(|c| *c = translate(*c));
+        Ok(charmap)
+    }
+
+    pub fn set(&mut self, code: u8, value: u8) {
+        self.table[code as usize] = value;
+    }
+
+    #[inline]
+    pub fn translate(&self, code: u8) -> u8 {
+        self.table[code as usize]
+    }
+
+    /// Decodes a quoted string literal, translating characters through the table.
+    /// Bytes given as `\xHH` escapes are emitted as they are.
+    pub fn encode(&self, literal: &str) -> Result<Vec<u8>, AppError> {
+        let invalid = || AppError::InvalidString(String::from(literal));
+        let body = literal
+            .strip_prefix(QUOTE)
+            .and_then(|s| s.strip_suffix(QUOTE))
+            .ok_or_else(invalid)?;
+        let mut bytes = Vec::new();
+        let mut chars = body.chars();
+        while let Some(c) = chars.next() {
+            let c = match c {
+                ESCAPE => match chars.next().ok_or_else(invalid)? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    'x' => {
+                        let hex: String = chars.by_ref().take(2).collect();
+                        bytes.push(u8::from_str_radix(&hex, 16).map_err(|_| invalid())?);
+                        continue;
+                    }
+                    c @ (ESCAPE | QUOTE | '\'') => c,
+                    _ => return Err(invalid()),
+                },
+                QUOTE => return Err(invalid()),
+                c => c,
+            };
+            let code = u8::try_from(c as u32).map_err(|_| invalid())?;
+            bytes.push(self.translate(code));
+        }
+        Ok(bytes)
+    }
+}
+
+fn petscii(c: u8) -> u8 {
+    match c {
+        b'a'..=b'z' => c - 0x20,
+        b'A'..=b'Z' => c + 0x80,
+        _ => c,
+    }
+}
+
+fn screen_code(c: u8) -> u8 {
+    match c {
+        b'@' | b'['..=b'_' => c - 0x40,
+        b'a'..=b'z' => c - 0x60,
+        b'`' => 0x40,
+        b'{'..=0x7f => c - 0x20,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii() {
+        assert_eq!(Charmap::ascii().encode("\"Hi!\"").unwrap(), b"Hi!");
+    }
+
+    #[test]
+    fn escapes() {
+        let cm = Charmap::ascii();
+        assert_eq!(
+            cm.encode("\"a\\n\\t\\0\\\\\\\"\\x7f\"").unwrap(),
+            &[b'a', 10, 9, 0, b'\\', b'"', 0x7f]
+        );
+        assert!(matches!(cm.encode("\"abc"), Err(AppError::InvalidString(_))));
+        assert!(matches!(cm.encode("\"a\\q\""), Err(AppError::InvalidString(_))));
+    }
+
+    #[test]
+    fn petscii_and_screen_codes() {
+        assert_eq!(
+            Charmap::named("petscii").unwrap().encode("\"Hello 1\"").unwrap(),
+            &[0xc8, 0x45, 0x4c, 0x4c, 0x4f, 0x20, 0x31]
+        );
+        assert_eq!(
+            Charmap::named("SCREEN").unwrap().encode("\"@az[Z 1\"").unwrap(),
+            &[0x00, 0x01, 0x1a, 0x1b, 0x5a, 0x20, 0x31]
+        );
+        assert!(matches!(Charmap::named("ebcdic"), Err(AppError::InvalidCharmap(_))));
+    }
+
+    #[test]
+    fn custom_mapping() {
+        let mut cm = Charmap::named("petscii").unwrap();
+        cm.set(b'a', 0);
+        assert_eq!(cm.encode("\"ab\\x61\"").unwrap(), &[0x00, 0x42, 0x61]);
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Error or warning related to a span of a source line, columns are counted from 1.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+    pub source: String,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        if !self.file.is_empty() {
+            write!(f, "{}:", self.file)?;
+        }
+        writeln!(f, "{}:{}: {}: {}", self.line, self.column, severity, self.message)?;
+        let number = self.line.to_string();
+        writeln!(f, "{} | {}", number, self.source.replace('\t', " "))?;
+        write!(
+            f,
+            "{:w$} | {:c$}{}",
+            "",
+            "",
+            "^".repeat(self.len.max(1)),
+            w = number.len(),
+            c = self.column - 1
+        )
+    }
+}
+
+/// Column and length of the first occurrence of `text` in the line, ignoring case.
+pub fn find_span(line: &str, text: &str) -> Option<(usize, usize)> {
+    match text.is_empty() {
+        true => None,
+        false => line.to_lowercase().find(&text.to_lowercase()).map(|i| (i + 1, text.len())),
+    }
+}
+
+/// Span of the statement, leading whitespace and trailing comment excluded.
+pub fn statement_span(line: &str) -> (usize, usize) {
+    let code = line.split(';').next().unwrap_or_default();
+    let start = code.len() - code.trim_start().len();
+    (start + 1, code.trim().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let d = Diagnostic {
+            severity: Severity::Error,
+            message: String::from("undefined symbol: foo"),
+            file: String::from("demo.asm"),
+            line: 12,
+            column: 7,
+            len: 3,
+            source: String::from("  lda foo,x"),
+        };
+        assert_eq!(
+            d.to_string(),
+            "demo.asm:12:7: error: undefined symbol: foo\n12 |   lda foo,x\n   |       ^^^"
+        );
+    }
+
+    #[test]
+    fn spans() {
+        assert_eq!(find_span("  LDA Foo", "foo"), Some((7, 3)));
+        assert_eq!(find_span("  lda bar", "foo"), None);
+        assert_eq!(statement_span("  lda #1 ; comment"), (3, 6));
+        assert_eq!(statement_span("; comment"), (1, 0));
+    }
+}
@@ -0,0 +1,318 @@
+use super::operand::{Modifier, Operand, OperandSize, Resolver, ANONYMOUS_BACKWARD, ANONYMOUS_FORWARD};
+use crate::{
+    error::AppError,
+    mos6510::object::{RelocKind, Target},
+};
+use std::f64::consts::PI;
+
+const SIN_PERIOD: i32 = 256;
+const SIN_AMPLITUDE: i32 = 127;
+
+type Reloc = Option<(Target, RelocKind)>;
+
+/// Operand made of numbers, symbols and function calls combined with `+ - * /` and parentheses,
+/// multiplication and division take precedence.
+pub fn evaluate(resolver: &mut Resolver, text: &str, no_symbol_fail: bool) -> Result<Operand, AppError> {
+    if text.chars().all(|c| c == ANONYMOUS_FORWARD) || text.chars().all(|c| c == ANONYMOUS_BACKWARD) {
+        return resolver.resolve_raw(text, no_symbol_fail);
+    }
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        resolver,
+        no_symbol_fail,
+    };
+    let operand = parser.expression()?;
+    parser.skip_spaces();
+    match parser.pos == text.len() {
+        true => Ok(operand),
+        false => Err(AppError::SyntaxError(String::from(text))),
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    resolver: &'a mut Resolver,
+    no_symbol_fail: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn skip_spaces(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), AppError> {
+        self.skip_spaces();
+        match self.peek() == Some(c) {
+            true => {
+                self.pos += 1;
+                Ok(())
+            }
+            false => Err(AppError::SyntaxError(String::from(self.text))),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Operand, AppError> {
+        let mut left = self.product()?;
+        loop {
+            self.skip_spaces();
+            let subtract = match self.peek() {
+                Some('+') => false,
+                Some('-') => true,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.product()?;
+            let reloc = match (left.reloc, right.reloc, subtract) {
+                (None, None, _) => None,
+                (Some(r), None, _) | (None, Some(r), false) if r.1 == RelocKind::Word => Some(r),
+                // distance between addresses of the same segment does not change
+                (Some(a), Some(b), true) if a == b && a.1 == RelocKind::Word => None,
+                _ => return Err(not_relocatable()),
+            };
+            let value = match subtract {
+                true => left.value.wrapping_sub(right.value),
+                false => left.value.wrapping_add(right.value),
+            };
+            left = combined(&[left, right], value, reloc);
+        }
+    }
+
+    fn product(&mut self) -> Result<Operand, AppError> {
+        let mut left = self.factor()?;
+        loop {
+            self.skip_spaces();
+            let operator = match self.peek() {
+                Some(c @ ('*' | '/')) => c,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.factor()?;
+            if left.reloc.is_some() || right.reloc.is_some() {
+                return Err(not_relocatable());
+            }
+            let value = match operator {
+                '*' => left.value.wrapping_mul(right.value),
+                _ if right.value != 0 => left.value.wrapping_div(right.value),
+                _ if right.defined => return Err(AppError::DivisionByZero),
+                _ => 0,
+            };
+            left = combined(&[left, right], value, None);
+        }
+    }
+
+    fn factor(&mut self) -> Result<Operand, AppError> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let operand = self.expression()?;
+                self.expect(')')?;
+                Ok(operand)
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.factor()
+            }
+            Some('-') => {
+                self.pos += 1;
+                let operand = self.factor()?;
+                if operand.reloc.is_some() {
+                    return Err(not_relocatable());
+                }
+                Ok(combined(&[operand], operand.value.wrapping_neg(), None))
+            }
+            _ => {
+                let atom = self.atom();
+                if atom.is_empty() {
+                    return Err(AppError::SyntaxError(String::from(self.text)));
+                }
+                match self.peek() {
+                    Some('(') => {
+                        self.pos += 1;
+                        self.call(atom)
+                    }
+                    _ => self.resolver.resolve_raw(atom, self.no_symbol_fail),
+                }
+            }
+        }
+    }
+
+    /// Number or symbol name of ASCII characters, hexadecimal and binary numbers may be negative like `$-10`.
+    fn atom(&mut self) -> &'a str {
+        let start = self.pos;
+        if self.text[start..].starts_with(['$', '%']) && self.text[start + 1..].starts_with('-') {
+            self.pos += 2;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || "_$%:.@".contains(c)) {
+            self.pos += 1;
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn string(&mut self) -> Result<&'a str, AppError> {
+        self.skip_spaces();
+        let start = self.pos;
+        let mut escaped = false;
+        self.expect('"')?;
+        while let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => return Ok(&self.text[start..self.pos]),
+                _ => {}
+            }
+        }
+        Err(AppError::InvalidString(String::from(&self.text[start..])))
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Operand>, AppError> {
+        let mut args = Vec::new();
+        loop {
+            args.push(self.expression()?);
+            self.skip_spaces();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                _ => return self.expect(')').map(|_| args),
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<Operand, AppError> {
+        let invalid = || AppError::InvalidFunction(String::from(name));
+        match name.to_lowercase().as_str() {
+            "defined" => {
+                self.skip_spaces();
+                let symbol = String::from(self.atom());
+                self.expect(')')?;
+                Ok(Operand::literal(self.resolver.is_defined(&symbol) as i32))
+            }
+            "strlen" => {
+                let len = string_len(self.string()?);
+                self.expect(')')?;
+                Ok(Operand::literal(len as i32))
+            }
+            function => {
+                let args = self.arguments()?;
+                if args.iter().any(|a| a.reloc.is_some()) && !matches!(function, "lo" | "hi") {
+                    return Err(not_relocatable());
+                }
+                match (function, args.as_slice()) {
+                    ("lo", [arg]) => Ok(arg.modified(Modifier::LoByte)),
+                    ("hi", [arg]) => Ok(arg.modified(Modifier::HiByte)),
+                    ("min", [_, ..]) => Ok(combined(&args, args.iter().map(|a| a.value).min().unwrap(), None)),
+                    ("max", [_, ..]) => Ok(combined(&args, args.iter().map(|a| a.value).max().unwrap(), None)),
+                    ("sin" | "cos", [_] | [_, _] | [_, _, _]) => {
+                        let period = args.get(1).map_or(SIN_PERIOD, |a| a.value);
+                        let amplitude = args.get(2).map_or(SIN_AMPLITUDE, |a| a.value);
+                        if period == 0 {
+                            return match args.iter().all(|a| a.defined) {
+                                true => Err(AppError::DivisionByZero),
+                                false => Ok(combined(&args, 0, None)),
+                            };
+                        }
+                        let angle = 2.0 * PI * args[0].value as f64 / period as f64;
+                        let ratio = if function == "sin" { angle.sin() } else { angle.cos() };
+                        Ok(combined(&args, (amplitude as f64 * ratio).round() as i32, None))
+                    }
+                    _ => Err(invalid()),
+                }
+            }
+        }
+    }
+}
+
+/// Result of an operation, known only when all of its arguments are.
+fn combined(args: &[Operand], value: i32, reloc: Reloc) -> Operand {
+    Operand {
+        value,
+        symbolic: args.iter().any(|a| a.symbolic),
+        defined: args.iter().all(|a| a.defined),
+        forward: args.iter().any(|a| a.forward),
+        size: OperandSize::Auto,
+        reloc,
+    }
+}
+
+fn not_relocatable() -> AppError {
+    AppError::NotRelocatable(String::from("expression"))
+}
+
+/// Length of quoted string, escape sequence counts as a single character.
+fn string_len(quoted: &str) -> usize {
+    let text = &quoted[1..quoted.len() - 1];
+    let mut len = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.next() == Some('x') {
+            chars.nth(1);
+        }
+        len += 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str) -> Result<i32, AppError> {
+        let mut resolver = Resolver::new();
+        resolver.define_symbol("table", 0x1234).unwrap();
+        resolver.define_constant("SIZE", 16).unwrap();
+        evaluate(&mut resolver, text, true).map(|o| o.value)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(eval("table+1").unwrap(), 0x1235);
+        assert_eq!(eval("table - SIZE*2").unwrap(), 0x1214);
+        assert_eq!(eval("(SIZE+2)*3").unwrap(), 54);
+        assert_eq!(eval("-SIZE/3").unwrap(), -5);
+        assert_eq!(eval("$10*%10-1").unwrap(), 31);
+        assert!(matches!(eval("1/0"), Err(AppError::DivisionByZero)));
+        assert!(matches!(eval("1+"), Err(AppError::SyntaxError(_))));
+        assert!(matches!(eval("(1+2"), Err(AppError::SyntaxError(_))));
+        assert_eq!(eval("1\u{a0}+ 2").unwrap(), 3);
+        assert!(matches!(eval("table\u{e9}+1"), Err(AppError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(eval("lo(table)").unwrap(), 0x34);
+        assert_eq!(eval("hi(table+$100)").unwrap(), 0x13);
+        assert_eq!(eval("min(3, SIZE, -1)").unwrap(), -1);
+        assert_eq!(eval("max(3, SIZE)").unwrap(), 16);
+        assert_eq!(eval("sin(64)").unwrap(), 127);
+        assert_eq!(eval("128+sin(32, 256, 100)").unwrap(), 199);
+        assert_eq!(eval("cos(2, 4, 10)").unwrap(), -10);
+        assert_eq!(eval("defined(table)+defined(none)").unwrap(), 1);
+        assert_eq!(eval("strlen(\"a\\\"b\\x41\")").unwrap(), 4);
+        assert!(matches!(eval("foo(1)"), Err(AppError::InvalidFunction(name)) if name == "foo"));
+        assert!(matches!(eval("lo(1, 2)"), Err(AppError::InvalidFunction(_))));
+    }
+
+    #[test]
+    fn relocation() {
+        let mut resolver = Resolver::new();
+        resolver.set_segment(Some(0));
+        resolver.define_symbol("start", 0).unwrap();
+        resolver.define_symbol("end", 10).unwrap();
+        let mut eval = |text: &str| evaluate(&mut resolver, text, true);
+        let operand = eval("start+2").unwrap();
+        assert_eq!((operand.value, operand.reloc), (2, Some((Target::Segment(0), RelocKind::Word))));
+        let operand = eval("end-start").unwrap();
+        assert_eq!((operand.value, operand.reloc), (10, None));
+        assert_eq!(eval("hi(end+1)").unwrap().reloc, Some((Target::Segment(0), RelocKind::High(11))));
+        assert!(matches!(eval("start*2"), Err(AppError::NotRelocatable(_))));
+        assert!(matches!(eval("2-start"), Err(AppError::NotRelocatable(_))));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::mos6510::operation::{CpuVariant, Operation};
+use std::{collections::HashMap, io::Write};
+
+const BYTES_PER_ROW: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct ListingLine {
+    pub line: usize,
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub opcode: Option<u8>,
+    pub cpu: CpuVariant,
+    pub source: String,
+    pub expanded: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CrossReference {
+    pub name: String,
+    pub value: i32,
+    pub defined: Option<usize>,
+    pub used: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+    pub lines: Vec<ListingLine>,
+    pub xref: Vec<CrossReference>,
+}
+
+impl Listing {
+    pub fn set_xref(&mut self, symbols: &HashMap<String, i32>, defs: &HashMap<String, usize>, refs: &HashMap<String, Vec<usize>>) {
+        self.xref = symbols
+            .iter()
+            .map(|(name, value)| CrossReference {
+                name: name.clone(),
+                value: *value,
+                defined: defs.get(name).copied(),
+                used: refs.get(name).cloned().unwrap_or_default(),
+            })
+            .collect();
+        self.xref.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, " line  addr  bytes        cyc   source")?;
+        for line in &self.lines {
+            let mut rows = line.bytes.chunks(BYTES_PER_ROW);
+            let first = rows.next().unwrap_or(&[]);
+            let marker = if line.expanded { '+' } else { ' ' };
+            let text = format!(
+                "{:5}{} {:04X}  {:12} {:5} {}",
+                line.line,
+                marker,
+                line.address,
+                hex_bytes(first),
+                cycles(line.cpu, line.opcode),
+                line.source
+            );
+            writeln!(out, "{}", text.trim_end())?;
+            for (i, row) in rows.enumerate() {
+                let address = line.address.wrapping_add(((i + 1) * BYTES_PER_ROW) as u16);
+                writeln!(out, "       {:04X}  {}", address, hex_bytes(row))?;
+            }
+        }
+        writeln!(out)?;
+        writeln!(out, "symbols ({}):", self.xref.len())?;
+        writeln!(out, "{:32} value  defined  used", "name")?;
+        for x in &self.xref {
+            let defined = x.defined.map(|l| l.to_string()).unwrap_or_default();
+            let used: Vec<String> = x.used.iter().map(|l| l.to_string()).collect();
+            writeln!(out, "{:32} {:04X}   {:7}  {}", x.name, x.value as u16, defined, used.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ")
+}
+
+fn cycles(cpu: CpuVariant, opcode: Option<u8>) -> String {
+    opcode.map(|code| Operation::decode(cpu, code).timing()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_listing() {
+        let mut listing = Listing {
+            lines: vec![
+                ListingLine {
+                    line: 1,
+                    address: 0x0600,
+                    bytes: vec![0xbd, 0x00, 0x02],
+                    opcode: Some(0xbd),
+                    cpu: CpuVariant::Mos6502,
+                    source: String::from("loop: lda $200,x"),
+                    expanded: false,
+                },
+                ListingLine {
+                    line: 2,
+                    address: 0x0603,
+                    bytes: vec![1, 2, 3, 4, 5],
+                    opcode: None,
+                    cpu: CpuVariant::Mos6502,
+                    source: String::from("  dcb 1,2,3,4,5"),
+                    expanded: true,
+                },
+            ],
+            xref: Vec::new(),
+        };
+        let mut symbols = HashMap::new();
+        symbols.insert(String::from("loop"), 0x0600);
+        let mut defs = HashMap::new();
+        defs.insert(String::from("loop"), 1);
+        let mut refs = HashMap::new();
+        refs.insert(String::from("loop"), vec![4, 6]);
+        listing.set_xref(&symbols, &defs, &refs);
+        let mut out = Vec::new();
+        listing.write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], "    1  0600  BD 00 02     4*    loop: lda $200,x");
+        assert_eq!(lines[2], "    2+ 0603  01 02 03 04          dcb 1,2,3,4,5");
+        assert_eq!(lines[3], "       0607  05");
+        assert_eq!(lines[7], "loop                             0600   1        4 6");
+    }
+}
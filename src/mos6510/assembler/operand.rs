@@ -1,32 +1,81 @@
+use super::expression;
 use crate::error::AppError;
+use crate::mos6510::{
+    object::{RelocKind, Target},
+    symbols::{Symbol, SymbolKind},
+};
 use std::collections::HashMap;
 
 pub const LO_BYTE_MODIFIER: char = '<';
 pub const HI_BYTE_MODIFIER: char = '>';
+pub const ABSOLUTE_MODIFIER: char = '!';
 pub const HEX_PREFIX: char = '$';
 pub const BIN_PREFIX: char = '%';
 
+/// Addressing size requested explicitly by operand prefix or mnemonic suffix.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OperandSize {
+    Auto,
+    ZeroPage,
+    Absolute,
+}
+
 #[derive(Copy, Clone)]
 pub struct Operand {
     pub value: i32,
     pub symbolic: bool,
+    pub defined: bool,
+    pub forward: bool,
+    pub size: OperandSize,
+    /// Relocation needed by the value in a relocatable object.
+    pub reloc: Option<(Target, RelocKind)>,
 }
 
 impl Operand {
     pub fn literal(value: i32) -> Self {
-        Self { value, symbolic: false }
+        Self {
+            value,
+            symbolic: false,
+            defined: true,
+            forward: false,
+            size: OperandSize::Auto,
+            reloc: None,
+        }
     }
     pub fn symbol(value: i32) -> Self {
-        Self { value, symbolic: true }
+        Self {
+            value,
+            symbolic: true,
+            defined: true,
+            forward: false,
+            size: OperandSize::Auto,
+            reloc: None,
+        }
+    }
+    /// Symbol not defined yet, its value is unknown until the next pass.
+    pub fn undefined() -> Self {
+        Self {
+            defined: false,
+            ..Self::symbol(0)
+        }
     }
     pub fn modified(&self, modifier: Modifier) -> Self {
+        let (value, size) = match modifier {
+            Modifier::None => (self.value, self.size),
+            Modifier::LoByte => (self.value & 0x00ff, OperandSize::ZeroPage),
+            Modifier::HiByte => (self.value >> 8, OperandSize::ZeroPage),
+            Modifier::Absolute => (self.value, OperandSize::Absolute),
+        };
+        let reloc = self.reloc.map(|(target, kind)| match (modifier, kind) {
+            (Modifier::LoByte, RelocKind::Word) => (target, RelocKind::Low),
+            (Modifier::HiByte, RelocKind::Word) => (target, RelocKind::High(self.value as u8)),
+            _ => (target, kind),
+        });
         Self {
-            value: match modifier {
-                Modifier::None => self.value,
-                Modifier::LoByte => self.value & 0x00ff,
-                Modifier::HiByte => self.value >> 8,
-            },
-            symbolic: self.symbolic,
+            value,
+            size,
+            reloc,
+            ..*self
         }
     }
 }
@@ -35,6 +84,7 @@ pub enum Modifier {
     None,
     LoByte,
     HiByte,
+    Absolute,
 }
 
 impl Modifier {
@@ -43,6 +93,8 @@ impl Modifier {
             Modifier::LoByte
         } else if str.starts_with(HI_BYTE_MODIFIER) {
             Modifier::HiByte
+        } else if str.starts_with(ABSOLUTE_MODIFIER) {
+            Modifier::Absolute
         } else {
             Modifier::None
         }
@@ -53,56 +105,330 @@ impl Modifier {
             Modifier::None => 0,
             Modifier::LoByte => 1,
             Modifier::HiByte => 1,
+            Modifier::Absolute => 1,
         }
     }
 }
 
+pub const SCOPE_SEPARATOR: &str = "::";
+pub const LOCAL_PREFIXES: [char; 2] = ['.', '@'];
+pub const ANONYMOUS_FORWARD: char = '+';
+pub const ANONYMOUS_BACKWARD: char = '-';
+
 pub struct Resolver {
     symbols: HashMap<String, i32>,
+    scopes: Vec<String>,
+    global: String,
+    backward: Vec<Operand>,
+    forward: Vec<Operand>,
+    forward_next: Vec<Operand>,
+    line: usize,
+    definitions: HashMap<String, usize>,
+    site: (usize, usize),
+    sites: HashMap<String, (usize, usize)>,
+    references: HashMap<String, Vec<usize>>,
+    kinds: HashMap<String, SymbolKind>,
+    changed: Option<String>,
+    forward_references: Vec<String>,
+    relocs: HashMap<String, (Target, RelocKind)>,
+    imports: Vec<String>,
+    segment: Option<usize>,
+    variables: HashMap<String, i32>,
 }
 
 impl Resolver {
     pub fn new() -> Self {
-        Self { symbols: HashMap::new() }
+        Self {
+            symbols: HashMap::new(),
+            scopes: Vec::new(),
+            global: String::new(),
+            backward: Vec::new(),
+            forward: Vec::new(),
+            forward_next: Vec::new(),
+            line: 0,
+            definitions: HashMap::new(),
+            site: (0, 0),
+            sites: HashMap::new(),
+            references: HashMap::new(),
+            kinds: HashMap::new(),
+            changed: None,
+            forward_references: Vec::new(),
+            relocs: HashMap::new(),
+            imports: Vec::new(),
+            segment: None,
+            variables: HashMap::new(),
+        }
+    }
+
+    pub fn init_pass(&mut self) {
+        self.scopes.clear();
+        self.global.clear();
+        self.backward.clear();
+        self.forward = std::mem::take(&mut self.forward_next);
+        self.definitions.clear();
+        self.sites.clear();
+        self.references.clear();
+        self.changed = None;
+        self.forward_references.clear();
+        self.variables.clear();
     }
 
-    pub fn resolve(&self, txt: &str, no_symbol_fail: bool) -> Result<Operand, AppError> {
+    /// Sets source line number used for the symbol cross-reference.
+    pub fn set_line(&mut self, line: usize) {
+        self.line = line;
+    }
+
+    /// Sets file index and line number within the file, where following symbols are defined.
+    pub fn set_site(&mut self, file: usize, line: usize) {
+        self.site = (file, line);
+    }
+
+    pub fn resolve(&mut self, txt: &str, no_symbol_fail: bool) -> Result<Operand, AppError> {
         let modifier = Modifier::from(txt);
         let rest = &txt[modifier.len()..];
-        self.resolve_raw(rest, no_symbol_fail).and_then(|op| Ok(op.modified(modifier)))
+        expression::evaluate(self, rest, no_symbol_fail).map(|op| op.modified(modifier))
+    }
+
+    /// Loop variable shadows symbols of the same name, it is neither defined nor referenced as a symbol.
+    pub fn set_variable(&mut self, name: &str, value: Option<i32>) {
+        match value {
+            Some(value) => self.variables.insert(String::from(name), value),
+            None => self.variables.remove(name),
+        };
+    }
+
+    pub fn variable(&self, name: &str) -> Option<i32> {
+        self.variables.get(name).copied()
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        let import = name.strip_prefix(SCOPE_SEPARATOR).unwrap_or(name);
+        self.variables.contains_key(name) || self.lookup(name).is_some() || self.imports.iter().any(|i| i == import)
     }
 
     pub fn define_symbol(&mut self, key: &str, val: i32) -> Result<(), AppError> {
-        match self.symbols.insert(String::from(key), val) {
-            Some(old) => {
-                if old != val {
-                    Err(AppError::RedefinedSymbol(String::from(key), old, val))
-                } else {
-                    Ok(())
+        let kind = if is_local(key) { SymbolKind::Local } else { SymbolKind::Label };
+        self.define(key, Operand::literal(val), kind)
+    }
+
+    pub fn define_constant(&mut self, key: &str, val: i32) -> Result<(), AppError> {
+        self.define(key, Operand::literal(val), SymbolKind::Constant)
+    }
+
+    /// Constant keeps relocation of the operand, e.g. `alias = label` in a relocatable object.
+    pub fn define_alias(&mut self, key: &str, operand: Operand) -> Result<(), AppError> {
+        self.define(key, operand, SymbolKind::Constant)
+    }
+
+    /// Labels defined from now on are offsets into given segment of a relocatable object, `None` for absolute code.
+    pub fn set_segment(&mut self, segment: Option<usize>) {
+        self.segment = segment;
+    }
+
+    /// Symbol defined in another object, its value is known after linking.
+    pub fn import(&mut self, name: &str) {
+        if !self.imports.iter().any(|i| i == name) {
+            self.imports.push(String::from(name));
+        }
+    }
+
+    pub fn imports(&self) -> &Vec<String> {
+        &self.imports
+    }
+
+    /// Symbol may change its value between passes, but only once within a pass.
+    fn define(&mut self, key: &str, operand: Operand, kind: SymbolKind) -> Result<(), AppError> {
+        let val = operand.value;
+        let reloc = match kind {
+            SymbolKind::Constant => operand.reloc,
+            _ => self.segment.map(|segment| (Target::Segment(segment), RelocKind::Word)),
+        };
+        if is_anonymous(key, ANONYMOUS_BACKWARD) {
+            self.backward.push(Operand {
+                reloc,
+                ..Operand::symbol(val)
+            });
+            return Ok(());
+        }
+        if is_anonymous(key, ANONYMOUS_FORWARD) {
+            self.forward_next.push(Operand {
+                reloc,
+                ..Operand::symbol(val)
+            });
+            return Ok(());
+        }
+        let name = self.qualified(key);
+        if self.imports.contains(&name) {
+            return Err(AppError::RedefinedSymbol(name, 0, val));
+        }
+        if kind == SymbolKind::Label {
+            self.global = name.clone();
+        }
+        match self.symbols.get(&name).copied() {
+            Some(old) if old != val && self.definitions.contains_key(&name) => Err(AppError::RedefinedSymbol(name, old, val)),
+            old => {
+                if old.is_some_and(|old| old != val) && self.forward_references.contains(&name) {
+                    self.changed.get_or_insert_with(|| name.clone());
                 }
+                self.symbols.insert(name.clone(), val);
+                match reloc {
+                    Some(reloc) => self.relocs.insert(name.clone(), reloc),
+                    None => self.relocs.remove(&name),
+                };
+                self.definitions.insert(name.clone(), self.line);
+                self.sites.insert(name.clone(), self.site);
+                self.kinds.insert(name, kind);
+                Ok(())
             }
-            None => Ok(()),
         }
     }
 
+    pub fn begin_scope(&mut self, name: &str) {
+        self.scopes.push(String::from(name));
+    }
+
+    /// Qualified name of the current scope.
+    pub fn scope(&self) -> String {
+        self.scopes.join(SCOPE_SEPARATOR)
+    }
+
+    pub fn end_scope(&mut self) -> Result<(), AppError> {
+        self.scopes.pop().map(|_| ()).ok_or(AppError::UnbalancedScope)
+    }
+
+    pub fn in_scope(&self) -> bool {
+        !self.scopes.is_empty()
+    }
+
     pub fn symbols(&self) -> &HashMap<String, i32> {
         &self.symbols
     }
 
-    fn resolve_raw(&self, raw: &str, no_symbol_fail: bool) -> Result<Operand, AppError> {
+    pub fn definitions(&self) -> &HashMap<String, usize> {
+        &self.definitions
+    }
+
+    /// File index and line of each symbol definition, included files have their own line numbers.
+    pub fn sites(&self) -> &HashMap<String, (usize, usize)> {
+        &self.sites
+    }
+
+    pub fn references(&self) -> &HashMap<String, Vec<usize>> {
+        &self.references
+    }
+
+    /// First symbol referenced before its definition with a value from the previous pass,
+    /// which turned out to be different.
+    pub fn changed(&self) -> Option<&String> {
+        self.changed.as_ref()
+    }
+
+    /// Symbols with the file of `files` and the line within it, where they are defined.
+    pub fn symbol_table(&self, files: &[String]) -> Vec<Symbol> {
+        self.symbols
+            .iter()
+            .map(|(name, value)| {
+                let site = self.sites.get(name).filter(|(_, line)| *line > 0);
+                Symbol {
+                    name: name.clone(),
+                    value: *value,
+                    kind: self.kinds.get(name).copied().unwrap_or(SymbolKind::Label),
+                    line: site.map(|(_, line)| *line),
+                    file: site.and_then(|(file, _)| files.get(*file)).filter(|f| !f.is_empty()).cloned(),
+                }
+            })
+            .collect()
+    }
+
+    fn qualified(&self, key: &str) -> String {
+        if is_local(key) {
+            format!("{}.{}", self.global, &key[1..])
+        } else if self.scopes.is_empty() {
+            String::from(key)
+        } else {
+            format!("{}{}{}", self.scopes.join(SCOPE_SEPARATOR), SCOPE_SEPARATOR, key)
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<(String, i32)> {
+        let find = |key: String| self.symbols.get(&key).map(|v| (key, *v));
+        if is_local(name) {
+            return find(self.qualified(name));
+        }
+        if let Some(global) = name.strip_prefix(SCOPE_SEPARATOR) {
+            return find(String::from(global));
+        }
+        (0..=self.scopes.len()).rev().find_map(|depth| {
+            find(match depth {
+                0 => String::from(name),
+                _ => format!("{}{}{}", self.scopes[..depth].join(SCOPE_SEPARATOR), SCOPE_SEPARATOR, name),
+            })
+        })
+    }
+
+    fn reference(&mut self, name: &str) -> Option<Operand> {
+        let (key, value) = match self.lookup(name) {
+            Some(symbol) => symbol,
+            None => return self.reference_import(name),
+        };
+        let forward = !self.definitions.contains_key(&key);
+        if forward && !self.forward_references.contains(&key) {
+            self.forward_references.push(key.clone());
+        }
+        let reloc = self.relocs.get(&key).copied();
+        let lines = self.references.entry(key).or_default();
+        if lines.last() != Some(&self.line) {
+            lines.push(self.line);
+        }
+        Some(Operand {
+            forward,
+            reloc,
+            ..Operand::symbol(value)
+        })
+    }
+
+    /// Imported symbol is zero with relocation filled in by the linker.
+    fn reference_import(&mut self, name: &str) -> Option<Operand> {
+        let name = name.strip_prefix(SCOPE_SEPARATOR).unwrap_or(name);
+        let index = self.imports.iter().position(|i| i == name)?;
+        let lines = self.references.entry(String::from(name)).or_default();
+        if lines.last() != Some(&self.line) {
+            lines.push(self.line);
+        }
+        Some(Operand {
+            reloc: Some((Target::Import(index), RelocKind::Word)),
+            ..Operand::symbol(0)
+        })
+    }
+
+    fn lookup_anonymous(&self, raw: &str) -> Option<Operand> {
+        if is_anonymous(raw, ANONYMOUS_FORWARD) {
+            self.forward.get(self.forward_next.len() + raw.len() - 1).copied()
+        } else if is_anonymous(raw, ANONYMOUS_BACKWARD) {
+            self.backward.len().checked_sub(raw.len()).map(|i| self.backward[i])
+        } else {
+            None
+        }
+    }
+
+    pub(super) fn resolve_raw(&mut self, raw: &str, no_symbol_fail: bool) -> Result<Operand, AppError> {
         match raw.chars().next() {
             Some(c) => match c {
                 HEX_PREFIX => parse_int(&raw[1..], 16),
                 BIN_PREFIX => parse_int(&raw[1..], 2),
                 _ => {
-                    if c.is_ascii_digit() || c == '+' || c == '-' {
-                        parse_int(&raw, 10)
-                    } else if let Some(num) = self.symbols.get(raw) {
-                        Ok(Operand::symbol(*num))
+                    let anonymous = is_anonymous(raw, ANONYMOUS_FORWARD) || is_anonymous(raw, ANONYMOUS_BACKWARD);
+                    let value = if anonymous { self.lookup_anonymous(raw) } else { None };
+                    if !anonymous && (c.is_ascii_digit() || c == '+' || c == '-') {
+                        parse_int(raw, 10)
+                    } else if let Some(value) = self.variables.get(raw) {
+                        Ok(Operand::literal(*value))
+                    } else if let Some(operand) = value.or_else(|| self.reference(raw)) {
+                        Ok(operand)
                     } else if no_symbol_fail {
                         Err(AppError::UndefinedSymbol(raw.to_string()))
                     } else {
-                        Ok(Operand::symbol(0))
+                        Ok(Operand::undefined())
                     }
                 }
             },
@@ -111,8 +437,70 @@ impl Resolver {
     }
 }
 
+fn is_local(name: &str) -> bool {
+    name.starts_with(LOCAL_PREFIXES)
+}
+
+fn is_anonymous(name: &str, kind: char) -> bool {
+    !name.is_empty() && name.chars().all(|c| c == kind)
+}
+
+/// Splits a list of operands separated by commas or whitespace, keeping quoted strings and parentheses intact.
+/// Whitespace around binary operators does not separate items, so `1 + 2 -3` is a list of `1 + 2` and `-3`.
+pub fn split_list(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut depth = 0;
+    let mut operator = false;
+    for (i, c) in list.char_indices() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+        } else if depth > 0 {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                '"' => quoted = true,
+                _ => {}
+            }
+        } else if c == ',' || (c.is_whitespace() && !operator && !continues(&list[i..])) {
+            if let Some(s) = start.take() {
+                items.push(list[s..i].trim_end());
+            }
+        } else if !c.is_whitespace() {
+            match c {
+                '"' => quoted = true,
+                '(' => depth += 1,
+                _ => {}
+            }
+            let s = *start.get_or_insert(i);
+            operator = "+-*/".contains(c) && list[s..i].contains(|p: char| !"+- ".contains(p));
+        }
+    }
+    if let Some(s) = start {
+        items.push(list[s..].trim_end());
+    }
+    items
+}
+
+/// Text following whitespace starts with a binary operator.
+fn continues(rest: &str) -> bool {
+    let mut chars = rest.trim_start().chars();
+    match chars.next() {
+        Some('*' | '/') => true,
+        Some('+' | '-') => chars.next().is_some_and(char::is_whitespace),
+        _ => false,
+    }
+}
+
 fn parse_int(str: &str, radix: u32) -> Result<Operand, AppError> {
-    match i32::from_str_radix(str, radix) {
+    match i32::from_str_radix(str, radix).or_else(|perr| u32::from_str_radix(str, radix).map(|num| num as i32).map_err(|_| perr)) {
         Ok(num) => Ok(Operand::literal(num)),
         Err(perr) => Err(AppError::ParseIntError(String::from(str), perr)),
     }
@@ -195,4 +583,130 @@ mod tests {
         assert_ok(">label_1", 0x2f);
         assert_err("labeloza", AppError::UndefinedSymbol(String::from("labeloza")));
     }
+
+    #[test]
+    fn list_with_strings() {
+        assert_eq!(
+            split_list("\"a, b\" 13,$0d , \"\\\" x\""),
+            vec!["\"a, b\"", "13", "$0d", "\"\\\" x\""]
+        );
+        assert_eq!(split_list("  "), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn list_with_expressions() {
+        assert_eq!(
+            split_list("1 + 2 -3, min(4, 5) (6 * 7) x* 2"),
+            vec!["1 + 2", "-3", "min(4, 5)", "(6 * 7)", "x* 2"]
+        );
+        assert_eq!(split_list("- -- +"), vec!["-", "--", "+"]);
+    }
+
+    #[test]
+    fn loop_variables() {
+        let mut r = operand_parser();
+        r.set_line(5);
+        r.set_variable("i", Some(3));
+        r.set_variable("label_1", Some(4));
+        assert_eq!(r.resolve("label_1*i", true).unwrap().value, 12);
+        assert!(r.is_defined("i"));
+        assert!(r.references().is_empty());
+        r.set_variable("label_1", None);
+        assert_eq!(r.resolve("label_1", true).unwrap().value, 0x2ffe);
+        r.init_pass();
+        assert!(!r.is_defined("i"));
+    }
+
+    #[test]
+    fn cross_reference() {
+        let mut r = Resolver::new();
+        r.set_line(3);
+        r.define_symbol("start", 0x0600).unwrap();
+        r.set_line(7);
+        r.resolve("start", true).unwrap();
+        r.resolve("<start", true).unwrap();
+        r.set_line(9);
+        r.resolve(">start", true).unwrap();
+        assert_eq!(r.definitions().get("start"), Some(&3));
+        assert_eq!(r.references().get("start"), Some(&vec![7, 9]));
+    }
+
+    #[test]
+    fn redefined_symbols() {
+        let mut r = Resolver::new();
+        r.define_symbol("start", 0x0600).unwrap();
+        r.define_symbol("start", 0x0600).unwrap();
+        assert!(matches!(
+            r.define_symbol("start", 0x0700),
+            Err(AppError::RedefinedSymbol(_, 0x0600, 0x0700))
+        ));
+        r.init_pass();
+        r.set_site(1, 2);
+        r.define_constant("end", 0x0800).unwrap();
+        assert!(r.changed().is_none());
+        r.resolve("start", true).unwrap();
+        r.define_symbol("start", 0x0700).unwrap();
+        assert_eq!(r.changed(), Some(&String::from("start")));
+        r.define_constant("SCREEN", 0x0200).unwrap();
+        r.define_symbol(".loop", 0x0702).unwrap();
+        let mut table = r.symbol_table(&[String::from("main.asm"), String::from("lib.inc")]);
+        table.sort_by(|a, b| a.name.cmp(&b.name));
+        let kinds: Vec<(&str, i32, SymbolKind)> = table.iter().map(|s| (s.name.as_str(), s.value, s.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("SCREEN", 0x0200, SymbolKind::Constant),
+                ("end", 0x0800, SymbolKind::Constant),
+                ("start", 0x0700, SymbolKind::Label),
+                ("start.loop", 0x0702, SymbolKind::Local)
+            ]
+        );
+        assert_eq!((table[1].file.as_deref(), table[1].line), (Some("lib.inc"), Some(2)));
+    }
+
+    #[test]
+    fn local_symbols() {
+        let mut r = Resolver::new();
+        r.define_symbol("draw", 0x0600).unwrap();
+        r.define_symbol(".loop", 0x0602).unwrap();
+        r.define_symbol("scroll", 0x0700).unwrap();
+        r.define_symbol("@loop", 0x0704).unwrap();
+        assert_eq!(r.resolve(".loop", true).unwrap().value, 0x0704);
+        assert_eq!(r.resolve("@loop", true).unwrap().value, 0x0704);
+        assert_eq!(r.symbols().get("draw.loop"), Some(&0x0602));
+        assert_eq!(r.symbols().get("scroll.loop"), Some(&0x0704));
+    }
+
+    #[test]
+    fn scoped_symbols() {
+        let mut r = Resolver::new();
+        r.define_symbol("count", 1).unwrap();
+        r.begin_scope("sprite");
+        r.define_symbol("count", 2).unwrap();
+        assert_eq!(r.resolve("count", true).unwrap().value, 2);
+        assert_eq!(r.resolve("::count", true).unwrap().value, 1);
+        r.end_scope().unwrap();
+        assert_eq!(r.resolve("count", true).unwrap().value, 1);
+        assert_eq!(r.resolve("sprite::count", true).unwrap().value, 2);
+        assert!(matches!(r.end_scope(), Err(AppError::UnbalancedScope)));
+    }
+
+    #[test]
+    fn anonymous_symbols() {
+        let mut r = Resolver::new();
+        r.define_symbol("-", 0x10).unwrap();
+        r.define_symbol("-", 0x20).unwrap();
+        r.define_symbol("+", 0x30).unwrap();
+        r.define_symbol("+", 0x40).unwrap();
+        assert_eq!(r.resolve("-", true).unwrap().value, 0x20);
+        assert_eq!(r.resolve("--", true).unwrap().value, 0x10);
+        assert!(r.resolve("---", true).is_err());
+        r.init_pass();
+        assert_eq!(r.resolve("+", true).unwrap().value, 0x30);
+        assert_eq!(r.resolve("++", true).unwrap().value, 0x40);
+        r.define_symbol("+", 0x30).unwrap();
+        assert_eq!(r.resolve("+", true).unwrap().value, 0x40);
+        assert_eq!(r.resolve("+8", true).unwrap().value, 8);
+        assert_eq!(r.resolve("-8", true).unwrap().value, -8);
+    }
 }
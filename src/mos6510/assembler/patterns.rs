@@ -1,8 +1,10 @@
-use super::operand::{HI_BYTE_MODIFIER, LO_BYTE_MODIFIER};
+use super::operand::{ABSOLUTE_MODIFIER, HI_BYTE_MODIFIER, LO_BYTE_MODIFIER};
+use crate::mos6510::{addrmode::AddrMode, instruction::Instruction, operation::instructions_with};
 use regex::Regex;
 
-pub const SYMBOL: &str = "[a-z]\\w*";
-pub const LABEL: &str = "^(?:([a-z]\\w*):)?\\s*";
+pub const NAME: &str = "[a-z]\\w*";
+pub const SYMBOL: &str = "(?:(?:::)?[a-z]\\w*(?:::[a-z]\\w*)*(?:\\.[a-z]\\w*)?)|(?:[.@][a-z]\\w*)|(?:\\++)|(?:-+)";
+pub const LABEL: &str = "^(?:([.@]?[a-z]\\w*:|[+-](?::|\\s|$)))?\\s*";
 pub const COMMENT: &str = "(?:;.*)?$";
 pub const SEPARATOR: &str = "\\s*,?\\s*";
 
@@ -11,9 +13,29 @@ pub struct AsmPatterns {
     pub cmd_set_location_counter: Regex,
     pub cmd_emit_bytes: Regex,
     pub cmd_emit_words: Regex,
+    pub cmd_emit_dbytes: Regex,
+    pub cmd_emit_dwords: Regex,
+    pub cmd_emit_text: Regex,
+    pub cmd_charmap: Regex,
+    pub cmd_fill: Regex,
+    pub cmd_segment: Regex,
+    pub cmd_define_constant: Regex,
+    pub cmd_begin_scope: Regex,
+    pub cmd_end_scope: Regex,
+    pub cmd_cpu: Regex,
+    pub cmd_include: Regex,
+    pub cmd_export: Regex,
+    pub cmd_import: Regex,
+    pub cmd_rept: Regex,
+    pub cmd_for: Regex,
+    pub cmd_end_block: Regex,
+    /// Lines opening `.rept` or `.for` block, used to find its end.
+    pub block_begin: Regex,
     pub ins_implied: Regex,
     pub ins_immediate: Regex,
     pub ins_branch: Regex,
+    pub ins_long_branch: Regex,
+    pub ins_zero_page_relative: Regex,
     pub ins_absolute: Regex,
     pub ins_absolute_indexed_x: Regex,
     pub ins_absolute_indexed_y: Regex,
@@ -22,6 +44,33 @@ pub struct AsmPatterns {
     pub ins_indirect_indexed_y: Regex,
 }
 
+/// Alternative of mnemonics having given addressing mode, `prefix` replaces their first letter when given.
+fn mnemonics(addrmode: AddrMode, prefix: Option<&str>) -> String {
+    let list: Vec<String> = instructions_with(addrmode)
+        .iter()
+        .filter(|i| prefix.is_none() || **i != Instruction::Bra)
+        .map(|i| match prefix {
+            Some(prefix) => format!("{}{}", prefix, &i.mnemonic()[1..]),
+            None => String::from(i.mnemonic()),
+        })
+        .collect();
+    format!("({})\\s*", list.join("|"))
+}
+
+/// Numbers, symbols and function calls combined with arithmetic operators. Address operand can not start with
+/// a parenthesis, which would be taken for indirect addressing, immediate values and items of directives can.
+fn expression(number: &str, parenthesized: bool) -> String {
+    let args = "(?:[^()]|\\([^()]*\\))*";
+    let term = format!("-?(?:(?:{}\\({}\\))|{})", NAME, args, number);
+    let group = format!("-?\\({}\\)", args);
+    let first = if parenthesized {
+        format!("(?:{})|(?:{})", term, group)
+    } else {
+        term.clone()
+    };
+    format!("(?:{})(?:\\s*[-+*/]\\s*(?:(?:{})|(?:{})))*", first, term, group)
+}
+
 fn rx(pattern: &str) -> Regex {
     Regex::new(&format!("(?i){}{}\\s*{}", LABEL, pattern, COMMENT)).unwrap()
 }
@@ -31,24 +80,69 @@ impl AsmPatterns {
         let org_cmd = String::from("((?:\\.ORG\\s+)|(?:\\*\\s*=\\s*))");
         let byte_cmd = String::from("(\\.BYTE|DCB)\\s+");
         let word_cmd = String::from("(\\.WORD)\\s+");
+        let dbyte_cmd = String::from("(\\.DBYTE)\\s+");
+        let dword_cmd = String::from("(\\.DWORD)\\s+");
+        let text_cmd = String::from("(\\.TEXT|\\.ASCIIZ)\\s+");
+        let charmap_cmd = String::from("(\\.CHARMAP)\\s+");
+        let fill_cmd = String::from("(\\.FILL|\\.RES|\\.ALIGN)\\s+");
+        let constant_cmd = format!("({})\\s*(?:=|\\.EQU\\s)\\s*", NAME);
+        let segment_cmd = String::from("(\\.SEGMENT)\\s+");
+        let begin_scope_cmd = String::from("(\\.PROC|\\.SCOPE)\\s+");
+        let end_scope_cmd = String::from("(\\.ENDPROC|\\.ENDSCOPE)\\s*");
+        let cpu_cmd = String::from("(\\.CPU)\\s+");
+        let include_cmd = String::from("(\\.INCLUDE)\\s+");
+        let export_cmd = String::from("(\\.EXPORT)\\s+");
+        let import_cmd = String::from("(\\.IMPORT)\\s+");
+        let rept_cmd = String::from("(\\.REPT)\\s+");
+        let for_cmd = String::from("(\\.FOR)\\s+");
+        let end_block_cmd = String::from("(\\.ENDREPT|\\.ENDFOR)\\s*");
+        let name_list = format!("((?:(?:{}){})+)\\s*", NAME, SEPARATOR);
         let hex_num = String::from("\\$[\\d|a-f]{1,4}");
         let dec_num = String::from("\\d{1,5}");
         let bin_num = String::from("%[01]{1,16}");
-        let mnemonic = String::from("([a-z]{3})\\s*");
+        let mnemonic = String::from("([a-z]{3}[0-7]?(?:\\.[az])?)\\s*");
         let num_or_symbol = format!("(?:{})|(?:{})|(?:{})|(?:{})", hex_num, dec_num, bin_num, SYMBOL);
-        let lo_hi_prefix = format!("[{}|{}]?", LO_BYTE_MODIFIER, HI_BYTE_MODIFIER);
-        let operand = format!("({}(?:{}))\\s*", lo_hi_prefix, num_or_symbol);
-        let operand_list = format!("((?:(?:{}(?:{})){})+)\\s*", lo_hi_prefix, num_or_symbol, SEPARATOR);
-        let branch_mnemonic = String::from("(BCC|BCS|BNE|BEQ|BMI|BPL|BVC|BVS)\\s*");
+        let lo_hi_prefix = format!("[{}|{}|{}]?", LO_BYTE_MODIFIER, HI_BYTE_MODIFIER, ABSOLUTE_MODIFIER);
+        let term = expression(&num_or_symbol, false);
+        let operand = format!("({}(?:{}))\\s*", lo_hi_prefix, term);
+        let item = expression(&num_or_symbol, true);
+        let item_operand = format!("({}(?:{}))\\s*", lo_hi_prefix, item);
+        let operand_list = format!("((?:(?:{}(?:{})){})+)\\s*", lo_hi_prefix, item, SEPARATOR);
+        let long_num_or_symbol = format!("(?:\\$[\\da-f]{{1,8}})|(?:\\d{{1,10}})|(?:%[01]{{1,32}})|(?:{})", SYMBOL);
+        let long_item = expression(&long_num_or_symbol, true);
+        let long_operand_list = format!("((?:(?:{}(?:{})){})+)\\s*", lo_hi_prefix, long_item, SEPARATOR);
+        let string_list = String::from("((?:\"(?:[^\"\\\\]|\\\\.)*\"|[^;\"])+)");
+        let branch_mnemonic = mnemonics(AddrMode::Relative, None);
+        let long_branch_mnemonic = mnemonics(AddrMode::Relative, Some("J"));
+        let bit_branch_mnemonic = mnemonics(AddrMode::ZeroPageRelative, None);
         let branch_target = format!("((?:[+|-]?\\d{{1,3}})|(?:{}))\\s*", SYMBOL);
         AsmPatterns {
             empty_line: rx(""),
-            cmd_set_location_counter: rx(&format!("{}{}", org_cmd, operand)),
+            cmd_set_location_counter: rx(&format!("{}{}", org_cmd, item_operand)),
             cmd_emit_bytes: rx(&format!("{}{}", byte_cmd, operand_list)),
             cmd_emit_words: rx(&format!("{}{}", word_cmd, operand_list)),
+            cmd_emit_dbytes: rx(&format!("{}{}", dbyte_cmd, operand_list)),
+            cmd_emit_dwords: rx(&format!("{}{}", dword_cmd, long_operand_list)),
+            cmd_emit_text: rx(&format!("{}{}", text_cmd, string_list)),
+            cmd_charmap: rx(&format!("{}{}", charmap_cmd, string_list)),
+            cmd_fill: rx(&format!("{}{}", fill_cmd, operand_list)),
+            cmd_define_constant: rx(&format!("{}{}", constant_cmd, item_operand)),
+            cmd_segment: rx(&format!("{}(\"\\w+\"|{})\\s*", segment_cmd, NAME)),
+            cmd_begin_scope: rx(&format!("{}({})\\s*", begin_scope_cmd, NAME)),
+            cmd_end_scope: rx(&end_scope_cmd),
+            cmd_cpu: rx(&format!("{}(\"\\w+\"|\\w+)\\s*", cpu_cmd)),
+            cmd_include: rx(&format!("{}(\"[^\"]+\")\\s*", include_cmd)),
+            cmd_export: rx(&format!("{}{}", export_cmd, name_list)),
+            cmd_import: rx(&format!("{}{}", import_cmd, name_list)),
+            cmd_rept: rx(&format!("{}{}", rept_cmd, item_operand)),
+            cmd_for: rx(&format!("{}({}\\s*=\\s*{}{}{})\\s*", for_cmd, NAME, item, SEPARATOR, item)),
+            cmd_end_block: rx(&end_block_cmd),
+            block_begin: Regex::new(&format!("(?i){}(?:\\.REPT|\\.FOR)\\s", LABEL)).unwrap(),
             ins_implied: rx(&format!("{}", mnemonic)),
-            ins_immediate: rx(&format!("{}#{}", mnemonic, operand)),
+            ins_immediate: rx(&format!("{}#{}", mnemonic, item_operand)),
             ins_branch: rx(&format!("{}{}", branch_mnemonic, branch_target)),
+            ins_long_branch: rx(&format!("{}{}", long_branch_mnemonic, branch_target)),
+            ins_zero_page_relative: rx(&format!("{}{},\\s*{}", bit_branch_mnemonic, operand, branch_target)),
             ins_absolute: rx(&format!("{}{}", mnemonic, operand)),
             ins_absolute_indexed_x: rx(&format!("{}{},x", mnemonic, operand)),
             ins_absolute_indexed_y: rx(&format!("{}{},y", mnemonic, operand)),
@@ -85,6 +179,38 @@ mod tests {
         assert_line(&AsmPatterns::new().empty_line, "label:", Some("label"), None, None);
     }
 
+    #[test]
+    fn match_local_and_anonymous_labels() {
+        let ap = AsmPatterns::new();
+        assert_line(&ap.empty_line, ".loop:", Some(".loop"), None, None);
+        assert_line(&ap.ins_implied, "@next: inx", Some("@next"), Some("inx"), None);
+        assert_line(&ap.ins_implied, "- inx", Some("-"), Some("inx"), None);
+        assert_line(&ap.ins_branch, "+: bne -", Some("+"), Some("bne"), Some("-"));
+        assert_line(&ap.ins_branch, "bne ++", None, Some("bne"), Some("++"));
+        assert_line(&ap.ins_long_branch, "jeq .done", None, Some("jeq"), Some(".done"));
+        assert_line(&ap.ins_branch, "bra loop", None, Some("bra"), Some("loop"));
+        assert!(!ap.ins_long_branch.is_match("jra loop"));
+        assert_line(&ap.ins_zero_page_relative, "bbr7 $10, loop", None, Some("bbr7"), Some("$10"));
+        assert_line(&ap.ins_absolute, "smb3 flags", None, Some("smb3"), Some("flags"));
+        assert_line(
+            &ap.cmd_include,
+            ".include \"defs/io.inc\" ; hw",
+            None,
+            Some(".include"),
+            Some("\"defs/io.inc\""),
+        );
+        assert_line(&ap.cmd_cpu, ".cpu \"65c02\"", None, Some(".cpu"), Some("\"65c02\""));
+        assert_line(&ap.ins_absolute, "jsr sprite::init", None, Some("jsr"), Some("sprite::init"));
+    }
+
+    #[test]
+    fn match_scope() {
+        let ap = AsmPatterns::new();
+        assert_line(&ap.cmd_begin_scope, "  .proc draw", None, Some(".proc"), Some("draw"));
+        assert_line(&ap.cmd_begin_scope, ".SCOPE gfx ; c", None, Some(".SCOPE"), Some("gfx"));
+        assert_line(&ap.cmd_end_scope, "  .endproc", None, Some(".endproc"), None);
+    }
+
     #[test]
     fn match_emit_bytes() {
         assert_line(
@@ -107,6 +233,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn match_emit_text() {
+        let ap = AsmPatterns::new();
+        assert_line(&ap.cmd_emit_text, ".text \"a;b\", 13 ; c", None, Some(".text"), Some("\"a;b\", 13"));
+        assert_line(
+            &ap.cmd_emit_text,
+            "msg: .ASCIIZ \"\\\"\"",
+            Some("msg"),
+            Some(".ASCIIZ"),
+            Some("\"\\\"\""),
+        );
+        assert_line(&ap.cmd_charmap, ".charmap \"petscii\"", None, Some(".charmap"), Some("\"petscii\""));
+    }
+
+    #[test]
+    fn match_segment() {
+        let ap = AsmPatterns::new();
+        assert_line(&ap.cmd_segment, ".segment \"VECTORS\"", None, Some(".segment"), Some("\"VECTORS\""));
+        assert_line(&ap.cmd_segment, ".SEGMENT zp ; page 0", None, Some(".SEGMENT"), Some("zp"));
+    }
+
+    #[test]
+    fn match_linkage() {
+        let ap = AsmPatterns::new();
+        assert_line(
+            &ap.cmd_export,
+            ".export start, irq ; api",
+            None,
+            Some(".export"),
+            Some("start, irq"),
+        );
+        assert_line(&ap.cmd_import, ".IMPORT print", None, Some(".IMPORT"), Some("print"));
+    }
+
+    #[test]
+    fn match_define_constant() {
+        let ap = AsmPatterns::new();
+        assert_line(&ap.cmd_define_constant, "SCREEN = $0200", None, Some("SCREEN"), Some("$0200"));
+        assert_line(&ap.cmd_define_constant, "size .equ >table ; hi", None, Some("size"), Some(">table"));
+        assert!(ap.cmd_define_constant.captures("*= $0600").is_none());
+    }
+
+    #[test]
+    fn match_fill() {
+        let ap = AsmPatterns::new();
+        assert_line(&ap.cmd_fill, ".fill 16, $ea", None, Some(".fill"), Some("16, $ea"));
+        assert_line(&ap.cmd_fill, ".align $100", None, Some(".align"), Some("$100"));
+        assert_line(&ap.cmd_emit_dwords, ".dword $12345678", None, Some(".dword"), Some("$12345678"));
+    }
+
+    #[test]
+    fn match_expressions() {
+        let ap = AsmPatterns::new();
+        assert_line(
+            &ap.ins_immediate,
+            "lda #<table + 2*SIZE",
+            None,
+            Some("lda"),
+            Some("<table + 2*SIZE"),
+        );
+        assert_line(&ap.ins_absolute_indexed_y, "sta buf-(1+n),y", None, Some("sta"), Some("buf-(1+n)"));
+        assert_line(&ap.ins_indirect_indexed_y, "lda (ptr+2),y", None, Some("lda"), Some("ptr+2"));
+        assert!(!ap.ins_absolute_indexed_y.is_match("lda (ptr),y"));
+        assert_line(
+            &ap.cmd_emit_bytes,
+            ".byte 128+sin(i*4), hi(x) ; t",
+            None,
+            Some(".byte"),
+            Some("128+sin(i*4), hi(x)"),
+        );
+        assert_line(
+            &ap.cmd_define_constant,
+            "SIZE = (END-START)/2",
+            None,
+            Some("SIZE"),
+            Some("(END-START)/2"),
+        );
+    }
+
+    #[test]
+    fn match_blocks() {
+        let ap = AsmPatterns::new();
+        assert_line(&ap.cmd_rept, ".rept SIZE/2 ; half", None, Some(".rept"), Some("SIZE/2"));
+        assert_line(&ap.cmd_for, "  .for i = 0, 255", None, Some(".for"), Some("i = 0, 255"));
+        assert_line(&ap.cmd_end_block, ".ENDFOR", None, Some(".ENDFOR"), None);
+        assert!(ap.block_begin.is_match("row: .for y = 0, 24"));
+        assert!(!ap.block_begin.is_match("; .rept 2"));
+    }
+
     #[test]
     fn match_implied() {
         let p = AsmPatterns::new().ins_implied;
@@ -127,6 +342,8 @@ mod tests {
         let p = AsmPatterns::new().ins_absolute;
         assert_line(&p, "LDY $8f", None, Some("LDY"), Some("$8f"));
         assert_line(&p, "jmp $2000", None, Some("jmp"), Some("$2000"));
+        assert_line(&p, "lda.z ptr", None, Some("lda.z"), Some("ptr"));
+        assert_line(&p, "sta !ptr", None, Some("sta"), Some("!ptr"));
     }
 
     #[test]
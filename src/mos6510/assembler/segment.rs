@@ -0,0 +1,172 @@
+use crate::error::AppError;
+
+pub const DEFAULT_SEGMENT: &str = "CODE";
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub name: String,
+    pub origin: Option<u16>,
+    pub location_counter: u16,
+    pub code: Vec<u8>,
+    emitted: usize,
+}
+
+impl Segment {
+    pub fn new(name: &str, location_counter: u16) -> Self {
+        Self {
+            name: String::from(name),
+            origin: None,
+            location_counter,
+            code: Vec::new(),
+            emitted: 0,
+        }
+    }
+
+    pub fn start(&self) -> u16 {
+        self.origin.unwrap_or(self.location_counter)
+    }
+
+    /// Address following the last byte, may be 0x10000 for a segment reaching the end of memory.
+    pub fn end(&self) -> usize {
+        self.start() as usize + self.code.len()
+    }
+
+    /// Segment holding only reserved space is not a part of the output.
+    pub fn is_initialized(&self) -> bool {
+        self.emitted > 0 && !self.code.is_empty()
+    }
+
+    /// Number of bytes emitted so far, reserved space is not counted.
+    pub fn emitted(&self) -> usize {
+        self.emitted
+    }
+
+    pub fn emit(&mut self, byte: u8, generate_code: bool) {
+        self.origin.get_or_insert(self.location_counter);
+        if generate_code {
+            self.code.push(byte);
+        }
+        self.emitted += 1;
+        self.location_counter = self.location_counter.wrapping_add(1);
+    }
+
+    pub fn reserve(&mut self, count: usize, fill: u8, generate_code: bool) {
+        self.origin.get_or_insert(self.location_counter);
+        if generate_code {
+            self.code.resize(self.code.len() + count, fill);
+        }
+        self.location_counter = self.location_counter.wrapping_add(count as u16);
+    }
+
+    pub fn set_location_counter(&mut self, addr: u16, generate_code: bool) -> Result<(), AppError> {
+        if self.origin.is_none() {
+            self.origin = Some(addr);
+            self.location_counter = addr;
+            Ok(())
+        } else if addr >= self.location_counter {
+            self.reserve((addr - self.location_counter) as usize, 0, generate_code);
+            Ok(())
+        } else {
+            Err(AppError::OriginTooLow(addr, self.location_counter))
+        }
+    }
+}
+
+/// Segments sorted by start address.
+fn sorted(segments: &[Segment]) -> Vec<&Segment> {
+    let mut sorted: Vec<&Segment> = segments.iter().collect();
+    sorted.sort_by_key(|s| s.start());
+    sorted
+}
+
+/// Largest number of bytes between consecutive segments, which `flatten` fills.
+pub fn largest_gap(segments: &[Segment]) -> usize {
+    let sorted = sorted(segments);
+    sorted
+        .windows(2)
+        .map(|pair| (pair[1].start() as usize).saturating_sub(pair[0].end()))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Merges segments into a single image starting at the lowest address, gaps are filled with given byte.
+/// Segments must not overlap.
+pub fn flatten(segments: &[Segment], fill: u8) -> Result<(u16, Vec<u8>), AppError> {
+    let sorted = sorted(segments);
+    if let Some(pair) = sorted.windows(2).find(|pair| (pair[1].start() as usize) < pair[0].end()) {
+        return Err(AppError::SegmentOverlap(pair[0].name.clone(), pair[1].name.clone()));
+    }
+    let start = segments.iter().map(|s| s.start()).min().unwrap_or(0);
+    let end = segments.iter().map(|s| s.end()).max().unwrap_or(start as usize);
+    let mut image = vec![fill; end - start as usize];
+    for segment in segments {
+        let offset = (segment.start() - start) as usize;
+        image[offset..offset + segment.code.len()].copy_from_slice(&segment.code);
+    }
+    Ok((start, image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(name: &str, origin: u16, code: &[u8]) -> Segment {
+        let mut segment = Segment::new(name, 0);
+        segment.set_location_counter(origin, true).unwrap();
+        code.iter().for_each(|b| segment.emit(*b, true));
+        segment
+    }
+
+    #[test]
+    fn reserved_only() {
+        let mut zp = Segment::new("ZP", 0x10);
+        zp.reserve(2, 0, true);
+        assert_eq!(zp.location_counter, 0x12);
+        assert!(!zp.is_initialized());
+        zp.emit(1, true);
+        assert!(zp.is_initialized());
+        assert_eq!(zp.start(), 0x10);
+    }
+
+    #[test]
+    fn without_origin() {
+        let mut data = Segment::new("DATA", 0);
+        [1, 2, 3].iter().for_each(|b| data.emit(*b, true));
+        assert_eq!((data.start(), data.end()), (0, 3));
+        assert!(data.set_location_counter(0x0004, true).is_ok());
+        assert_eq!(data.code, &[1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn origin_too_low() {
+        let mut s = segment("CODE", 0x0600, &[0xea]);
+        assert!(s.set_location_counter(0x0604, true).is_ok());
+        assert_eq!(s.code, &[0xea, 0, 0, 0]);
+        assert!(matches!(
+            s.set_location_counter(0x0600, true),
+            Err(AppError::OriginTooLow(0x0600, 0x0604))
+        ));
+    }
+
+    #[test]
+    fn flatten_segments() {
+        let segments = [segment("B", 0x0604, &[3, 4]), segment("A", 0x0600, &[1])];
+        let (start, image) = flatten(&segments, 0xff).unwrap();
+        assert_eq!(start, 0x0600);
+        assert_eq!(image, &[1, 0xff, 0xff, 0xff, 3, 4]);
+        assert_eq!(largest_gap(&segments), 3);
+        let (start, image) = flatten(&[segment("V", 0xfffe, &[1, 2])], 0).unwrap();
+        assert_eq!(start, 0xfffe);
+        assert_eq!(image, &[1, 2]);
+        assert_eq!(largest_gap(&[segment("V", 0xfffe, &[1, 2])]), 0);
+    }
+
+    #[test]
+    fn overlapping_segments() {
+        let segments = [segment("CODE", 0x0600, &[1, 2, 3]), segment("DATA", 0x0602, &[4])];
+        assert!(matches!(
+            flatten(&segments, 0),
+            Err(AppError::SegmentOverlap(a, b)) if a == "CODE" && b == "DATA"
+        ));
+    }
+}
@@ -10,7 +10,7 @@ impl<'a> Tokens<'a> {
     }
 
     pub fn label(&self) -> Option<&str> {
-        self.get_str(1)
+        self.get_str(1).map(|label| label.trim_end_matches(':'))
     }
 
     pub fn operation(&self) -> Option<&str> {
@@ -21,6 +21,20 @@ impl<'a> Tokens<'a> {
         self.get_str(3)
     }
 
+    /// Second operand, e.g. branch target of `BBR0 zp,target`.
+    pub fn target(&self) -> Option<&str> {
+        self.get_str(4)
+    }
+
+    /// Column (counted from 1) and length of the token.
+    pub fn span(&self, i: usize) -> Option<(usize, usize)> {
+        self.captures.get(i).map(|m| {
+            let text = m.as_str();
+            let start = m.start() + text.len() - text.trim_start().len();
+            (start + 1, text.trim().len())
+        })
+    }
+
     fn get_str(&self, i: usize) -> Option<&str> {
         self.captures.get(i).map_or(None, |m| Some(m.as_str().trim()))
     }
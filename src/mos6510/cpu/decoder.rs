@@ -36,6 +36,9 @@ impl OpCodeEntry {
             AddrMode::Absolute => Env::prep_absolute,
             AddrMode::AbsoluteX => Env::prep_absolute_x,
             AddrMode::AbsoluteY => Env::prep_absolute_y,
+            AddrMode::ZeroPageIndirect | AddrMode::AbsoluteIndexedIndirect | AddrMode::ZeroPageRelative => {
+                unreachable!("{:?} is not in the NMOS opcode table", addrmode)
+            }
         }
     }
 
@@ -98,6 +101,82 @@ impl OpCodeEntry {
             Instruction::Plp => Cpu::exec_plp,
             Instruction::Nop => Cpu::exec_nop,
             Instruction::Kil => Cpu::exec_kil,
+            // the table is built from documented NMOS opcodes, extended ones are assembled only
+            Instruction::Lax
+            | Instruction::Sax
+            | Instruction::Dcp
+            | Instruction::Isc
+            | Instruction::Slo
+            | Instruction::Rla
+            | Instruction::Sre
+            | Instruction::Rra
+            | Instruction::Anc
+            | Instruction::Alr
+            | Instruction::Arr
+            | Instruction::Sbx
+            | Instruction::Las
+            | Instruction::Bra
+            | Instruction::Stz
+            | Instruction::Trb
+            | Instruction::Tsb
+            | Instruction::Phx
+            | Instruction::Phy
+            | Instruction::Plx
+            | Instruction::Ply
+            | Instruction::Wai
+            | Instruction::Stp
+            | Instruction::Bbr0
+            | Instruction::Bbr1
+            | Instruction::Bbr2
+            | Instruction::Bbr3
+            | Instruction::Bbr4
+            | Instruction::Bbr5
+            | Instruction::Bbr6
+            | Instruction::Bbr7
+            | Instruction::Bbs0
+            | Instruction::Bbs1
+            | Instruction::Bbs2
+            | Instruction::Bbs3
+            | Instruction::Bbs4
+            | Instruction::Bbs5
+            | Instruction::Bbs6
+            | Instruction::Bbs7
+            | Instruction::Rmb0
+            | Instruction::Rmb1
+            | Instruction::Rmb2
+            | Instruction::Rmb3
+            | Instruction::Rmb4
+            | Instruction::Rmb5
+            | Instruction::Rmb6
+            | Instruction::Rmb7
+            | Instruction::Smb0
+            | Instruction::Smb1
+            | Instruction::Smb2
+            | Instruction::Smb3
+            | Instruction::Smb4
+            | Instruction::Smb5
+            | Instruction::Smb6
+            | Instruction::Smb7
+            | Instruction::Cle
+            | Instruction::See
+            | Instruction::Tsy
+            | Instruction::Tys
+            | Instruction::Inz
+            | Instruction::Dez
+            | Instruction::Neg
+            | Instruction::Asr
+            | Instruction::Taz
+            | Instruction::Tza
+            | Instruction::Tab
+            | Instruction::Tba
+            | Instruction::Phz
+            | Instruction::Plz
+            | Instruction::Ldz
+            | Instruction::Cpz
+            | Instruction::Inw
+            | Instruction::Dew
+            | Instruction::Asw
+            | Instruction::Row => unreachable!("{:?} is not in the NMOS opcode table", instruction),
         }
     }
 }
@@ -0,0 +1,182 @@
+use crate::error::AppError;
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+const HEADER: &str = "; mo65x debug info";
+
+/// Range of bytes emitted by a single source line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineInfo {
+    pub address: u16,
+    pub size: u16,
+    pub file: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeInfo {
+    pub name: String,
+    pub address: u16,
+    pub size: u16,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DebugInfo {
+    pub files: Vec<String>,
+    pub lines: Vec<LineInfo>,
+    pub scopes: Vec<ScopeInfo>,
+}
+
+fn contains(address: u16, size: u16, addr: u16) -> bool {
+    addr.wrapping_sub(address) < size
+}
+
+impl DebugInfo {
+    pub fn line_at(&self, addr: u16) -> Option<&LineInfo> {
+        self.lines.iter().find(|l| contains(l.address, l.size, addr))
+    }
+
+    /// Innermost scope containing given address.
+    pub fn scope_at(&self, addr: u16) -> Option<&ScopeInfo> {
+        self.scopes
+            .iter()
+            .filter(|s| contains(s.address, s.size, addr))
+            .min_by_key(|s| s.size)
+    }
+
+    /// Address of the code generated by given line or by the nearest following line which generated any code.
+    /// File name may be given with or without leading directories.
+    pub fn address_of(&self, file: &str, line: usize) -> Option<u16> {
+        let file = self.files.iter().position(|f| Path::new(f).ends_with(file))?;
+        self.lines
+            .iter()
+            .filter(|l| l.file == file && l.line >= line)
+            .min_by_key(|l| (l.line, l.address))
+            .map(|l| l.address)
+    }
+
+    pub fn location(&self, info: &LineInfo) -> String {
+        format!("{}:{}", self.files[info.file], info.line)
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        for (i, file) in self.files.iter().enumerate() {
+            writeln!(out, "file {} {}", i, file)?;
+        }
+        for l in &self.lines {
+            writeln!(out, "line {:04X} {:04X} {} {}", l.address, l.size, l.file, l.line)?;
+        }
+        for s in &self.scopes {
+            writeln!(out, "scope {:04X} {:04X} {}", s.address, s.size, s.name)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: BufRead>(input: R) -> Result<Self, AppError> {
+        let mut info = Self::default();
+        for (num, line) in input.lines().enumerate() {
+            let line = line?;
+            let invalid = || AppError::InvalidDebugInfo(num + 1);
+            let hex = |s: Option<&str>| s.and_then(|s| u16::from_str_radix(s, 16).ok()).ok_or_else(invalid);
+            let dec = |s: Option<&str>| s.and_then(|s| s.parse::<usize>().ok()).ok_or_else(invalid);
+            let mut fields = line.trim().splitn(4, ' ');
+            match fields.next() {
+                Some("file") => {
+                    if dec(fields.next())? != info.files.len() {
+                        return Err(invalid());
+                    }
+                    let rest: Vec<&str> = fields.collect();
+                    info.files.push(rest.join(" "));
+                }
+                Some("line") => {
+                    let (address, size) = (hex(fields.next())?, hex(fields.next())?);
+                    let mut rest = fields.next().unwrap_or_default().split(' ');
+                    let (file, line) = (dec(rest.next())?, dec(rest.next())?);
+                    if file >= info.files.len() || line == 0 {
+                        return Err(invalid());
+                    }
+                    info.lines.push(LineInfo { address, size, file, line });
+                }
+                Some("scope") => {
+                    let (address, size) = (hex(fields.next())?, hex(fields.next())?);
+                    let name = String::from(fields.next().ok_or_else(invalid)?);
+                    info.scopes.push(ScopeInfo { name, address, size });
+                }
+                Some(s) if s.is_empty() || s.starts_with(';') => {}
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn debug_info() -> DebugInfo {
+        DebugInfo {
+            files: vec![String::from("asm/demo scene.asm")],
+            lines: vec![
+                LineInfo {
+                    address: 0x0600,
+                    size: 2,
+                    file: 0,
+                    line: 3,
+                },
+                LineInfo {
+                    address: 0x0602,
+                    size: 3,
+                    file: 0,
+                    line: 6,
+                },
+            ],
+            scopes: vec![
+                ScopeInfo {
+                    name: String::from("main"),
+                    address: 0x0600,
+                    size: 5,
+                },
+                ScopeInfo {
+                    name: String::from("main::inner"),
+                    address: 0x0602,
+                    size: 3,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let info = debug_info();
+        assert_eq!(info.line_at(0x0601).map(|l| l.line), Some(3));
+        assert_eq!(info.line_at(0x0605), None);
+        assert_eq!(info.location(info.line_at(0x0604).unwrap()), "asm/demo scene.asm:6");
+        assert_eq!(info.scope_at(0x0603).map(|s| s.name.as_str()), Some("main::inner"));
+        assert_eq!(info.scope_at(0x0601).map(|s| s.name.as_str()), Some("main"));
+        assert_eq!(info.address_of("demo scene.asm", 4), Some(0x0602));
+        assert_eq!(info.address_of("asm/demo scene.asm", 3), Some(0x0600));
+        assert_eq!(info.address_of("scene.asm", 3), None);
+        assert_eq!(info.address_of("demo scene.asm", 7), None);
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut out = Vec::new();
+        debug_info().write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().nth(2), Some("line 0600 0002 0 3"));
+        assert_eq!(DebugInfo::read(text.as_bytes()).unwrap(), debug_info());
+        assert!(matches!(
+            DebugInfo::read("line 0600 0002 1 3".as_bytes()),
+            Err(AppError::InvalidDebugInfo(1))
+        ));
+        assert!(matches!(
+            DebugInfo::read("file 0 a.asm\nline 0600 0002 0 0".as_bytes()),
+            Err(AppError::InvalidDebugInfo(2))
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+//! Comparison of two binary images loaded at the same address, changed code is disassembled side by side.
+
+use super::{
+    disassembler::{disassemble_traced, linear, trace, Hints, Trace},
+    memory::Memory,
+};
+
+/// Minimal width of the disassembly of the first image.
+const SIDE_WIDTH: usize = 30;
+
+/// Address range (inclusive) of bytes differing between the images, bytes beyond the end of an image differ.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Change {
+    pub first: u16,
+    pub last: u16,
+}
+
+pub fn changes(a: &[u8], b: &[u8], base: u16) -> Vec<Change> {
+    let len = a.len().max(b.len()).min(0x10000 - base as usize);
+    let mut changes: Vec<Change> = Vec::new();
+    for index in (0..len).filter(|i| a.get(*i) != b.get(*i)) {
+        let address = base + index as u16;
+        match changes.last_mut() {
+            Some(change) if change.last.wrapping_add(1) == address => change.last = address,
+            _ => changes.push(Change {
+                first: address,
+                last: address,
+            }),
+        }
+    }
+    changes
+}
+
+/// Rows of the disassembly overlapping the change.
+fn side(memory: &Memory, trace: &Trace, change: &Change) -> Vec<String> {
+    trace
+        .rows(memory)
+        .iter()
+        .zip(disassemble_traced(memory, trace, None))
+        .filter(|(row, _)| row.address <= change.last && row.address as usize + row.len > change.first as usize)
+        .map(|(_, columns)| format!("{}{}{}", columns.0, columns.1, columns.2))
+        .collect()
+}
+
+/// Each change with the rows of both images next to each other, code is found by tracing when `traced`,
+/// otherwise every byte is decoded as an instruction.
+pub fn diff_lines(a: &[u8], b: &[u8], base: u16, traced: bool) -> Vec<String> {
+    let len = a.len().max(b.len()).min(0x10000 - base as usize);
+    let load = |data: &[u8]| {
+        let mut memory = Memory::new();
+        memory.set_block(base, &data[..len.min(data.len())]);
+        let trace = match traced {
+            true => trace(&memory, base, len, &Hints::default()),
+            false => linear(&memory, base, len),
+        };
+        (memory, trace)
+    };
+    let (memory_a, trace_a) = load(a);
+    let (memory_b, trace_b) = load(b);
+    let mut lines = Vec::new();
+    if a.len() != b.len() {
+        lines.push(format!("sizes differ: {} and {} bytes", a.len(), b.len()));
+    }
+    for change in changes(a, b, base) {
+        let count = change.last as usize - change.first as usize + 1;
+        lines.push(format!("{:04X}-{:04X}: {} byte(s) differ", change.first, change.last, count));
+        let left = side(&memory_a, &trace_a, &change);
+        let right = side(&memory_b, &trace_b, &change);
+        let width = left.iter().map(String::len).fold(SIDE_WIDTH, usize::max);
+        for i in 0..left.len().max(right.len()) {
+            let line = format!(
+                "  {:2$} | {}",
+                left.get(i).map_or("", String::as_str),
+                right.get(i).map_or("", String::as_str),
+                width
+            );
+            lines.push(line.trim_end().to_string());
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_runs() {
+        let a = [0xa9, 0x01, 0x8d, 0x20, 0xd0, 0x60];
+        let b = [0xa9, 0x02, 0x8d, 0x21, 0xd0, 0x60, 0x00];
+        assert_eq!(
+            changes(&a, &b, 0x0600),
+            &[
+                Change {
+                    first: 0x0601,
+                    last: 0x0601
+                },
+                Change {
+                    first: 0x0603,
+                    last: 0x0603
+                },
+                Change {
+                    first: 0x0606,
+                    last: 0x0606
+                },
+            ]
+        );
+        assert_eq!(
+            diff_lines(&a, &b[..6], 0x0600, false),
+            &[
+                "0601-0601: 1 byte(s) differ",
+                "  0600 A9 01     LDA #$01        | 0600 A9 02     LDA #$02",
+                "0603-0603: 1 byte(s) differ",
+                "  0602 8D 20 D0  STA $D020       | 0602 8D 21 D0  STA $D021",
+            ]
+        );
+        // bytes beyond the end of memory are left out
+        let (a, b) = (vec![0xea; 0x2001], vec![0xea; 0x2000]);
+        assert_eq!(diff_lines(&a, &b, 0xe000, false), &["sizes differ: 8193 and 8192 bytes"]);
+    }
+}
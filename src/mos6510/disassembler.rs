@@ -1,10 +1,26 @@
+mod annotations;
+mod config;
+mod json;
+mod source;
+mod symbol_map;
+mod trace;
+mod xref;
+
 use super::{addrmode::AddrMode, memory::Memory, operation::Operation};
 use crate::error::AppError;
+pub use annotations::{arrows, effective_address, Annotations};
+pub use config::Config;
+pub use json::{decode, json_range, json_traced, Decoded};
+pub use source::{label, write_source};
 use std::{fs::File, io::Read, path::Path};
+pub use symbol_map::SymbolMap;
+pub use trace::{linear, trace, ByteKind, Hints, Region, RegionKind, Row, Trace, VECTORS};
+pub use xref::{xrefs, Access, Xrefs};
 
-type Columns = (String, String, String);
+pub type Columns = (String, String, String);
 
-pub fn disassemble(memory: &Memory, pc: &mut u16) -> Columns {
+/// Operand addresses known to `symbols` are shown by name, hardware registers are named in a comment.
+pub fn disassemble(memory: &Memory, pc: &mut u16, symbols: Option<&SymbolMap>) -> Columns {
     let mut buf = (format!("{:04X} ", pc), String::new(), String::new());
     let opcode = memory[*pc];
     let operation = Operation::get(opcode);
@@ -18,41 +34,158 @@ pub fn disassemble(memory: &Memory, pc: &mut u16) -> Columns {
     }
     buf.2.push_str(&format!(" {} ", operation.instruction.mnemonic()));
     let opaddr = *pc + 1;
+    let (byte, word) = (memory[opaddr], memory.word(opaddr));
+    let name = |address: u16, number: String| symbols.and_then(|s| s.label(address)).map_or(number, String::from);
+    let zp = name(byte as u16, format!("${:02X}", byte));
+    let abs = name(word, format!("${:04X}", word));
+    let target = |offset: u8, len: u16| {
+        let address = pc.wrapping_add(len).wrapping_add(offset as i8 as u16);
+        name(address, format!("${:04X}", address))
+    };
     buf.2.push_str(&match operation.addrmode {
         AddrMode::Implied => String::from(""),
-        AddrMode::Relative => format!("${:04X}", *pc as i32 + (memory[opaddr] as i8) as i32 + 2),
-        AddrMode::Immediate => format!("#${:02X}", memory[opaddr]),
-        AddrMode::ZeroPage => format!("${:02X}", memory[opaddr]),
-        AddrMode::ZeroPageX => format!("${:02X},X", memory[opaddr]),
-        AddrMode::ZeroPageY => format!("${:02X},Y", memory[opaddr]),
-        AddrMode::IndexedIndirectX => format!("(${:02X},X)", memory[opaddr]),
-        AddrMode::IndirectIndexedY => format!("(${:02X}),Y", memory[opaddr]),
-        AddrMode::Indirect => format!("(${:04X})", memory.word(opaddr)),
-        AddrMode::Absolute => format!("${:04X}", memory.word(opaddr)),
-        AddrMode::AbsoluteX => format!("${:04X},X", memory.word(opaddr)),
-        AddrMode::AbsoluteY => format!("${:04X},Y", memory.word(opaddr)),
+        AddrMode::Relative => target(byte, 2),
+        AddrMode::Immediate => format!("#${:02X}", byte),
+        AddrMode::ZeroPage => zp,
+        AddrMode::ZeroPageX => format!("{},X", zp),
+        AddrMode::ZeroPageY => format!("{},Y", zp),
+        AddrMode::IndexedIndirectX => format!("({},X)", zp),
+        AddrMode::IndirectIndexedY => format!("({}),Y", zp),
+        AddrMode::Indirect => format!("({})", abs),
+        AddrMode::Absolute => abs,
+        AddrMode::AbsoluteX => format!("{},X", abs),
+        AddrMode::AbsoluteY => format!("{},Y", abs),
+        AddrMode::ZeroPageIndirect => format!("({})", zp),
+        AddrMode::AbsoluteIndexedIndirect => format!("({},X)", abs),
+        AddrMode::ZeroPageRelative => format!("{},{}", zp, target(memory[opaddr + 1], 3)),
     });
+    if let Some(notes) = symbols.and_then(|s| s.notes(*pc, operand_address(memory, *pc, operation))) {
+        buf.2.push_str(&format!(" ; {}", notes));
+    }
     *pc += opsize;
     buf
 }
 
-pub fn disassemble_file<F: AsRef<Path>>(start_addr: u16, end_addr: Option<u16>, fpath: F) -> Result<Vec<Columns>, AppError> {
+/// Address given by the operand, without indexing.
+fn operand_address(memory: &Memory, pc: u16, operation: &Operation) -> Option<u16> {
+    match operation.addrmode {
+        AddrMode::Implied | AddrMode::Relative | AddrMode::Immediate => None,
+        AddrMode::Indirect | AddrMode::Absolute | AddrMode::AbsoluteX | AddrMode::AbsoluteY | AddrMode::AbsoluteIndexedIndirect => {
+            Some(memory.word(pc.wrapping_add(1)))
+        }
+        _ => Some(memory[pc.wrapping_add(1)] as u16),
+    }
+}
+
+/// Row with a label, printed before the row at its address.
+pub fn label_row(name: &str) -> Columns {
+    (String::new(), String::new(), format!("{}:", name))
+}
+
+/// String literal of the bytes, characters other than printable ASCII are escaped.
+fn quoted(bytes: &[u8]) -> String {
+    let text: String = bytes
+        .iter()
+        .map(|b| match b {
+            b'"' => String::from("\\\""),
+            b'\\' => String::from("\\\\"),
+            0x20..=0x7e => String::from(*b as char),
+            _ => format!("\\x{:02X}", b),
+        })
+        .collect();
+    format!("\"{}\"", text)
+}
+
+/// Instructions of traced code, `.BYTE` rows of data (broken at jump targets), `.WORD` rows of pointers and `.TEXT` rows.
+pub fn disassemble_traced(memory: &Memory, trace: &Trace, symbols: Option<&SymbolMap>) -> Vec<Columns> {
+    let data = |address: u16, text: String| {
+        let text = match symbols.and_then(|s| s.comment(address)) {
+            Some(comment) => format!("{} ; {}", text, comment),
+            None => text,
+        };
+        (format!("{:04X} ", address), " ".repeat(9), text)
+    };
+    let mut lines = Vec::new();
+    for row in trace.rows(memory) {
+        if let Some(name) = symbols.and_then(|s| s.label(row.address)) {
+            lines.push(label_row(name));
+        }
+        lines.push(match row.kind {
+            ByteKind::Word => {
+                let pointer = memory.word(row.address);
+                let name = symbols
+                    .and_then(|s| s.label(pointer))
+                    .map_or(format!("${:04X}", pointer), String::from);
+                data(row.address, format!(" .WORD {}", name))
+            }
+            ByteKind::Data | ByteKind::Operand => {
+                let bytes: Vec<String> = memory.view(row.address, row.len).iter().map(|b| format!("${:02X}", b)).collect();
+                data(row.address, format!(" .BYTE {}", bytes.join(",")))
+            }
+            ByteKind::Text => data(row.address, format!(" .TEXT {}", quoted(memory.view(row.address, row.len)))),
+            ByteKind::Code => disassemble(memory, &mut { row.address }, symbols),
+        });
+    }
+    lines
+}
+
+/// Memory with the file loaded at `start_addr` and the end of disassembled range, by default the end of the file.
+pub fn load_file<F: AsRef<Path>>(start_addr: u16, end_addr: Option<u16>, fpath: F) -> Result<(Memory, u16), AppError> {
+    let (memory, end) = load_image(start_addr, fpath)?;
+    Ok((memory, end_addr.unwrap_or(end.min(0xffff) as u16)))
+}
+
+/// Memory with as much of the file as fits loaded at `start_addr` and the end of the loaded bytes (exclusive, at most $10000).
+pub fn load_image<F: AsRef<Path>>(start_addr: u16, fpath: F) -> Result<(Memory, usize), AppError> {
     let mut buf = Vec::new();
-    let fsize = File::open(&fpath)?.read_to_end(&mut buf)?;
-    let end_addr = end_addr.unwrap_or(start_addr.saturating_add(fsize as u16));
+    File::open(&fpath)?.read_to_end(&mut buf)?;
+    let len = buf.len().min(0x10000 - start_addr as usize);
     let mut memory = Memory::new();
-    memory.set_block(start_addr, &buf);
+    memory.set_block(start_addr, &buf[..len]);
+    Ok((memory, start_addr as usize + len))
+}
+
+/// Every byte from `start_addr` to `end_addr` is decoded as an instruction.
+pub fn disassemble_range(memory: &Memory, start_addr: u16, end_addr: u16, symbols: Option<&SymbolMap>) -> Vec<Columns> {
     let mut lc = start_addr;
     let mut lines = Vec::new();
     while lc < end_addr {
-        lines.push(disassemble(&memory, &mut lc));
+        if let Some(name) = symbols.and_then(|s| s.label(lc)) {
+            lines.push(label_row(name));
+        }
+        lines.push(disassemble(memory, &mut lc, symbols));
     }
-    Ok(lines)
+    lines
+}
+
+pub fn disassemble_file<F: AsRef<Path>>(
+    start_addr: u16,
+    end_addr: Option<u16>,
+    fpath: F,
+    symbols: Option<&SymbolMap>,
+) -> Result<Vec<Columns>, AppError> {
+    let (memory, end_addr) = load_file(start_addr, end_addr, fpath)?;
+    Ok(disassemble_range(&memory, start_addr, end_addr, symbols))
+}
+
+/// Code is separated from data by tracing execution from the entry points given by `hints` or the vectors,
+/// see `trace`.
+pub fn trace_file<F: AsRef<Path>>(
+    start_addr: u16,
+    end_addr: Option<u16>,
+    fpath: F,
+    hints: &Hints,
+    symbols: Option<&SymbolMap>,
+) -> Result<Vec<Columns>, AppError> {
+    let (memory, end_addr) = load_file(start_addr, end_addr, fpath)?;
+    let len = end_addr.saturating_sub(start_addr) as usize;
+    Ok(disassemble_traced(&memory, &trace(&memory, start_addr, len, hints), symbols))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mos6510::symbols::{Symbol, SymbolKind};
 
     #[test]
     fn mode_absolute() {
@@ -61,8 +194,76 @@ mod tests {
         memory[pc] = 0xad;
         memory.set_word(pc + 1, 0x1234);
         assert_eq!(
-            disassemble(&memory, &mut pc),
+            disassemble(&memory, &mut pc, None),
             ("1000 ".to_string(), "AD 34 12 ".to_string(), " LDA $1234".to_string())
         );
     }
+
+    #[test]
+    fn file_past_end_of_memory() {
+        let path = std::env::temp_dir().join(format!("mo65x-load-image-{}", std::process::id()));
+        let data: Vec<u8> = (0..20000).map(|i| i as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let (memory, end) = load_image(0xc000, &path).unwrap();
+        let (_, end_addr) = load_file(0xc000, None, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(end, 0x10000);
+        assert_eq!(end_addr, 0xffff);
+        assert_eq!(memory[0xffff], data[0x3fff]);
+    }
+
+    #[test]
+    fn traced_rows() {
+        let mut memory = Memory::new();
+        // jmp next / .byte 1..10 / next: jmp (ptr) / ptr: .word next
+        memory.set_block(
+            0x1000,
+            &[0x4c, 0x0d, 0x10, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 0x6c, 0x10, 0x10, 0x0d, 0x10],
+        );
+        let hints = Hints::default();
+        let lines: Vec<String> = disassemble_traced(&memory, &trace(&memory, 0x1000, 18, &hints), None)
+            .iter()
+            .map(|c| format!("{}{}{}", c.0, c.1, c.2))
+            .collect();
+        assert_eq!(
+            lines,
+            &[
+                "1000 4C 0D 10  JMP $100D",
+                "1003           .BYTE $01,$02,$03,$04,$05,$06,$07,$08",
+                "100B           .BYTE $09,$0A",
+                "100D 6C 10 10  JMP ($1010)",
+                "1010           .WORD $100D",
+            ]
+        );
+    }
+    #[test]
+    fn symbol_names() {
+        let mut memory = Memory::new();
+        // lda screen,x / jsr draw_main / sta $d020 / bne start
+        memory.set_block(0x0600, &[0xbd, 0x00, 0x02, 0x20, 0x10, 0x06, 0x8d, 0x20, 0xd0, 0xd0, 0xf5]);
+        let symbol = |name: &str, value: i32| Symbol {
+            name: String::from(name),
+            value,
+            kind: SymbolKind::Label,
+            line: None,
+            file: None,
+        };
+        let mut symbols = SymbolMap::default();
+        symbols.add_labels(&[symbol("start", 0x0600), symbol("screen", 0x0200), symbol("draw_main", 0x0610)]);
+        symbols.add_registers(&[symbol("BORDER", 0xd020)]);
+        let lines: Vec<String> = disassemble_traced(&memory, &linear(&memory, 0x0600, 11), Some(&symbols))
+            .iter()
+            .map(|c| format!("{}{}{}", c.0, c.1, c.2))
+            .collect();
+        assert_eq!(
+            lines,
+            &[
+                "start:",
+                "0600 BD 00 02  LDA screen,X",
+                "0603 20 10 06  JSR draw_main",
+                "0606 8D 20 D0  STA $D020 ; BORDER",
+                "0609 D0 F5     BNE start",
+            ]
+        );
+    }
 }
@@ -0,0 +1,239 @@
+//! Optional columns of the disassembly: cycles, modified flags and effective address of instructions,
+//! and arrows in the margin connecting jumps with their targets.
+
+use super::Columns;
+use crate::mos6510::{addrmode::AddrMode, cpu::registers::Registers, instruction::Instruction, memory::Memory, operation::Operation};
+use std::collections::HashMap;
+
+const MAX_LANES: usize = 8;
+/// Minimal width of disassembly lines followed by the columns.
+const LINE_WIDTH: usize = 28;
+const CYCLES_WIDTH: usize = 4;
+const FLAGS_WIDTH: usize = 7;
+const EFFECTIVE_WIDTH: usize = 6;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Annotations {
+    pub cycles: bool,
+    pub flags: bool,
+    /// Effective address of the instruction at PC, known only with CPU state.
+    pub effective: bool,
+    pub arrows: bool,
+}
+
+impl Annotations {
+    /// Letters `c` (cycles), `f` (flags), `e` (effective address) and `a` (arrows) select the columns.
+    pub fn from_letters(letters: &str) -> Self {
+        let letters = letters.to_lowercase();
+        Self {
+            cycles: letters.contains('c'),
+            flags: letters.contains('f'),
+            effective: letters.contains('e'),
+            arrows: letters.contains('a'),
+        }
+    }
+
+    /// Width of the cycles, flags and effective address columns.
+    pub fn width(&self) -> usize {
+        self.cycles as usize * CYCLES_WIDTH + self.flags as usize * FLAGS_WIDTH + self.effective as usize * EFFECTIVE_WIDTH
+    }
+
+    /// Cycles and flags of the instruction at `address`, and its effective address when `regs` are given.
+    pub fn columns(&self, memory: &Memory, address: u16, regs: Option<&Registers>) -> String {
+        let operation = Operation::get(memory[address]);
+        let mut text = String::new();
+        if self.cycles {
+            text.push_str(&format!("{:1$}", operation.timing(), CYCLES_WIDTH));
+        }
+        if self.flags {
+            text.push_str(&format!("{:1$}", operation.instruction.flags(), FLAGS_WIDTH));
+        }
+        if self.effective {
+            let effective = regs.and_then(|regs| effective_address(memory, regs));
+            text.push_str(&effective.map_or(" ".repeat(EFFECTIVE_WIDTH), |a| format!("@{:04X} ", a)));
+        }
+        text
+    }
+
+    /// Lines of `dasm` output, the columns follow the instruction text of code rows.
+    pub fn lines(&self, memory: &Memory, lines: &[Columns]) -> Vec<String> {
+        let rows: Vec<Option<u16>> = lines.iter().map(code_address).collect();
+        let margins = match self.arrows {
+            true => arrows(memory, &rows),
+            false => vec![String::new(); rows.len()],
+        };
+        let width = lines
+            .iter()
+            .map(|columns| columns.0.len() + columns.1.len() + columns.2.len())
+            .fold(LINE_WIDTH, usize::max);
+        lines
+            .iter()
+            .zip(rows)
+            .zip(margins)
+            .map(|((columns, row), margin)| {
+                let line = format!("{}{}{}{}", margin, columns.0, columns.1, columns.2);
+                match row.filter(|_| self.width() > 0) {
+                    Some(address) => {
+                        let width = margin.len() + width;
+                        format!("{:2$} {}", line, self.columns(memory, address, None), width)
+                            .trim_end()
+                            .to_string()
+                    }
+                    None => line,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Address of the instruction of a code row, label and data rows have none.
+fn code_address(columns: &Columns) -> Option<u16> {
+    match columns.2.starts_with(" .") {
+        true => None,
+        false => u16::from_str_radix(columns.0.trim(), 16).ok(),
+    }
+}
+
+/// Address the instruction at PC accesses, or the address of the byte it modifies, given current registers.
+pub fn effective_address(memory: &Memory, regs: &Registers) -> Option<u16> {
+    let operation = Operation::get(memory[regs.pc]);
+    let byte = memory[regs.pc.wrapping_add(1)];
+    let word = memory.word(regs.pc.wrapping_add(1));
+    Some(match operation.addrmode {
+        AddrMode::Implied | AddrMode::Immediate | AddrMode::Relative | AddrMode::ZeroPageRelative => return None,
+        AddrMode::ZeroPage => byte as u16,
+        AddrMode::ZeroPageX => byte.wrapping_add(regs.x) as u16,
+        AddrMode::ZeroPageY => byte.wrapping_add(regs.y) as u16,
+        AddrMode::IndexedIndirectX => memory.word(byte.wrapping_add(regs.x) as u16),
+        AddrMode::IndirectIndexedY => memory.word(byte as u16).wrapping_add(regs.y as u16),
+        AddrMode::ZeroPageIndirect => memory.word(byte as u16),
+        AddrMode::Indirect => memory.word(word),
+        AddrMode::Absolute => word,
+        AddrMode::AbsoluteX => word.wrapping_add(regs.x as u16),
+        AddrMode::AbsoluteY => word.wrapping_add(regs.y as u16),
+        AddrMode::AbsoluteIndexedIndirect => memory.word(word.wrapping_add(regs.x as u16)),
+    })
+}
+
+/// Destination of a branch or absolute jump.
+fn jump_target(memory: &Memory, pc: u16) -> Option<u16> {
+    let operation = Operation::get(memory[pc]);
+    let relative = |offset: u8, len: u16| pc.wrapping_add(len).wrapping_add(offset as i8 as u16);
+    match (operation.instruction, operation.addrmode) {
+        (_, AddrMode::Relative) => Some(relative(memory[pc.wrapping_add(1)], 2)),
+        (_, AddrMode::ZeroPageRelative) => Some(relative(memory[pc.wrapping_add(2)], 3)),
+        (Instruction::Jmp, AddrMode::Absolute) => Some(memory.word(pc.wrapping_add(1))),
+        _ => None,
+    }
+}
+
+/// Margins of the rows (addresses of code rows) with arrows from jumps to their targets among the rows,
+/// shorter jumps are closer to the code. Jumps which do not fit in the lanes are not shown.
+pub fn arrows(memory: &Memory, rows: &[Option<u16>]) -> Vec<String> {
+    let mut index = HashMap::new();
+    for (i, address) in rows.iter().enumerate() {
+        if let Some(address) = address {
+            index.entry(*address).or_insert(i);
+        }
+    }
+    let mut jumps: Vec<(usize, usize)> = rows
+        .iter()
+        .enumerate()
+        .filter_map(|(i, address)| Some((i, *index.get(&jump_target(memory, (*address)?)?)?)))
+        .collect();
+    jumps.sort_by_key(|(from, to)| from.abs_diff(*to));
+    let mut lanes: Vec<Vec<(usize, usize)>> = Vec::new();
+    for (from, to) in jumps {
+        let (first, last) = (from.min(to), from.max(to));
+        let free = |lane: &Vec<(usize, usize)>| lane.iter().all(|(f, t)| first > *f.max(t) || last < *f.min(t));
+        match lanes.iter().position(free) {
+            Some(lane) => lanes[lane].push((from, to)),
+            None if lanes.len() < MAX_LANES => lanes.push(vec![(from, to)]),
+            None => {}
+        }
+    }
+    let width = lanes.len();
+    if width == 0 {
+        return vec![String::new(); rows.len()];
+    }
+    let mut margins = vec![vec![' '; width + 2]; rows.len()];
+    for (lane, jumps) in lanes.iter().enumerate() {
+        let col = width - 1 - lane;
+        for (from, to) in jumps {
+            for margin in &mut margins[*from.min(to) + 1..*from.max(to)] {
+                margin[col] = '|';
+            }
+        }
+    }
+    for (lane, jumps) in lanes.iter().enumerate() {
+        let col = width - 1 - lane;
+        for (from, to) in jumps {
+            for row in [*from, *to] {
+                margins[row][col] = '+';
+                margins[row][col + 1..=width].iter_mut().for_each(|c| *c = '-');
+            }
+        }
+    }
+    for (_, to) in lanes.iter().flatten() {
+        margins[*to][width] = '>';
+    }
+    margins.into_iter().map(|m| m.into_iter().collect()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mos6510::disassembler::disassemble;
+
+    fn memory(bytes: &[u8]) -> Memory {
+        let mut memory = Memory::new();
+        memory.set_block(0x0600, bytes);
+        memory
+    }
+
+    #[test]
+    fn cycles_and_flags() {
+        // lda $1234,x / sta $d020 / bne $0600
+        let memory = memory(&[0xbd, 0x34, 0x12, 0x8d, 0x20, 0xd0, 0xd0, 0xf8]);
+        let annotations = Annotations::from_letters("cf");
+        let mut pc = 0x0600;
+        let lines: Vec<_> = (0..3).map(|_| disassemble(&memory, &mut pc, None)).collect();
+        assert_eq!(
+            annotations.lines(&memory, &lines),
+            &[
+                "0600 BD 34 12  LDA $1234,X   4*  NZ",
+                "0603 8D 20 D0  STA $D020     4",
+                "0606 D0 F8     BNE $0600     2*",
+            ]
+        );
+    }
+
+    #[test]
+    fn effective_addresses() {
+        // lda ($10),y / sta $0200,x / jmp ($0010)
+        let mut memory = memory(&[0xb1, 0x10, 0x9d, 0x00, 0x02, 0x6c, 0x10, 0x00]);
+        memory.set_word(0x10, 0x1234);
+        let mut regs = Registers {
+            pc: 0x0600,
+            x: 2,
+            y: 1,
+            ..Registers::default()
+        };
+        assert_eq!(effective_address(&memory, &regs), Some(0x1235));
+        regs.pc = 0x0602;
+        assert_eq!(effective_address(&memory, &regs), Some(0x0202));
+        regs.pc = 0x0605;
+        assert_eq!(effective_address(&memory, &regs), Some(0x1234));
+        let annotations = Annotations::from_letters("e");
+        assert_eq!(annotations.columns(&memory, 0x0605, Some(&regs)), "@1234 ");
+    }
+
+    #[test]
+    fn jump_arrows() {
+        // ldx #8 / loop: dex / beq done / jmp loop / done: rts
+        let memory = memory(&[0xa2, 0x08, 0xca, 0xf0, 0x03, 0x4c, 0x02, 0x06, 0x60]);
+        let rows = [Some(0x0600), Some(0x0602), Some(0x0603), Some(0x0605), None, Some(0x0608)];
+        assert_eq!(arrows(&memory, &rows), &["    ", " +> ", "+-- ", "|+- ", "|   ", "+-> "]);
+        assert_eq!(arrows(&memory, &rows[..1]), &[""]);
+    }
+}
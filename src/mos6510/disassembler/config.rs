@@ -0,0 +1,159 @@
+//! Disassembly configuration file, e.g.
+//!
+//! ```toml
+//! entries = [0x0600]
+//!
+//! [[regions]]
+//! start = 0x0700
+//! end = 0x070f
+//! kind = "rts_table"
+//!
+//! [labels]
+//! main = 0x0600
+//!
+//! [[comments]]
+//! address = 0x0600
+//! text = "set up the screen"
+//! ```
+//!
+//! Region kinds are `code`, `bytes`, `words`, `pointers`, `text` and `rts_table`.
+
+use super::{Hints, Region, SymbolMap};
+use crate::{
+    error::AppError,
+    mos6510::symbols::{Symbol, SymbolKind},
+};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default)]
+    entries: Vec<u16>,
+    #[serde(default)]
+    regions: Vec<RegionEntry>,
+    #[serde(default)]
+    labels: BTreeMap<String, u16>,
+    #[serde(default)]
+    comments: Vec<CommentEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegionEntry {
+    start: u16,
+    end: u16,
+    kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CommentEntry {
+    address: u16,
+    text: String,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    pub hints: Hints,
+    pub labels: Vec<Symbol>,
+    pub comments: Vec<(u16, String)>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, AppError> {
+        let file: File = toml::from_str(text).map_err(|e| AppError::InvalidConfig(e.to_string()))?;
+        let mut regions = Vec::new();
+        for entry in file.regions {
+            if entry.end < entry.start {
+                return Err(AppError::InvalidConfig(format!(
+                    "region {:04X}-{:04X} ends before start",
+                    entry.start, entry.end
+                )));
+            }
+            regions.push(Region {
+                first: entry.start,
+                last: entry.end,
+                kind: entry.kind.parse()?,
+            });
+        }
+        let labels = file
+            .labels
+            .into_iter()
+            .map(|(name, value)| Symbol {
+                name,
+                value: value as i32,
+                kind: SymbolKind::Label,
+                line: None,
+                file: None,
+            })
+            .collect();
+        Ok(Self {
+            hints: Hints {
+                entries: file.entries,
+                data: Vec::new(),
+                regions,
+            },
+            labels,
+            comments: file.comments.into_iter().map(|c| (c.address, c.text)).collect(),
+        })
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, AppError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Adds the labels and comments to the symbols, labels of the configuration win.
+    pub fn apply(&self, symbols: &mut SymbolMap) {
+        symbols.add_labels(&self.labels);
+        for (address, text) in &self.comments {
+            symbols.add_comment(*address, text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mos6510::disassembler::RegionKind;
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(
+            "entries = [0x0600]\n\
+             [[regions]]\nstart = 0x0700\nend = 0x070f\nkind = \"rts_table\"\n\
+             [[regions]]\nstart = 0x0710\nend = 0x071f\nkind = \"text\"\n\
+             [labels]\nmain = 0x0600\n\
+             [[comments]]\naddress = 0x0600\ntext = \"entry\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.hints.entries, &[0x0600]);
+        assert_eq!(
+            config.hints.regions,
+            &[
+                Region {
+                    first: 0x0700,
+                    last: 0x070f,
+                    kind: RegionKind::RtsTable
+                },
+                Region {
+                    first: 0x0710,
+                    last: 0x071f,
+                    kind: RegionKind::Text
+                },
+            ]
+        );
+        let mut symbols = SymbolMap::default();
+        config.apply(&mut symbols);
+        assert_eq!(symbols.label(0x0600), Some("main"));
+        assert_eq!(symbols.comment(0x0600), Some("entry"));
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(Config::parse("[[regions]]\nstart = 0x0700\nend = 0x070f\nkind = \"table\"\n").is_err());
+        assert!(Config::parse("[[regions]]\nstart = 0x0700\nend = 0x06ff\nkind = \"bytes\"\n").is_err());
+        assert!(Config::parse("entry = [0x0600]\n").is_err());
+    }
+}
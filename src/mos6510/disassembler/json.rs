@@ -0,0 +1,113 @@
+//! Disassembly as JSON objects, one per instruction, for analysis by other tools.
+
+use super::{ByteKind, SymbolMap, Trace};
+use crate::mos6510::{addrmode::AddrMode, instruction::Instruction, memory::Memory, operation::Operation};
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Decoded {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    pub mode: String,
+    /// Operand as encoded: byte or word, `None` for implied instructions.
+    pub operand: Option<u16>,
+    /// Destination of branches, `JMP` and `JSR` to an absolute address.
+    pub target: Option<u16>,
+    pub cycles: u8,
+    /// One more cycle is taken on page crossing (or a taken branch).
+    pub page_penalty: bool,
+    pub length: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+pub fn decode(memory: &Memory, pc: u16, symbols: Option<&SymbolMap>) -> Decoded {
+    let operation = Operation::get(memory[pc]);
+    let length = operation.len();
+    let (byte, word) = (memory[pc.wrapping_add(1)], memory.word(pc.wrapping_add(1)));
+    let relative = |offset: u8, len: u16| pc.wrapping_add(len).wrapping_add(offset as i8 as u16);
+    let operand = match operation.addrmode.len() {
+        0 => None,
+        1 => Some(byte as u16),
+        _ if operation.addrmode == AddrMode::ZeroPageRelative => Some(byte as u16),
+        _ => Some(word),
+    };
+    let target = match (operation.instruction, operation.addrmode) {
+        (_, AddrMode::Relative) => Some(relative(byte, 2)),
+        (_, AddrMode::ZeroPageRelative) => Some(relative(memory[pc.wrapping_add(2)], 3)),
+        (Instruction::Jmp, AddrMode::Absolute) | (Instruction::Jsr, AddrMode::Absolute) => Some(word),
+        _ => None,
+    };
+    Decoded {
+        address: pc,
+        bytes: (0..length as u16).map(|i| memory[pc.wrapping_add(i)]).collect(),
+        mnemonic: operation.instruction.mnemonic(),
+        mode: format!("{:?}", operation.addrmode),
+        operand,
+        target,
+        cycles: operation.cycles,
+        page_penalty: operation.page_penalty(),
+        length,
+        label: symbols.and_then(|s| s.label(pc)).map(String::from),
+    }
+}
+
+fn json_line(memory: &Memory, address: u16, symbols: Option<&SymbolMap>) -> String {
+    serde_json::to_string(&decode(memory, address, symbols)).unwrap()
+}
+
+/// JSON line of each instruction from `start_addr` to `end_addr`, like `disassemble_range`.
+pub fn json_range(memory: &Memory, start_addr: u16, end_addr: u16, symbols: Option<&SymbolMap>) -> Vec<String> {
+    let mut lc = start_addr;
+    let mut lines = Vec::new();
+    while lc < end_addr {
+        lines.push(json_line(memory, lc, symbols));
+        lc = lc.saturating_add(Operation::get(memory[lc]).len() as u16);
+    }
+    lines
+}
+
+/// JSON line of each instruction of traced code, data is left out.
+pub fn json_traced(memory: &Memory, trace: &Trace, symbols: Option<&SymbolMap>) -> Vec<String> {
+    trace
+        .rows(memory)
+        .iter()
+        .filter(|row| row.kind == ByteKind::Code)
+        .map(|row| json_line(memory, row.address, symbols))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mos6510::symbols::{Symbol, SymbolKind};
+
+    #[test]
+    fn instruction_objects() {
+        // lda $1234,x / bne $0600 / jsr $0700
+        let mut memory = Memory::new();
+        memory.set_block(0x0600, &[0xbd, 0x34, 0x12, 0xd0, 0xfb, 0x20, 0x00, 0x07]);
+        memory[0x0700] = 0x60;
+        let mut symbols = SymbolMap::default();
+        symbols.add_labels(&[Symbol {
+            name: String::from("sub"),
+            value: 0x0700,
+            kind: SymbolKind::Label,
+            line: None,
+            file: None,
+        }]);
+        assert_eq!(
+            json_range(&memory, 0x0600, 0x0608, Some(&symbols))
+                .into_iter()
+                .chain(json_range(&memory, 0x0700, 0x0701, Some(&symbols)))
+                .collect::<Vec<_>>(),
+            &[
+                r#"{"address":1536,"bytes":[189,52,18],"mnemonic":"LDA","mode":"AbsoluteX","operand":4660,"target":null,"cycles":4,"page_penalty":true,"length":3}"#,
+                r#"{"address":1539,"bytes":[208,251],"mnemonic":"BNE","mode":"Relative","operand":251,"target":1536,"cycles":2,"page_penalty":true,"length":2}"#,
+                r#"{"address":1541,"bytes":[32,0,7],"mnemonic":"JSR","mode":"Absolute","operand":1792,"target":1792,"cycles":6,"page_penalty":false,"length":3}"#,
+                r#"{"address":1792,"bytes":[96],"mnemonic":"RTS","mode":"Implied","operand":null,"target":null,"cycles":6,"page_penalty":false,"length":1,"label":"sub"}"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Disassembly written as source, which assembles back to identical bytes.
+
+use super::{operand_address, quoted, ByteKind, Row, SymbolMap, Trace};
+use crate::mos6510::{addrmode::AddrMode, memory::Memory, operation::Operation};
+use std::{cell::RefCell, collections::BTreeMap, io::Write};
+
+/// Generated label of an address.
+pub fn label(address: u16) -> String {
+    format!("L{:04X}", address)
+}
+
+/// Names of addresses: labels of rows and symbols defined as constants when used.
+struct Names<'a> {
+    labels: BTreeMap<u16, String>,
+    symbols: Option<&'a SymbolMap>,
+    constants: RefCell<BTreeMap<u16, String>>,
+}
+
+impl Names<'_> {
+    fn name(&self, value: u16) -> Option<String> {
+        if let Some(label) = self.labels.get(&value) {
+            return Some(label.clone());
+        }
+        let name = self.symbols.and_then(|s| s.label(value)).filter(|name| is_name(name))?;
+        self.constants.borrow_mut().insert(value, String::from(name));
+        Some(String::from(name))
+    }
+
+    fn address(&self, value: u16) -> String {
+        self.name(value).unwrap_or_else(|| format!("${:04X}", value))
+    }
+
+    /// Branches take only symbols, targets without a label of a row are defined as constants.
+    fn target(&self, value: u16) -> String {
+        self.name(value).unwrap_or_else(|| {
+            self.constants.borrow_mut().insert(value, label(value));
+            label(value)
+        })
+    }
+}
+
+/// Symbol names usable in the source: not scoped, not local and not a register name.
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !matches!(name.to_lowercase().as_str(), "a" | "x" | "y")
+}
+
+/// Jump targets and addresses named by `symbols` get labels when they start a row, other addresses are numbers
+/// or symbols defined as constants. Hardware registers are named in comments.
+pub fn write_source<W: Write>(out: &mut W, memory: &Memory, trace: &Trace, symbols: Option<&SymbolMap>) -> std::io::Result<()> {
+    let rows = trace.rows(memory);
+    let symbol = |address: u16| symbols.and_then(|s| s.label(address)).filter(|name| is_name(name));
+    let labels = rows
+        .iter()
+        .filter_map(|row| match symbol(row.address) {
+            Some(name) => Some((row.address, String::from(name))),
+            None if trace.targets.contains(&row.address) => Some((row.address, label(row.address))),
+            None => None,
+        })
+        .collect();
+    let names = Names {
+        labels,
+        symbols,
+        constants: RefCell::new(BTreeMap::new()),
+    };
+    let statements: Vec<String> = rows.iter().map(|row| statement(memory, row, &names)).collect();
+    for (value, name) in names.constants.borrow().iter() {
+        writeln!(out, "{} = ${:04X}", name, value)?;
+    }
+    writeln!(out, "  .org ${:04X}", trace.start)?;
+    for (row, statement) in rows.iter().zip(statements) {
+        if let Some(label) = names.labels.get(&row.address) {
+            writeln!(out, "{}:", label)?;
+        }
+        writeln!(out, "  {}", statement)?;
+    }
+    Ok(())
+}
+
+fn statement(memory: &Memory, row: &Row, names: &Names) -> String {
+    match row.kind {
+        ByteKind::Code => {
+            let operation = Operation::get(memory[row.address]);
+            let mnemonic = operation.instruction.mnemonic().to_lowercase();
+            let statement = match operand(memory, row.address, operation, names) {
+                Some(operand) => format!("{} {}", mnemonic, operand),
+                None => mnemonic,
+            };
+            let operand = operand_address(memory, row.address, operation);
+            commented(statement, names.symbols.and_then(|s| s.notes(row.address, operand)))
+        }
+        ByteKind::Word => {
+            let pointer = memory.word(row.address);
+            let statement = match names.labels.get(&pointer.wrapping_add(1)) {
+                Some(label) if !names.labels.contains_key(&pointer) => format!(".word {}-1", label),
+                _ => format!(".word {}", names.address(pointer)),
+            };
+            commented(statement, names.symbols.and_then(|s| s.notes(row.address, None)))
+        }
+        ByteKind::Data | ByteKind::Operand => {
+            let bytes: Vec<String> = memory.view(row.address, row.len).iter().map(|b| format!("${:02X}", b)).collect();
+            commented(
+                format!(".byte {}", bytes.join(", ")),
+                names.symbols.and_then(|s| s.notes(row.address, None)),
+            )
+        }
+        ByteKind::Text => {
+            let statement = format!(".text {}", quoted(memory.view(row.address, row.len)));
+            commented(statement, names.symbols.and_then(|s| s.notes(row.address, None)))
+        }
+    }
+}
+
+fn commented(statement: String, comment: Option<String>) -> String {
+    match comment {
+        Some(comment) => format!("{} ; {}", statement, comment),
+        None => statement,
+    }
+}
+
+/// Absolute addresses below $100 get `!` prefix, otherwise they would be assembled as zero page.
+fn operand(memory: &Memory, pc: u16, operation: &Operation, names: &Names) -> Option<String> {
+    let byte = memory[pc.wrapping_add(1)];
+    let word = memory.word(pc.wrapping_add(1));
+    let zero_page = || names.name(byte as u16).unwrap_or_else(|| format!("${:02X}", byte));
+    let absolute = |value: u16| format!("{}{}", if value < 0x100 { "!" } else { "" }, names.address(value));
+    let relative = |offset: u8, len: u16| names.target(pc.wrapping_add(len).wrapping_add(offset as i8 as u16));
+    Some(match operation.addrmode {
+        AddrMode::Implied => return None,
+        AddrMode::Relative => relative(byte, 2),
+        AddrMode::Immediate => format!("#${:02X}", byte),
+        AddrMode::ZeroPage => zero_page(),
+        AddrMode::ZeroPageX => format!("{},x", zero_page()),
+        AddrMode::ZeroPageY => format!("{},y", zero_page()),
+        AddrMode::IndexedIndirectX => format!("({},x)", zero_page()),
+        AddrMode::IndirectIndexedY => format!("({}),y", zero_page()),
+        AddrMode::ZeroPageIndirect => format!("({})", zero_page()),
+        AddrMode::Indirect => format!("({})", absolute(word)),
+        AddrMode::Absolute => absolute(word),
+        AddrMode::AbsoluteX => format!("{},x", absolute(word)),
+        AddrMode::AbsoluteY => format!("{},y", absolute(word)),
+        AddrMode::AbsoluteIndexedIndirect => format!("({},x)", absolute(word)),
+        AddrMode::ZeroPageRelative => format!("{}, {}", zero_page(), relative(memory[pc.wrapping_add(2)], 3)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mos6510::{
+        assembler::{self, flatten},
+        disassembler::{linear, trace, Hints},
+        operation::CpuVariant,
+        symbols::{Symbol, SymbolKind},
+    };
+    use std::{fs, path::Path};
+
+    fn source(memory: &Memory, trace: &Trace, symbols: Option<&SymbolMap>) -> String {
+        let mut text = Vec::new();
+        write_source(&mut text, memory, trace, symbols).unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn labels_and_data() {
+        let mut memory = Memory::new();
+        // lda $0012 / ldx $12,y / beq +1 / rts / + jmp ($0600) / .byte 1, 2
+        memory.set_block(0x0600, &[0xad, 0x12, 0x00, 0xb6, 0x12, 0xf0, 0x01, 0x60, 0x6c, 0x00, 0x06, 1, 2]);
+        let text = source(&memory, &trace(&memory, 0x0600, 13, &Hints::default()), None);
+        assert_eq!(
+            text,
+            "  .org $0600\nL0600:\n  lda !$0012\n  ldx $12,y\n  beq L0608\n  rts\nL0608:\n  jmp (L0600)\n  .byte $01, $02\n"
+        );
+    }
+
+    #[test]
+    fn symbol_names() {
+        let mut memory = Memory::new();
+        // lda $d020 / sta $fb / jsr sub / rts / sub: rts
+        memory.set_block(0x0600, &[0xad, 0x20, 0xd0, 0x85, 0xfb, 0x20, 0x09, 0x06, 0x60, 0x60]);
+        let symbol = |name: &str, value: i32| Symbol {
+            name: String::from(name),
+            value,
+            kind: SymbolKind::Label,
+            line: None,
+            file: None,
+        };
+        let mut symbols = SymbolMap::default();
+        symbols.add_labels(&[
+            symbol("start", 0x0600),
+            symbol("ptr", 0xfb),
+            symbol("sub", 0x0609),
+            symbol("SCREEN", 0x0200),
+        ]);
+        symbols.add_registers(&[symbol("BORDER", 0xd020)]);
+        let text = source(&memory, &linear(&memory, 0x0600, 10), Some(&symbols));
+        assert_eq!(
+            text,
+            "ptr = $00FB\n  .org $0600\nstart:\n  lda $D020 ; BORDER\n  sta ptr\n  jsr sub\n  rts\nsub:\n  rts\n"
+        );
+    }
+
+    #[test]
+    fn branch_out_of_range() {
+        let mut memory = Memory::new();
+        // bne $0612 / rts
+        memory.set_block(0x0600, &[0xd0, 0x10, 0x60]);
+        let text = source(&memory, &linear(&memory, 0x0600, 3), None);
+        assert_eq!(text, "L0612 = $0612\n  .org $0600\n  bne L0612\n  rts\n");
+    }
+
+    /// Every example, and a branch out of the disassembled range, assembles back from its disassembly
+    /// to the same image, whether code is traced or not.
+    #[test]
+    fn round_trip() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("asm");
+        let temp = std::env::temp_dir().join(format!("mo65x-round-trip-{}", std::process::id()));
+        fs::create_dir_all(&temp).unwrap();
+        let mut examples: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        examples.sort();
+        let mut images = vec![(String::from("branch"), 0x0600, vec![0xd0, 0x10, 0x60])];
+        for example in examples.iter().filter(|p| p.extension().is_some_and(|e| e == "asm")) {
+            let assembly = assembler::assemble_file(example, 20, false, false, CpuVariant::default(), false).unwrap();
+            let (origin, code) = flatten(&assembly.segments, 0).unwrap();
+            images.push((example.file_stem().unwrap().to_string_lossy().to_string(), origin, code));
+        }
+        for (name, origin, code) in images {
+            let mut memory = Memory::new();
+            memory.set_block(origin, &code);
+            let traces = [
+                linear(&memory, origin, code.len()),
+                trace(&memory, origin, code.len(), &Hints::default()),
+            ];
+            for (mode, trace) in traces.iter().enumerate() {
+                let path = temp.join(format!("{}.{}.asm", name, mode));
+                fs::write(&path, source(&memory, trace, None)).unwrap();
+                let assembly = assembler::assemble_file(&path, 20, false, false, CpuVariant::default(), false).unwrap();
+                assert_eq!(flatten(&assembly.segments, 0).unwrap(), (origin, code.clone()), "{:?}", path);
+            }
+        }
+        fs::remove_dir_all(&temp).unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+//! Names shown in the disassembly in place of addresses, and hardware register names shown in comments.
+
+use crate::{
+    error::AppError,
+    mos6510::symbols::{read_symbols, Symbol, SymbolKind},
+};
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+#[derive(Debug, Default, Clone)]
+pub struct SymbolMap {
+    labels: HashMap<u16, String>,
+    registers: HashMap<u16, String>,
+    comments: HashMap<u16, String>,
+}
+
+impl SymbolMap {
+    /// Labels win over constants sharing the same address.
+    pub fn add_labels(&mut self, symbols: &[Symbol]) {
+        for symbol in symbols {
+            let address = symbol.value as u16;
+            if symbol.kind != SymbolKind::Constant || !self.labels.contains_key(&address) {
+                self.labels.insert(address, symbol.name.clone());
+            }
+        }
+    }
+
+    /// Register names of a machine, e.g. `BORDER = $D020`.
+    pub fn add_registers(&mut self, symbols: &[Symbol]) {
+        for symbol in symbols {
+            self.registers.insert(symbol.value as u16, symbol.name.clone());
+        }
+    }
+
+    /// Reads a symbol file in any format accepted by `read_symbols`, returns the number of symbols.
+    pub fn load_labels<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, AppError> {
+        let symbols = read_symbols(BufReader::new(File::open(path)?))?;
+        self.add_labels(&symbols);
+        Ok(symbols.len())
+    }
+
+    pub fn load_registers<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, AppError> {
+        let symbols = read_symbols(BufReader::new(File::open(path)?))?;
+        self.add_registers(&symbols);
+        Ok(symbols.len())
+    }
+
+    pub fn add_comment(&mut self, address: u16, text: &str) {
+        self.comments.insert(address, String::from(text));
+    }
+
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.labels.iter().map(|(address, name)| (*address, name.as_str()))
+    }
+
+    pub fn register(&self, address: u16) -> Option<&str> {
+        self.registers.get(&address).map(String::as_str)
+    }
+
+    /// Comment of the row at the address.
+    pub fn comment(&self, address: u16) -> Option<&str> {
+        self.comments.get(&address).map(String::as_str)
+    }
+
+    /// Register accessed by the row at `address` and its comment, separated with a comma.
+    pub fn notes(&self, address: u16, operand: Option<u16>) -> Option<String> {
+        let notes: Vec<&str> = [operand.and_then(|a| self.register(a)), self.comment(address)]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        Some(notes.join(", ")).filter(|n| !n.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, value: i32, kind: SymbolKind) -> Symbol {
+        Symbol {
+            name: String::from(name),
+            value,
+            kind,
+            line: None,
+            file: None,
+        }
+    }
+
+    #[test]
+    fn labels_over_constants() {
+        let mut map = SymbolMap::default();
+        map.add_labels(&[
+            symbol("start", 0x0600, SymbolKind::Label),
+            symbol("ORIGIN", 0x0600, SymbolKind::Constant),
+            symbol("SCREEN", 0x0200, SymbolKind::Constant),
+        ]);
+        map.add_registers(&[symbol("BORDER", 0xd020, SymbolKind::Label)]);
+        assert_eq!(map.label(0x0600), Some("start"));
+        assert_eq!(map.label(0x0200), Some("SCREEN"));
+        assert_eq!(map.label(0xd020), None);
+        assert_eq!(map.register(0xd020), Some("BORDER"));
+        map.add_comment(0x0600, "entry");
+        assert_eq!(map.notes(0x0600, Some(0xd020)).as_deref(), Some("BORDER, entry"));
+        assert_eq!(map.notes(0x0601, None), None);
+    }
+}
@@ -0,0 +1,461 @@
+//! Separates code from data by following execution paths from entry points, like the CPU would.
+//! Bytes never reached are data, runs of words pointing at traced instructions and accessed by them are pointer tables.
+
+use crate::{
+    error::AppError,
+    mos6510::{addrmode::AddrMode, instruction::Instruction, memory::Memory, operation::Operation},
+};
+use std::{collections::BTreeSet, str::FromStr};
+
+/// NMI, RESET and IRQ vectors, traced when they are within the range.
+pub const VECTORS: [(u16, &str); 3] = [(0xfffa, "NMI"), (0xfffc, "RESET"), (0xfffe, "IRQ")];
+const MIN_TABLE_LEN: usize = 2;
+const DATA_ROW: usize = 8;
+const TEXT_ROW: usize = 32;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ByteKind {
+    Data,
+    /// First byte of an instruction.
+    Code,
+    /// Byte following the first one of an instruction or a word.
+    Operand,
+    /// Low byte of a pointer.
+    Word,
+    /// Character of a string.
+    Text,
+}
+
+/// How bytes of a region are disassembled.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum RegionKind {
+    /// Traced from the start, bytes not reached are decoded one instruction after another.
+    Code,
+    Bytes,
+    Words,
+    /// Words pointing at code, which is traced.
+    Pointers,
+    Text,
+    /// Words pointing at bytes before code, jumped to by pushing the pointer and executing `RTS`.
+    RtsTable,
+}
+
+impl FromStr for RegionKind {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "code" => Ok(RegionKind::Code),
+            "bytes" => Ok(RegionKind::Bytes),
+            "words" => Ok(RegionKind::Words),
+            "pointers" => Ok(RegionKind::Pointers),
+            "text" => Ok(RegionKind::Text),
+            "rts_table" => Ok(RegionKind::RtsTable),
+            _ => Err(AppError::InvalidConfig(format!("unknown region kind {}", s))),
+        }
+    }
+}
+
+/// Address range (inclusive) of known contents.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Region {
+    pub first: u16,
+    pub last: u16,
+    pub kind: RegionKind,
+}
+
+impl Region {
+    fn contains(&self, address: u16) -> bool {
+        (self.first..=self.last).contains(&address)
+    }
+
+    fn len(&self) -> usize {
+        (self.last - self.first) as usize + 1
+    }
+}
+
+/// Entry points of the code, address ranges (inclusive) known to hold data and regions of known contents.
+#[derive(Debug, Default, Clone)]
+pub struct Hints {
+    pub entries: Vec<u16>,
+    pub data: Vec<(u16, u16)>,
+    pub regions: Vec<Region>,
+}
+
+impl Hints {
+    /// Data is never traced as code.
+    fn is_data(&self, address: u16) -> bool {
+        self.data.iter().any(|(first, last)| (*first..=*last).contains(&address))
+            || self.regions.iter().any(|r| r.kind != RegionKind::Code && r.contains(address))
+    }
+}
+
+/// Line of disassembly: an instruction, a pointer, up to 8 bytes of data or up to 32 characters of text.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Row {
+    pub address: u16,
+    pub kind: ByteKind,
+    pub len: usize,
+}
+
+pub struct Trace {
+    pub start: u16,
+    pub kinds: Vec<ByteKind>,
+    /// Destinations of jumps, branches, calls and pointers within the range.
+    pub targets: BTreeSet<u16>,
+    /// Absolute operands of traced instructions.
+    accessed: BTreeSet<u16>,
+}
+
+impl Trace {
+    /// Data rows are broken at jump targets, so each of them starts a row.
+    pub fn rows(&self, memory: &Memory) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let mut index = 0;
+        while index < self.kinds.len() {
+            let address = self.start.wrapping_add(index as u16);
+            let (kind, len) = match self.kinds[index] {
+                ByteKind::Code => (ByteKind::Code, Operation::get(memory[address]).len() as usize),
+                ByteKind::Word => (ByteKind::Word, 2),
+                ByteKind::Text => {
+                    let max = TEXT_ROW.min(self.kinds.len() - index);
+                    let len = (1..max)
+                        .find(|i| self.kinds[index + i] != ByteKind::Text || self.targets.contains(&address.wrapping_add(*i as u16)))
+                        .unwrap_or(max);
+                    (ByteKind::Text, len)
+                }
+                ByteKind::Data | ByteKind::Operand => {
+                    let max = DATA_ROW.min(self.kinds.len() - index);
+                    let len = (1..max)
+                        .find(|i| self.kinds[index + i] != ByteKind::Data || self.targets.contains(&address.wrapping_add(*i as u16)))
+                        .unwrap_or(max);
+                    (ByteKind::Data, len)
+                }
+            };
+            rows.push(Row { address, kind, len });
+            index += len;
+        }
+        rows
+    }
+
+    pub fn kind(&self, address: u16) -> Option<ByteKind> {
+        self.index(address).map(|i| self.kinds[i])
+    }
+
+    fn index(&self, address: u16) -> Option<usize> {
+        Some(address.wrapping_sub(self.start) as usize).filter(|i| *i < self.kinds.len())
+    }
+
+    fn is_free(&self, address: u16, len: usize, hints: &Hints) -> bool {
+        (0..len as u16).all(|i| {
+            let address = address.wrapping_add(i);
+            self.kind(address) == Some(ByteKind::Data) && !hints.is_data(address)
+        })
+    }
+
+    fn mark(&mut self, address: u16, len: usize, kind: ByteKind) {
+        for i in 0..len as u16 {
+            if let Some(index) = self.index(address.wrapping_add(i)) {
+                self.kinds[index] = if i == 0 { kind } else { ByteKind::Operand };
+            }
+        }
+    }
+
+    fn jump(&mut self, target: u16, pending: &mut Vec<u16>) {
+        if self.index(target).is_some() {
+            self.targets.insert(target);
+            pending.push(target);
+        }
+    }
+
+    /// Marks instructions from `pc` up to the one which does not continue with the next.
+    fn follow(&mut self, memory: &Memory, mut pc: u16, hints: &Hints, pending: &mut Vec<u16>) {
+        loop {
+            let operation = Operation::get(memory[pc]);
+            let len = operation.len() as usize;
+            if operation.instruction == Instruction::Kil || !self.is_free(pc, len, hints) {
+                return;
+            }
+            self.mark(pc, len, ByteKind::Code);
+            let next = pc.wrapping_add(len as u16);
+            let word = memory.word(pc.wrapping_add(1));
+            match (operation.instruction, operation.addrmode) {
+                (_, AddrMode::Relative) => self.jump(next.wrapping_add(memory[pc.wrapping_add(1)] as i8 as u16), pending),
+                (Instruction::Jsr, _) => self.jump(word, pending),
+                (Instruction::Jmp, AddrMode::Absolute) => return self.jump(word, pending),
+                (Instruction::Jmp, AddrMode::Indirect) => {
+                    if self.is_free(word, 2, hints) {
+                        self.mark(word, 2, ByteKind::Word);
+                        self.jump(memory.word(word), pending);
+                    }
+                    return;
+                }
+                (Instruction::Rts | Instruction::Rti | Instruction::Brk, _) => return,
+                (_, AddrMode::Absolute | AddrMode::AbsoluteX | AddrMode::AbsoluteY) => {
+                    self.accessed.insert(word);
+                }
+                _ => {}
+            }
+            pc = next;
+        }
+    }
+
+    /// Marks regions of text and words, code pointed to by words of pointer and `RTS` tables is traced.
+    fn mark_regions(&mut self, memory: &Memory, hints: &Hints, pending: &mut Vec<u16>) {
+        for region in &hints.regions {
+            let addresses = (0..region.len() as u16).map(|i| region.first.wrapping_add(i));
+            match region.kind {
+                RegionKind::Code | RegionKind::Bytes => {}
+                RegionKind::Text => {
+                    for address in addresses {
+                        if let Some(index) = self.index(address) {
+                            self.kinds[index] = ByteKind::Text;
+                        }
+                    }
+                }
+                RegionKind::Words | RegionKind::Pointers | RegionKind::RtsTable => {
+                    for entry in addresses.step_by(2).take(region.len() / 2) {
+                        self.mark(entry, 2, ByteKind::Word);
+                        match region.kind {
+                            RegionKind::Pointers => self.jump(memory.word(entry), pending),
+                            RegionKind::RtsTable => self.jump(memory.word(entry).wrapping_add(1), pending),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Bytes of code regions not reached by tracing are decoded one instruction after another.
+    fn fill_code(&mut self, memory: &Memory, hints: &Hints) {
+        for region in hints.regions.iter().filter(|r| r.kind == RegionKind::Code) {
+            let mut offset = 0;
+            while offset < region.len() {
+                let address = region.first.wrapping_add(offset as u16);
+                let len = Operation::get(memory[address]).len() as usize;
+                let valid = Operation::get(memory[address]).instruction != Instruction::Kil && offset + len <= region.len();
+                if self.kind(address) == Some(ByteKind::Code) || (valid && self.is_free(address, len, hints)) {
+                    self.mark(address, len, ByteKind::Code);
+                    offset += len;
+                } else {
+                    offset += 1;
+                }
+            }
+        }
+    }
+
+    /// Table of pointers to instructions, or to bytes before them as pushed for `RTS`, read by the traced code
+    /// (e.g. `lda table,x` and `lda table+1,x`).
+    fn table_len(&self, memory: &Memory, address: u16, hints: &Hints) -> usize {
+        if !self.accessed.contains(&address) && !self.accessed.contains(&address.wrapping_add(1)) {
+            return 0;
+        }
+        [0, 1]
+            .iter()
+            .map(|offset: &u16| {
+                (0..)
+                    .map(|i| address.wrapping_add(i * 2))
+                    .take_while(|entry| {
+                        let target = memory.word(*entry).wrapping_add(*offset);
+                        self.is_free(*entry, 2, hints) && self.kind(target) == Some(ByteKind::Code)
+                    })
+                    .count()
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn find_tables(&mut self, memory: &Memory, hints: &Hints) {
+        let mut index = 0;
+        while index < self.kinds.len() {
+            let address = self.start.wrapping_add(index as u16);
+            let len = self.table_len(memory, address, hints);
+            if len >= MIN_TABLE_LEN {
+                for entry in (0..len as u16).map(|i| address.wrapping_add(i * 2)) {
+                    self.mark(entry, 2, ByteKind::Word);
+                    let pointer = memory.word(entry);
+                    let target = if self.kind(pointer) == Some(ByteKind::Code) {
+                        pointer
+                    } else {
+                        pointer.wrapping_add(1)
+                    };
+                    self.targets.insert(target);
+                }
+                index += len * 2;
+            } else {
+                index += 1;
+            }
+        }
+    }
+}
+
+/// Every byte is decoded as an instruction, unless it is not a valid opcode or the instruction does not fit in the range.
+pub fn linear(memory: &Memory, start: u16, len: usize) -> Trace {
+    let mut trace = Trace {
+        start,
+        kinds: vec![ByteKind::Data; len],
+        targets: BTreeSet::new(),
+        accessed: BTreeSet::new(),
+    };
+    let mut index = 0;
+    while index < len {
+        let pc = start.wrapping_add(index as u16);
+        let operation = Operation::get(memory[pc]);
+        let size = operation.len() as usize;
+        if operation.instruction == Instruction::Kil || index + size > len {
+            index += 1;
+            continue;
+        }
+        trace.mark(pc, size, ByteKind::Code);
+        let next = pc.wrapping_add(size as u16);
+        match (operation.instruction, operation.addrmode) {
+            (_, AddrMode::Relative) => trace.jump(next.wrapping_add(memory[pc.wrapping_add(1)] as i8 as u16), &mut Vec::new()),
+            (Instruction::Jsr, _) | (Instruction::Jmp, AddrMode::Absolute) => trace.jump(memory.word(pc.wrapping_add(1)), &mut Vec::new()),
+            _ => {}
+        }
+        index += size;
+    }
+    trace
+}
+
+/// Traces `len` bytes from `start`, from the start address when no entry points are given and there are no vectors.
+pub fn trace(memory: &Memory, start: u16, len: usize, hints: &Hints) -> Trace {
+    let mut trace = Trace {
+        start,
+        kinds: vec![ByteKind::Data; len],
+        targets: BTreeSet::new(),
+        accessed: BTreeSet::new(),
+    };
+    let mut pending = Vec::new();
+    for entry in hints.entries.iter().rev() {
+        trace.jump(*entry, &mut pending);
+    }
+    for (vector, _) in VECTORS {
+        if trace.is_free(vector, 2, hints) {
+            trace.mark(vector, 2, ByteKind::Word);
+            trace.jump(memory.word(vector), &mut pending);
+        }
+    }
+    if pending.is_empty() {
+        trace.jump(start, &mut pending);
+    }
+    for region in hints.regions.iter().filter(|r| r.kind == RegionKind::Code) {
+        trace.jump(region.first, &mut pending);
+    }
+    trace.mark_regions(memory, hints, &mut pending);
+    while let Some(pc) = pending.pop() {
+        trace.follow(memory, pc, hints, &mut pending);
+    }
+    trace.fill_code(memory, hints);
+    trace.find_tables(memory, hints);
+    trace
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ByteKind::*;
+
+    fn memory(start: u16, bytes: &[u8]) -> Memory {
+        let mut memory = Memory::new();
+        memory.set_block(start, bytes);
+        memory
+    }
+
+    #[test]
+    fn code_and_data() {
+        // jsr sub / jmp skip / .byte 1 / skip: jmp (ptr) / .byte $ff, $ff / sub: rts / ptr: .word $0600 / .byte $ff
+        #[rustfmt::skip]
+        let bytes = [
+            0x20, 0x0c, 0x06, 0x4c, 0x07, 0x06, 0x01, 0x6c, 0x0d, 0x06, 0xff, 0xff, 0x60, 0x00, 0x06, 0xff,
+        ];
+        let memory = memory(0x0600, &bytes);
+        let trace = trace(&memory, 0x0600, bytes.len(), &Hints::default());
+        #[rustfmt::skip]
+        assert_eq!(trace.kinds, &[
+            Code, Operand, Operand, Code, Operand, Operand, Data, Code, Operand, Operand, Data, Data, Code, Word, Operand, Data,
+        ]);
+        assert_eq!(trace.targets.iter().copied().collect::<Vec<u16>>(), &[0x0600, 0x0607, 0x060c]);
+    }
+
+    #[test]
+    fn vectors_and_hints() {
+        let memory = memory(
+            0xfff0,
+            &[0xea, 0x40, 0x00, 0x60, 0, 0, 0, 0, 0, 0, 0xf0, 0xff, 0xf0, 0xff, 0xf1, 0xff],
+        );
+        let trace = trace(&memory, 0xfff0, 16, &Hints::default());
+        assert_eq!(&trace.kinds[..4], &[Code, Code, Data, Data]);
+        assert_eq!(&trace.kinds[10..], &[Word, Operand, Word, Operand, Word, Operand]);
+        let hints = Hints {
+            entries: vec![0xfff3],
+            data: vec![(0xfff0, 0xfff1)],
+            ..Hints::default()
+        };
+        let trace = super::trace(&memory, 0xfff0, 10, &hints);
+        assert_eq!(&trace.kinds[..4], &[Data, Data, Data, Code]);
+    }
+
+    #[test]
+    fn pointer_tables() {
+        // jsr a / lda table,x / jsr b / rts / .byte 0 / a: rts / b: rts / table: .word a, b / .byte $34, $12
+        #[rustfmt::skip]
+        let bytes = [
+            0x20, 0x0b, 0x10, 0xbd, 0x0d, 0x10, 0x20, 0x0c, 0x10, 0x60, 0x00, 0x60, 0x60, 0x0b, 0x10, 0x0c, 0x10, 0x34, 0x12,
+        ];
+        let mut memory = memory(0x1000, &bytes);
+        let trace = trace(&memory, 0x1000, bytes.len(), &Hints::default());
+        assert_eq!(
+            &trace.kinds[9..],
+            &[Code, Data, Code, Code, Word, Operand, Word, Operand, Data, Data]
+        );
+        // pointers pushed for RTS point at the byte before the instruction
+        memory.set_block(0x100d, &[0x0a, 0x10, 0x0b, 0x10]);
+        let trace = super::trace(&memory, 0x1000, bytes.len(), &Hints::default());
+        assert_eq!(&trace.kinds[13..17], &[Word, Operand, Word, Operand]);
+        // not read by the code
+        memory[0x1004] = 0x0e;
+        memory[0x1005] = 0x11;
+        let trace = super::trace(&memory, 0x1000, bytes.len(), &Hints::default());
+        assert_eq!(&trace.kinds[13..17], &[Data, Data, Data, Data]);
+    }
+    #[test]
+    fn regions() {
+        // jsr $1010 / rts / .text "ab" / .word $100f / .word $1011 / .byte 0 / nop / rts / nop / .byte 0 /
+        // $100f: rts / $1010: rts / .byte 0 / $1012: rts
+        #[rustfmt::skip]
+        let bytes = [
+            0x20, 0x10, 0x10, 0x60, b'a', b'b', 0x0f, 0x10, 0x11, 0x10, 0x00, 0xea, 0x60, 0xea, 0x00, 0x60, 0x60, 0x00, 0x60,
+        ];
+        let memory = memory(0x1000, &bytes);
+        let region = |first, last, kind| Region { first, last, kind };
+        let hints = Hints {
+            regions: vec![
+                region(0x1004, 0x1005, RegionKind::Text),
+                region(0x1006, 0x1007, RegionKind::Pointers),
+                region(0x1008, 0x1009, RegionKind::RtsTable),
+                region(0x100b, 0x100d, RegionKind::Code),
+            ],
+            ..Hints::default()
+        };
+        let trace = trace(&memory, 0x1000, bytes.len(), &hints);
+        #[rustfmt::skip]
+        assert_eq!(trace.kinds, &[
+            Code, Operand, Operand, Code, Text, Text, Word, Operand, Word, Operand, Data, Code, Code, Code, Data, Code, Code, Data, Code,
+        ]);
+        let rows = trace.rows(&memory);
+        assert_eq!(
+            rows[2],
+            Row {
+                address: 0x1004,
+                kind: Text,
+                len: 2
+            }
+        );
+        assert_eq!(
+            trace.targets.iter().copied().collect::<Vec<u16>>(),
+            &[0x1000, 0x100b, 0x100f, 0x1010, 0x1012]
+        );
+    }
+}
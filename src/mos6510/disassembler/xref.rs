@@ -0,0 +1,167 @@
+//! Cross references of traced code: instructions reading, writing, jumping to and calling each address,
+//! and the graph of subroutine calls.
+
+use super::{label, operand_address, ByteKind, SymbolMap, Trace};
+use crate::mos6510::{addrmode::AddrMode, instruction::Instruction, memory::Memory, operation::Operation};
+use std::collections::{BTreeMap, BTreeSet};
+use Instruction::*;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub enum Access {
+    Read,
+    Write,
+    Jump,
+    Call,
+}
+
+impl Access {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Jump => "jump",
+            Access::Call => "call",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Xrefs {
+    /// Addresses of instructions referring to each address.
+    pub refs: BTreeMap<u16, BTreeSet<(Access, u16)>>,
+    /// Caller and callee of each `JSR`, the caller is the nearest entry point or called address before it.
+    pub calls: BTreeSet<(u16, u16)>,
+}
+
+fn writes(instruction: Instruction) -> bool {
+    matches!(instruction, Sta | Stx | Sty)
+}
+
+/// Read-modify-write instructions.
+fn modifies(instruction: Instruction) -> bool {
+    matches!(instruction, Asl | Lsr | Rol | Ror | Inc | Dec)
+}
+
+/// Accesses of the instruction at `pc`, an indexed or indirect operand refers to its base address.
+fn accesses(memory: &Memory, pc: u16) -> Vec<(Access, u16)> {
+    let operation = Operation::get(memory[pc]);
+    let relative = |offset: u8, len: u16| pc.wrapping_add(len).wrapping_add(offset as i8 as u16);
+    let operand = operand_address(memory, pc, operation);
+    match (operation.instruction, operation.addrmode, operand) {
+        (_, AddrMode::Relative, _) => vec![(Access::Jump, relative(memory[pc.wrapping_add(1)], 2))],
+        (Jsr, AddrMode::Absolute, Some(address)) => vec![(Access::Call, address)],
+        (Jmp, AddrMode::Absolute, Some(address)) => vec![(Access::Jump, address)],
+        (instruction, _, Some(address)) if modifies(instruction) => vec![(Access::Read, address), (Access::Write, address)],
+        (instruction, _, Some(address)) if writes(instruction) => vec![(Access::Write, address)],
+        (_, _, Some(address)) => vec![(Access::Read, address)],
+        _ => Vec::new(),
+    }
+}
+
+/// References of the instructions of traced code, `entries` start the callers of the call graph besides called addresses.
+pub fn xrefs(memory: &Memory, trace: &Trace, entries: &[u16]) -> Xrefs {
+    let mut xrefs = Xrefs::default();
+    let code: Vec<u16> = trace
+        .rows(memory)
+        .iter()
+        .filter(|row| row.kind == ByteKind::Code)
+        .map(|row| row.address)
+        .collect();
+    for pc in &code {
+        for (access, address) in accesses(memory, *pc) {
+            xrefs.refs.entry(address).or_default().insert((access, *pc));
+        }
+    }
+    let mut starts: BTreeSet<u16> = entries.iter().copied().collect();
+    starts.insert(trace.start);
+    starts.extend(
+        code.iter()
+            .flat_map(|pc| accesses(memory, *pc))
+            .filter(|(a, _)| *a == Access::Call)
+            .map(|(_, address)| address),
+    );
+    for pc in code {
+        for (_, callee) in accesses(memory, pc).into_iter().filter(|(a, _)| *a == Access::Call) {
+            let caller = starts.range(..=pc).next_back().copied().unwrap_or(trace.start);
+            xrefs.calls.insert((caller, callee));
+        }
+    }
+    xrefs
+}
+
+fn name(address: u16, symbols: Option<&SymbolMap>) -> String {
+    symbols.and_then(|s| s.label(address)).map_or_else(|| label(address), String::from)
+}
+
+impl Xrefs {
+    /// Each referred address with its name or register, followed by instructions referring to it by kind of access.
+    pub fn report(&self, symbols: Option<&SymbolMap>) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (address, refs) in &self.refs {
+            let name = symbols.and_then(|s| s.label(*address).or_else(|| s.register(*address)));
+            lines.push(format!("{:04X} {}", address, name.unwrap_or_default()).trim_end().to_string());
+            for access in [Access::Read, Access::Write, Access::Jump, Access::Call] {
+                let from: Vec<String> = refs
+                    .iter()
+                    .filter(|(a, _)| *a == access)
+                    .map(|(_, pc)| format!("{:04X}", pc))
+                    .collect();
+                if !from.is_empty() {
+                    lines.push(format!("  {:6}{}", access.name(), from.join(" ")));
+                }
+            }
+        }
+        lines
+    }
+
+    /// Graphviz DOT graph of subroutine calls.
+    pub fn call_graph(&self, symbols: Option<&SymbolMap>) -> String {
+        let mut dot = String::from("digraph calls {\n");
+        for (caller, callee) in &self.calls {
+            dot.push_str(&format!("  \"{}\" -> \"{}\";\n", name(*caller, symbols), name(*callee, symbols)));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mos6510::disassembler::{trace, Hints};
+
+    #[test]
+    fn references_and_calls() {
+        // jsr sub / inc $d020 / jmp $0600 / sub: lda $10 / sta $0200,x / jsr leaf / rts / leaf: beq leaf / rts
+        let mut memory = Memory::new();
+        let bytes = [
+            0x20, 0x09, 0x06, 0xee, 0x20, 0xd0, 0x4c, 0x00, 0x06, 0xa5, 0x10, 0x9d, 0x00, 0x02, 0x20, 0x12, 0x06, 0x60, 0xf0, 0xfe, 0x60,
+        ];
+        memory.set_block(0x0600, &bytes);
+        let trace = trace(&memory, 0x0600, bytes.len(), &Hints::default());
+        let xrefs = xrefs(&memory, &trace, &[]);
+        assert_eq!(
+            xrefs.report(None),
+            &[
+                "0010",
+                "  read  0609",
+                "0200",
+                "  write 060B",
+                "0600",
+                "  jump  0606",
+                "0609",
+                "  call  0600",
+                "0612",
+                "  jump  0612",
+                "  call  060E",
+                "D020",
+                "  read  0603",
+                "  write 0603",
+            ]
+        );
+        assert_eq!(
+            xrefs.call_graph(None),
+            "digraph calls {\n  \"L0600\" -> \"L0609\";\n  \"L0609\" -> \"L0612\";\n}\n"
+        );
+    }
+}
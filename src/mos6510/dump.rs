@@ -0,0 +1,82 @@
+//! Hex dump of memory with the bytes shown as characters.
+
+use super::memory::Memory;
+
+const ROW_SIZE: usize = 16;
+
+/// Printable ASCII, anything else is shown as a dot.
+pub fn ascii_char(byte: u8) -> char {
+    match byte {
+        0x20..=0x7e => byte as char,
+        _ => '.',
+    }
+}
+
+/// PETSCII of the lower/upper case character set, graphic and control characters are shown as a dot.
+pub fn petscii_char(byte: u8) -> char {
+    match byte {
+        0x41..=0x5a => (byte + 0x20) as char,
+        0x61..=0x7a => (byte - 0x20) as char,
+        0xc1..=0xda => (byte - 0x80) as char,
+        0x20..=0x40 | 0x5b | 0x5d => byte as char,
+        _ => '.',
+    }
+}
+
+/// Rows of 16 bytes from `start` to `end` (exclusive) followed by their characters.
+pub fn hex_dump(memory: &Memory, start: u16, end: usize, petscii: bool) -> Vec<String> {
+    let decode = match petscii {
+        true => petscii_char,
+        false => ascii_char,
+    };
+    let mut lines = Vec::new();
+    let mut address = start as usize;
+    while address < end.min(0x10000) {
+        let len = ROW_SIZE.min(end.min(0x10000) - address);
+        let bytes = memory.view(address as u16, len);
+        let hex: Vec<String> = (0..ROW_SIZE)
+            .map(|i| match bytes.get(i) {
+                Some(byte) => format!("{:02X}", byte),
+                None => String::from("  "),
+            })
+            .collect();
+        let chars: String = bytes.iter().map(|b| decode(*b)).collect();
+        lines.push(format!(
+            "{:04X}  {}  {}  |{}|",
+            address,
+            hex[..ROW_SIZE / 2].join(" "),
+            hex[ROW_SIZE / 2..].join(" "),
+            chars
+        ));
+        address += len;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_and_petscii_rows() {
+        let mut memory = Memory::new();
+        memory.set_block(0x0600, b"Hello, World!\x00\x01\x02\x48\x49");
+        assert_eq!(
+            hex_dump(&memory, 0x0600, 0x0612, false),
+            &[
+                "0600  48 65 6C 6C 6F 2C 20 57  6F 72 6C 64 21 00 01 02  |Hello, World!...|",
+                "0610  48 49                                             |HI|",
+            ]
+        );
+        assert_eq!(
+            hex_dump(&memory, 0x0610, 0x0612, true),
+            &["0610  48 49                                             |hi|"]
+        );
+        assert_eq!(
+            hex_dump(&memory, 0xfffe, 0x10000, false),
+            &["FFFE  00 00                                             |..|"]
+        );
+        assert_eq!(petscii_char(0xc8), 'H');
+        assert_eq!(petscii_char(0x93), '.');
+    }
+}
@@ -0,0 +1,359 @@
+use super::assembler::{flatten, Segment};
+use crate::error::AppError;
+use std::{convert::TryFrom, io::Write, path::Path, str::FromStr};
+
+const RECORD_SIZE: usize = 16;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ImageFormat {
+    /// Plain bytes of the memory image.
+    Raw,
+    /// Commodore program file, memory image preceded by the load address.
+    Prg,
+    IntelHex,
+    /// Motorola S-record with 16-bit addresses.
+    SRecord,
+}
+
+impl FromStr for ImageFormat {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "raw" | "bin" => Ok(ImageFormat::Raw),
+            "prg" => Ok(ImageFormat::Prg),
+            "ihex" | "hex" => Ok(ImageFormat::IntelHex),
+            "srec" => Ok(ImageFormat::SRecord),
+            _ => Err(AppError::InvalidFormat(String::from(s))),
+        }
+    }
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Raw => "bin",
+            ImageFormat::Prg => "prg",
+            ImageFormat::IntelHex => "hex",
+            ImageFormat::SRecord => "srec",
+        }
+    }
+}
+
+/// Memory blocks of a loaded file with the start address given by the file, if any.
+#[derive(Debug, PartialEq)]
+pub struct Image {
+    pub blocks: Vec<(u16, Vec<u8>)>,
+    pub start: Option<u16>,
+}
+
+impl Image {
+    pub fn size(&self) -> usize {
+        self.blocks.iter().map(|(_, data)| data.len()).sum()
+    }
+
+    /// Consecutive data is merged into a single block, which must not run past the end of memory.
+    fn add(&mut self, address: u16, data: &[u8]) -> Result<(), AppError> {
+        let space = 0x10000 - address as usize;
+        if data.len() > space {
+            return Err(AppError::ImageTooLarge(data.len(), space));
+        }
+        match self.blocks.last_mut() {
+            Some((start, block)) if *start as usize + block.len() == address as usize => block.extend_from_slice(data),
+            _ => self.blocks.push((address, data.to_vec())),
+        }
+        Ok(())
+    }
+}
+
+/// Text formats are recognised by the content, PRG by the file extension, anything else is raw.
+pub fn detect_format(path: &Path, data: &[u8]) -> ImageFormat {
+    match data {
+        [b':', ..] => ImageFormat::IntelHex,
+        [b'S', b'0'..=b'9', ..] => ImageFormat::SRecord,
+        _ if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("prg")) => ImageFormat::Prg,
+        _ => ImageFormat::Raw,
+    }
+}
+
+/// Raw data is placed at `addr`, other formats carry their own load address.
+pub fn read_image(data: &[u8], format: ImageFormat, addr: Option<u16>) -> Result<Image, AppError> {
+    let mut image = Image {
+        blocks: Vec::new(),
+        start: None,
+    };
+    match format {
+        ImageFormat::Raw => image.add(addr.ok_or(AppError::MissingOperand)?, data)?,
+        ImageFormat::Prg => match data {
+            [lo, hi, rest @ ..] => image.add(u16::from_le_bytes([*lo, *hi]), rest)?,
+            _ => return Err(AppError::InvalidImage(1)),
+        },
+        ImageFormat::IntelHex => read_intel_hex(&mut image, data)?,
+        ImageFormat::SRecord => read_srecord(&mut image, data)?,
+    }
+    Ok(image)
+}
+
+/// Bytes of a hex encoded record, `None` when the encoding or checksum is invalid.
+fn record_bytes(hex: &str, checksum: fn(u8) -> u8) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    let (last, record) = bytes.split_last()?;
+    let sum = record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    match checksum(sum) == *last {
+        true => Some(bytes),
+        false => None,
+    }
+}
+
+fn read_intel_hex(image: &mut Image, data: &[u8]) -> Result<(), AppError> {
+    for (num, line) in String::from_utf8_lossy(data).lines().enumerate() {
+        let invalid = || AppError::InvalidImage(num + 1);
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let hex = line.strip_prefix(':').ok_or_else(invalid)?;
+        let record = record_bytes(hex, u8::wrapping_neg).ok_or_else(invalid)?;
+        let len = record[0] as usize;
+        if record.len() != len + 5 {
+            return Err(invalid());
+        }
+        let address = u16::from_be_bytes([record[1], record[2]]);
+        let payload = &record[4..4 + len];
+        match (record[3], payload) {
+            (0x00, _) => image.add(address, payload).map_err(|_| invalid())?,
+            (0x01, _) => break,
+            (0x02 | 0x04, [0, 0]) => {}
+            (0x03, [.., hi, lo]) | (0x05, [.., hi, lo]) => image.start = Some(u16::from_be_bytes([*hi, *lo])),
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(())
+}
+
+fn read_srecord(image: &mut Image, data: &[u8]) -> Result<(), AppError> {
+    for (num, line) in String::from_utf8_lossy(data).lines().enumerate() {
+        let invalid = || AppError::InvalidImage(num + 1);
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let kind = line.strip_prefix('S').and_then(|l| l.chars().next()).ok_or_else(invalid)?;
+        let record = record_bytes(line.get(2..).ok_or_else(invalid)?, |sum| !sum).ok_or_else(invalid)?;
+        if record.len() != record[0] as usize + 1 {
+            return Err(invalid());
+        }
+        let address_len = match kind {
+            '0' | '1' | '5' | '9' => 2,
+            '2' | '6' | '8' => 3,
+            '3' | '7' => 4,
+            _ => return Err(invalid()),
+        };
+        let fields = &record[1..record.len() - 1];
+        if fields.len() < address_len {
+            return Err(invalid());
+        }
+        let (address, payload) = fields.split_at(address_len);
+        let address = address.iter().fold(0u32, |a, b| a << 8 | *b as u32);
+        let address = u16::try_from(address).map_err(|_| invalid())?;
+        match kind {
+            '1'..='3' => image.add(address, payload).map_err(|_| invalid())?,
+            '7'..='9' => image.start = Some(address),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Binary formats merge segments into a single image with gaps filled with `fill`, raw image is also padded
+/// with it up to `size` when given. Record based formats write each segment as a separate run of records.
+pub fn write_image<W: Write>(
+    out: &mut W,
+    segments: &[Segment],
+    format: ImageFormat,
+    fill: u8,
+    size: Option<usize>,
+) -> Result<(), AppError> {
+    match format {
+        ImageFormat::Raw => {
+            let (_, mut image) = flatten(segments, fill)?;
+            if let Some(size) = size {
+                if image.len() > size {
+                    return Err(AppError::ImageTooLarge(image.len(), size));
+                }
+                image.resize(size, fill);
+            }
+            out.write_all(&image)?;
+        }
+        ImageFormat::Prg => {
+            let (start, image) = flatten(segments, fill)?;
+            out.write_all(&start.to_le_bytes())?;
+            out.write_all(&image)?;
+        }
+        ImageFormat::IntelHex => write_intel_hex(out, segments)?,
+        ImageFormat::SRecord => write_srecord(out, segments)?,
+    }
+    Ok(())
+}
+
+fn hex_record(record: &[u8]) -> String {
+    record.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn write_intel_hex<W: Write>(out: &mut W, segments: &[Segment]) -> std::io::Result<()> {
+    let mut write_record = |address: u16, kind: u8, data: &[u8]| {
+        let mut record = vec![data.len() as u8, (address >> 8) as u8, address as u8, kind];
+        record.extend_from_slice(data);
+        let sum = record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        record.push(sum.wrapping_neg());
+        writeln!(out, ":{}", hex_record(&record))
+    };
+    for segment in segments {
+        for (i, chunk) in segment.code.chunks(RECORD_SIZE).enumerate() {
+            write_record(segment.start().wrapping_add((i * RECORD_SIZE) as u16), 0x00, chunk)?;
+        }
+    }
+    write_record(0, 0x01, &[])
+}
+
+/// Header record holds the name of the first segment, termination record its start address.
+fn write_srecord<W: Write>(out: &mut W, segments: &[Segment]) -> std::io::Result<()> {
+    let mut write_record = |kind: char, address: u16, data: &[u8]| {
+        let mut record = vec![(data.len() + 3) as u8, (address >> 8) as u8, address as u8];
+        record.extend_from_slice(data);
+        let sum = record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        record.push(!sum);
+        writeln!(out, "S{}{}", kind, hex_record(&record))
+    };
+    let first = segments.first();
+    write_record('0', 0, first.map(|s| s.name.as_bytes()).unwrap_or_default())?;
+    let mut count = 0;
+    for segment in segments {
+        for (i, chunk) in segment.code.chunks(RECORD_SIZE).enumerate() {
+            write_record('1', segment.start().wrapping_add((i * RECORD_SIZE) as u16), chunk)?;
+            count += 1;
+        }
+    }
+    if count <= u16::MAX as usize {
+        write_record('5', count as u16, &[])?;
+    }
+    write_record('9', first.map(|s| s.start()).unwrap_or(0), &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments() -> Vec<Segment> {
+        let mut code = Segment::new("CODE", 0);
+        code.set_location_counter(0x0600, true).unwrap();
+        (0..18).for_each(|b| code.emit(b, true));
+        let mut vectors = Segment::new("VECTORS", 0);
+        vectors.set_location_counter(0x0614, true).unwrap();
+        [0x00, 0x06].iter().for_each(|b| vectors.emit(*b, true));
+        vec![code, vectors]
+    }
+
+    fn write(format: ImageFormat, size: Option<usize>) -> Result<Vec<u8>, AppError> {
+        let mut out = Vec::new();
+        write_image(&mut out, &segments(), format, 0xff, size)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!("IHEX".parse::<ImageFormat>().unwrap(), ImageFormat::IntelHex);
+        assert!(matches!("elf".parse::<ImageFormat>(), Err(AppError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn raw_and_prg() {
+        let raw = write(ImageFormat::Raw, None).unwrap();
+        assert_eq!(raw.len(), 22);
+        assert_eq!(&raw[16..], &[16, 17, 0xff, 0xff, 0x00, 0x06]);
+        let padded = write(ImageFormat::Raw, Some(32)).unwrap();
+        assert_eq!(padded.len(), 32);
+        assert_eq!(padded[31], 0xff);
+        assert!(matches!(write(ImageFormat::Raw, Some(16)), Err(AppError::ImageTooLarge(22, 16))));
+        let prg = write(ImageFormat::Prg, None).unwrap();
+        assert_eq!(&prg[..4], &[0x00, 0x06, 0, 1]);
+        assert_eq!(prg.len(), 24);
+    }
+
+    #[test]
+    fn intel_hex() {
+        let text = String::from_utf8(write(ImageFormat::IntelHex, None).unwrap()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], ":10060000000102030405060708090A0B0C0D0E0F72");
+        assert_eq!(lines[1], ":020610001011C7");
+        assert_eq!(lines[2], ":020614000006DE");
+        assert_eq!(lines[3], ":00000001FF");
+    }
+
+    fn read_back(format: ImageFormat) -> Image {
+        read_image(&write(format, None).unwrap(), format, None).unwrap()
+    }
+
+    #[test]
+    fn read_formats() {
+        let blocks = segments()
+            .iter()
+            .map(|s| (s.start(), s.code.clone()))
+            .collect::<Vec<(u16, Vec<u8>)>>();
+        assert_eq!(read_back(ImageFormat::IntelHex).blocks, blocks);
+        let srec = read_back(ImageFormat::SRecord);
+        assert_eq!((srec.blocks, srec.start), (blocks, Some(0x0600)));
+        let prg = read_back(ImageFormat::Prg);
+        assert_eq!(prg.blocks[0].0, 0x0600);
+        assert_eq!(prg.size(), 22);
+        let raw = read_image(&[1, 2], ImageFormat::Raw, Some(0xc000)).unwrap();
+        assert_eq!(raw.blocks, &[(0xc000, vec![1, 2])]);
+        let hex = read_image(b":020000041234B4\n:040000050000C00037\n", ImageFormat::IntelHex, None);
+        assert!(matches!(hex, Err(AppError::InvalidImage(1))));
+        let hex = read_image(b":040000050000C00037\n:00000001FF\n", ImageFormat::IntelHex, None).unwrap();
+        assert_eq!(hex.start, Some(0xc000));
+        assert!(matches!(
+            read_image(b"S1050610101100\n", ImageFormat::SRecord, None),
+            Err(AppError::InvalidImage(1))
+        ));
+        assert!(matches!(
+            read_image("S\u{e9}1234\n".as_bytes(), ImageFormat::SRecord, None),
+            Err(AppError::InvalidImage(1))
+        ));
+        let prg: Vec<u8> = [0xf0, 0xff].iter().chain(&[0; 18]).copied().collect();
+        assert!(matches!(
+            read_image(&prg, ImageFormat::Prg, None),
+            Err(AppError::ImageTooLarge(18, 16))
+        ));
+        assert!(matches!(
+            read_image(b":02FFFF00AABB43\n", ImageFormat::IntelHex, None),
+            Err(AppError::InvalidImage(1))
+        ));
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(detect_format(Path::new("a.bin"), b":10"), ImageFormat::IntelHex);
+        assert_eq!(detect_format(Path::new("a.txt"), b"S0030000FC"), ImageFormat::SRecord);
+        assert_eq!(detect_format(Path::new("game.PRG"), &[0x01, 0x08]), ImageFormat::Prg);
+        assert_eq!(detect_format(Path::new("rom"), &[0x4c, 0x00]), ImageFormat::Raw);
+    }
+
+    #[test]
+    fn srecord() {
+        let text = String::from_utf8(write(ImageFormat::SRecord, None).unwrap()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "S0070000434F4445DD");
+        assert_eq!(lines[2], "S10506101011C3");
+        assert_eq!(lines[4], "S5030003F9");
+        assert_eq!(lines[5], "S9030600F6");
+    }
+}
@@ -61,6 +61,84 @@ pub enum Instruction {
     Plp,
     Nop,
     Kil,
+    // illegal NMOS
+    Lax,
+    Sax,
+    Dcp,
+    Isc,
+    Slo,
+    Rla,
+    Sre,
+    Rra,
+    Anc,
+    Alr,
+    Arr,
+    Sbx,
+    Las,
+    // 65C02
+    Bra,
+    Stz,
+    Trb,
+    Tsb,
+    Phx,
+    Phy,
+    Plx,
+    Ply,
+    Wai,
+    Stp,
+    Bbr0,
+    Bbr1,
+    Bbr2,
+    Bbr3,
+    Bbr4,
+    Bbr5,
+    Bbr6,
+    Bbr7,
+    Bbs0,
+    Bbs1,
+    Bbs2,
+    Bbs3,
+    Bbs4,
+    Bbs5,
+    Bbs6,
+    Bbs7,
+    Rmb0,
+    Rmb1,
+    Rmb2,
+    Rmb3,
+    Rmb4,
+    Rmb5,
+    Rmb6,
+    Rmb7,
+    Smb0,
+    Smb1,
+    Smb2,
+    Smb3,
+    Smb4,
+    Smb5,
+    Smb6,
+    Smb7,
+    // 65CE02
+    Cle,
+    See,
+    Tsy,
+    Tys,
+    Inz,
+    Dez,
+    Neg,
+    Asr,
+    Taz,
+    Tza,
+    Tab,
+    Tba,
+    Phz,
+    Plz,
+    Ldz,
+    Cpz,
+    Inw,
+    Dew,
+    Asw,
+    Row,
 }
 
 impl Instruction {
@@ -68,6 +146,25 @@ impl Instruction {
         MNEMONICS.get(self).unwrap()
     }
 
+    /// Status flags the instruction modifies, in `NVDIZC` order (`E` is the 65CE02 extend stack flag).
+    pub fn flags(&self) -> &'static str {
+        match self {
+            Adc | Sbc | Isc | Rra | Arr => "NVZC",
+            Bit => "NVZ",
+            Asl | Lsr | Rol | Ror | Cmp | Cpx | Cpy | Cpz | Dcp | Slo | Rla | Sre | Anc | Alr | Sbx | Asr | Asw | Row => "NZC",
+            And | Ora | Eor | Inc | Inx | Iny | Dec | Dex | Dey | Lda | Ldx | Ldy | Ldz | Tax | Tay | Tsx | Txa | Tya | Pla | Plx | Ply
+            | Plz | Lax | Las | Tsy | Inz | Dez | Neg | Taz | Tza | Tba | Inw | Dew => "NZ",
+            Trb | Tsb => "Z",
+            Clc | Sec => "C",
+            Cld | Sed => "D",
+            Cli | Sei | Brk => "I",
+            Clv => "V",
+            Cle | See => "E",
+            Rti | Plp => "NVDIZC",
+            _ => "",
+        }
+    }
+
     pub fn parse(mnemonic: &str) -> Result<Instruction, AppError> {
         let m = &mnemonic.to_uppercase();
         MNEMONICS
@@ -78,68 +175,142 @@ impl Instruction {
     }
 }
 
-    static MNEMONICS: LazyLock<BTreeMap<Instruction, &'static str>> = LazyLock::new(|| {
-        let mut m = BTreeMap::new();
-        m.insert(Kil, "KIL");
-        m.insert(Adc, "ADC");
-        m.insert(Sbc, "SBC");
-        m.insert(And, "AND");
-        m.insert(Ora, "ORA");
-        m.insert(Asl, "ASL");
-        m.insert(Lsr, "LSR");
-        m.insert(Eor, "EOR");
-        m.insert(Rol, "ROL");
-        m.insert(Ror, "ROR");
-        m.insert(Bit, "BIT");
-        m.insert(Cmp, "CMP");
-        m.insert(Cpx, "CPX");
-        m.insert(Cpy, "CPY");
-        m.insert(Inc, "INC");
-        m.insert(Inx, "INX");
-        m.insert(Iny, "INY");
-        m.insert(Dec, "DEC");
-        m.insert(Dex, "DEX");
-        m.insert(Dey, "DEY");
-        m.insert(Bcc, "BCC");
-        m.insert(Bcs, "BCS");
-        m.insert(Beq, "BEQ");
-        m.insert(Bmi, "BMI");
-        m.insert(Bne, "BNE");
-        m.insert(Bpl, "BPL");
-        m.insert(Bvc, "BVC");
-        m.insert(Bvs, "BVS");
-        m.insert(Clc, "CLC");
-        m.insert(Cld, "CLD");
-        m.insert(Cli, "CLI");
-        m.insert(Clv, "CLV");
-        m.insert(Sec, "SEC");
-        m.insert(Sed, "SED");
-        m.insert(Sei, "SEI");
-        m.insert(Jmp, "JMP");
-        m.insert(Jsr, "JSR");
-        m.insert(Brk, "BRK");
-        m.insert(Rti, "RTI");
-        m.insert(Rts, "RTS");
-        m.insert(Lda, "LDA");
-        m.insert(Ldx, "LDX");
-        m.insert(Ldy, "LDY");
-        m.insert(Sta, "STA");
-        m.insert(Stx, "STX");
-        m.insert(Sty, "STY");
-        m.insert(Tax, "TAX");
-        m.insert(Tay, "TAY");
-        m.insert(Tsx, "TSX");
-        m.insert(Txa, "TXA");
-        m.insert(Tya, "TYA");
-        m.insert(Txs, "TXS");
-        m.insert(Pha, "PHA");
-        m.insert(Php, "PHP");
-        m.insert(Pla, "PLA");
-        m.insert(Plp, "PLP");
-        m.insert(Nop, "NOP");
-        m
-    });
-
+static MNEMONICS: LazyLock<BTreeMap<Instruction, &'static str>> = LazyLock::new(|| {
+    let mut m = BTreeMap::new();
+    m.insert(Kil, "KIL");
+    m.insert(Adc, "ADC");
+    m.insert(Sbc, "SBC");
+    m.insert(And, "AND");
+    m.insert(Ora, "ORA");
+    m.insert(Asl, "ASL");
+    m.insert(Lsr, "LSR");
+    m.insert(Eor, "EOR");
+    m.insert(Rol, "ROL");
+    m.insert(Ror, "ROR");
+    m.insert(Bit, "BIT");
+    m.insert(Cmp, "CMP");
+    m.insert(Cpx, "CPX");
+    m.insert(Cpy, "CPY");
+    m.insert(Inc, "INC");
+    m.insert(Inx, "INX");
+    m.insert(Iny, "INY");
+    m.insert(Dec, "DEC");
+    m.insert(Dex, "DEX");
+    m.insert(Dey, "DEY");
+    m.insert(Bcc, "BCC");
+    m.insert(Bcs, "BCS");
+    m.insert(Beq, "BEQ");
+    m.insert(Bmi, "BMI");
+    m.insert(Bne, "BNE");
+    m.insert(Bpl, "BPL");
+    m.insert(Bvc, "BVC");
+    m.insert(Bvs, "BVS");
+    m.insert(Clc, "CLC");
+    m.insert(Cld, "CLD");
+    m.insert(Cli, "CLI");
+    m.insert(Clv, "CLV");
+    m.insert(Sec, "SEC");
+    m.insert(Sed, "SED");
+    m.insert(Sei, "SEI");
+    m.insert(Jmp, "JMP");
+    m.insert(Jsr, "JSR");
+    m.insert(Brk, "BRK");
+    m.insert(Rti, "RTI");
+    m.insert(Rts, "RTS");
+    m.insert(Lda, "LDA");
+    m.insert(Ldx, "LDX");
+    m.insert(Ldy, "LDY");
+    m.insert(Sta, "STA");
+    m.insert(Stx, "STX");
+    m.insert(Sty, "STY");
+    m.insert(Tax, "TAX");
+    m.insert(Tay, "TAY");
+    m.insert(Tsx, "TSX");
+    m.insert(Txa, "TXA");
+    m.insert(Tya, "TYA");
+    m.insert(Txs, "TXS");
+    m.insert(Pha, "PHA");
+    m.insert(Php, "PHP");
+    m.insert(Pla, "PLA");
+    m.insert(Plp, "PLP");
+    m.insert(Nop, "NOP");
+    m.insert(Lax, "LAX");
+    m.insert(Sax, "SAX");
+    m.insert(Dcp, "DCP");
+    m.insert(Isc, "ISC");
+    m.insert(Slo, "SLO");
+    m.insert(Rla, "RLA");
+    m.insert(Sre, "SRE");
+    m.insert(Rra, "RRA");
+    m.insert(Anc, "ANC");
+    m.insert(Alr, "ALR");
+    m.insert(Arr, "ARR");
+    m.insert(Sbx, "SBX");
+    m.insert(Las, "LAS");
+    m.insert(Bra, "BRA");
+    m.insert(Stz, "STZ");
+    m.insert(Trb, "TRB");
+    m.insert(Tsb, "TSB");
+    m.insert(Phx, "PHX");
+    m.insert(Phy, "PHY");
+    m.insert(Plx, "PLX");
+    m.insert(Ply, "PLY");
+    m.insert(Wai, "WAI");
+    m.insert(Stp, "STP");
+    m.insert(Bbr0, "BBR0");
+    m.insert(Bbr1, "BBR1");
+    m.insert(Bbr2, "BBR2");
+    m.insert(Bbr3, "BBR3");
+    m.insert(Bbr4, "BBR4");
+    m.insert(Bbr5, "BBR5");
+    m.insert(Bbr6, "BBR6");
+    m.insert(Bbr7, "BBR7");
+    m.insert(Bbs0, "BBS0");
+    m.insert(Bbs1, "BBS1");
+    m.insert(Bbs2, "BBS2");
+    m.insert(Bbs3, "BBS3");
+    m.insert(Bbs4, "BBS4");
+    m.insert(Bbs5, "BBS5");
+    m.insert(Bbs6, "BBS6");
+    m.insert(Bbs7, "BBS7");
+    m.insert(Rmb0, "RMB0");
+    m.insert(Rmb1, "RMB1");
+    m.insert(Rmb2, "RMB2");
+    m.insert(Rmb3, "RMB3");
+    m.insert(Rmb4, "RMB4");
+    m.insert(Rmb5, "RMB5");
+    m.insert(Rmb6, "RMB6");
+    m.insert(Rmb7, "RMB7");
+    m.insert(Smb0, "SMB0");
+    m.insert(Smb1, "SMB1");
+    m.insert(Smb2, "SMB2");
+    m.insert(Smb3, "SMB3");
+    m.insert(Smb4, "SMB4");
+    m.insert(Smb5, "SMB5");
+    m.insert(Smb6, "SMB6");
+    m.insert(Smb7, "SMB7");
+    m.insert(Cle, "CLE");
+    m.insert(See, "SEE");
+    m.insert(Tsy, "TSY");
+    m.insert(Tys, "TYS");
+    m.insert(Inz, "INZ");
+    m.insert(Dez, "DEZ");
+    m.insert(Neg, "NEG");
+    m.insert(Asr, "ASR");
+    m.insert(Taz, "TAZ");
+    m.insert(Tza, "TZA");
+    m.insert(Tab, "TAB");
+    m.insert(Tba, "TBA");
+    m.insert(Phz, "PHZ");
+    m.insert(Plz, "PLZ");
+    m.insert(Ldz, "LDZ");
+    m.insert(Cpz, "CPZ");
+    m.insert(Inw, "INW");
+    m.insert(Dew, "DEW");
+    m.insert(Asw, "ASW");
+    m.insert(Row, "ROW");
+    m
+});
 
 #[cfg(test)]
 mod tests {
@@ -163,4 +334,19 @@ mod tests {
         assert_eq!(Kil.mnemonic(), "KIL");
         assert_eq!(Jmp.mnemonic(), "JMP");
     }
+
+    #[test]
+    fn modified_flags() {
+        assert_eq!(Adc.flags(), "NVZC");
+        assert_eq!(Lda.flags(), "NZ");
+        assert_eq!(Sta.flags(), "");
+        assert_eq!(Plp.flags(), "NVDIZC");
+    }
+
+    #[test]
+    fn extended_mnemonics() {
+        assert_eq!(Instruction::parse("lax").unwrap(), Lax);
+        assert_eq!(Instruction::parse("BBR0").unwrap(), Bbr0);
+        assert_eq!(Smb7.mnemonic(), "SMB7");
+    }
 }
@@ -0,0 +1,297 @@
+//! Places segments of relocatable objects in memory according to a layout and resolves their relocations.
+//!
+//! Layout lists segments in the order they are placed, one per line and each at most once:
+//!
+//! ```text
+//! ; segment  start   [end]
+//! ZP         $02     $ff
+//! CODE       $0600
+//! DATA       *               ; follows the previous segment
+//! BSS        *       $7fff
+//! ```
+//!
+//! Segments of the same name from all objects are placed one after another in order of the objects.
+
+use super::{
+    assembler::Segment,
+    object::{Object, RelocKind, Target},
+    symbols::{Symbol, SymbolKind},
+};
+use crate::error::AppError;
+use regex::Regex;
+use std::{collections::HashMap, convert::TryFrom, io::Write, sync::LazyLock};
+
+static LAYOUT_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^(\w+)\s+(\*|\$[0-9a-f]{1,4})(?:\s+\$([0-9a-f]{1,4}))?$").unwrap());
+
+/// Memory region of a segment, `start` is `None` when it follows the previous region.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Region {
+    pub segment: String,
+    pub start: Option<u16>,
+    pub end: Option<u16>,
+}
+
+/// Address of an object segment in the linked image.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Placement {
+    pub name: String,
+    pub object: usize,
+    pub segment: usize,
+    pub address: u16,
+    pub size: usize,
+}
+
+pub struct Linked {
+    /// Initialized regions of the image, one per layout line.
+    pub segments: Vec<Segment>,
+    /// Exported symbols with their final values.
+    pub symbols: Vec<Symbol>,
+    pub placements: Vec<Placement>,
+    names: Vec<String>,
+    exporters: HashMap<String, usize>,
+}
+
+pub fn read_layout(text: &str) -> Result<Vec<Region>, AppError> {
+    let mut regions = Vec::new();
+    for (num, line) in text.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let c = LAYOUT_LINE.captures(line).ok_or(AppError::InvalidLayout(num + 1))?;
+        let hex = |text: &str| u16::from_str_radix(text.trim_start_matches('$'), 16).unwrap();
+        if regions.iter().any(|r: &Region| r.segment == c[1]) {
+            return Err(AppError::InvalidLayout(num + 1));
+        }
+        regions.push(Region {
+            segment: String::from(&c[1]),
+            start: Some(&c[2]).filter(|s| *s != "*").map(hex),
+            end: c.get(3).map(|m| hex(m.as_str())),
+        });
+    }
+    Ok(regions)
+}
+
+/// Empty segments need not be placed, `objects` are given with their names used in the map file.
+pub fn link(objects: &[(String, Object)], layout: &[Region]) -> Result<Linked, AppError> {
+    let mut bases: HashMap<(usize, usize), u16> = HashMap::new();
+    let mut placements = Vec::new();
+    let mut segments = Vec::new();
+    let mut next = 0usize;
+    for region in layout {
+        let start = region.start.map_or(next, |start| start as usize);
+        let mut address = start;
+        let mut code = Vec::new();
+        let mut initialized = false;
+        for (o, (_, object)) in objects.iter().enumerate() {
+            for (s, segment) in object.segments.iter().enumerate().filter(|(_, s)| s.name == region.segment) {
+                let limit = region.end.map_or(0x10000, |end| end as usize + 1);
+                if address + segment.data.len() > limit {
+                    return Err(AppError::SegmentOverflow(region.segment.clone()));
+                }
+                bases.insert((o, s), address as u16);
+                placements.push(Placement {
+                    name: segment.name.clone(),
+                    object: o,
+                    segment: s,
+                    address: address as u16,
+                    size: segment.data.len(),
+                });
+                code.extend_from_slice(&segment.data);
+                initialized |= !segment.reserved;
+                address += segment.data.len();
+            }
+        }
+        if initialized && !code.is_empty() {
+            segments.push((region.segment.clone(), start as u16, code));
+        }
+        next = address;
+    }
+    for (o, (_, object)) in objects.iter().enumerate() {
+        for (s, segment) in object.segments.iter().enumerate() {
+            if !segment.data.is_empty() && !bases.contains_key(&(o, s)) {
+                return Err(AppError::UnplacedSegment(segment.name.clone()));
+            }
+        }
+    }
+    let mut symbols = Vec::new();
+    let mut exporters = HashMap::new();
+    for (o, (_, object)) in objects.iter().enumerate() {
+        for export in &object.exports {
+            let (value, kind) = match export.segment {
+                Some(s) => (
+                    export.value.wrapping_add(bases.get(&(o, s)).copied().unwrap_or(0)),
+                    SymbolKind::Label,
+                ),
+                None => (export.value, SymbolKind::Constant),
+            };
+            if exporters.insert(export.name.clone(), o).is_some() {
+                return Err(AppError::DuplicateExport(export.name.clone()));
+            }
+            symbols.push(Symbol {
+                name: export.name.clone(),
+                value: value as i32,
+                kind,
+                line: None,
+                file: None,
+            });
+        }
+    }
+    let values: HashMap<&str, u16> = symbols.iter().map(|s| (s.name.as_str(), s.value as u16)).collect();
+    for (o, (_, object)) in objects.iter().enumerate() {
+        for r in &object.relocations {
+            let base = match r.target {
+                Target::Segment(s) => bases.get(&(o, s)).copied().unwrap_or(0),
+                Target::Import(i) => {
+                    let name = &object.imports[i];
+                    *values.get(name.as_str()).ok_or_else(|| AppError::UnresolvedImport(name.clone()))?
+                }
+            };
+            let segment = &object.segments[r.segment];
+            let address = match bases.get(&(o, r.segment)) {
+                Some(address) => *address,
+                None => continue,
+            };
+            let (_, start, code) = match segments.iter_mut().find(|(name, _, _)| *name == segment.name) {
+                Some(region) => region,
+                None => continue,
+            };
+            let at = (address - *start) as usize + r.offset;
+            match r.kind {
+                RelocKind::Word => {
+                    let value = u16::from_le_bytes([code[at], code[at + 1]]).wrapping_add(base);
+                    code[at..at + 2].copy_from_slice(&value.to_le_bytes());
+                }
+                RelocKind::Byte => {
+                    let value = (code[at] as u16).wrapping_add(base);
+                    code[at] = u8::try_from(value).map_err(|_| AppError::RelocationOverflow(segment.name.clone(), value))?;
+                }
+                RelocKind::Low => code[at] = code[at].wrapping_add(base as u8),
+                RelocKind::High(lo) => code[at] = (u16::from_le_bytes([lo, code[at]]).wrapping_add(base) >> 8) as u8,
+            }
+        }
+    }
+    Ok(Linked {
+        segments: segments
+            .into_iter()
+            .map(|(name, start, code)| {
+                let mut segment = Segment::new(&name, start);
+                segment.origin = Some(start);
+                code.iter().for_each(|b| segment.emit(*b, true));
+                segment
+            })
+            .collect(),
+        symbols,
+        placements,
+        names: objects.iter().map(|(name, _)| name.clone()).collect(),
+        exporters,
+    })
+}
+
+impl Linked {
+    /// Placement of segments of each object followed by exported symbols sorted by value.
+    pub fn write_map<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "Segments:")?;
+        for p in &self.placements {
+            let end = (p.address as usize + p.size).saturating_sub(1).max(p.address as usize);
+            writeln!(
+                out,
+                "  {:<12} {:04X}-{:04X} {:>6} B  {}",
+                p.name, p.address, end, p.size, self.names[p.object]
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "Symbols:")?;
+        let mut sorted: Vec<&Symbol> = self.symbols.iter().collect();
+        sorted.sort_by(|a, b| (a.value, &a.name).cmp(&(b.value, &b.name)));
+        for symbol in sorted {
+            let object = self.exporters.get(&symbol.name).map_or("", |o| self.names[*o].as_str());
+            writeln!(out, "  {:04X}  {:<24} {}", symbol.value as u16, symbol.name, object)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mos6510::assembler::Assembler;
+
+    fn object(name: &str, src: &str) -> (String, Object) {
+        let assembly = Assembler::builder().source(name, src).relocatable(true).assemble().unwrap();
+        (String::from(name), assembly.object.unwrap())
+    }
+
+    fn objects() -> Vec<(String, Object)> {
+        vec![
+            object(
+                "main.obj",
+                ".import print, count\n.export start\nstart: jsr print\n lda #>msg\n ldx #<msg\n \
+                 inc count\n jmp start\n.segment \"DATA\"\nmsg: .text \"hi\"\n .word msg",
+            ),
+            object(
+                "lib.obj",
+                ".export print, count, SCREEN\nSCREEN = $0400\n.segment \"ZP\"\ncount: .res 1\n\
+                 .segment \"CODE\"\nprint: stx.z count\n rts",
+            ),
+        ]
+    }
+
+    fn layout() -> Vec<Region> {
+        read_layout("; test\nZP $10 $ff\nCODE $0600\nDATA * ; next\n").unwrap()
+    }
+
+    #[test]
+    fn layout_lines() {
+        let regions = layout();
+        assert_eq!(regions.len(), 3);
+        assert_eq!(
+            regions[0],
+            Region {
+                segment: String::from("ZP"),
+                start: Some(0x10),
+                end: Some(0xff)
+            }
+        );
+        assert_eq!(regions[2].start, None);
+        assert!(matches!(read_layout("CODE\n"), Err(AppError::InvalidLayout(1))));
+        assert!(matches!(read_layout("CODE $0800\nCODE $0600\n"), Err(AppError::InvalidLayout(2))));
+    }
+
+    #[test]
+    fn link_objects() {
+        let linked = link(&objects(), &layout()).unwrap();
+        let names: Vec<&str> = linked.segments.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, &["CODE", "DATA"]);
+        let code = &linked.segments[0];
+        assert_eq!(code.start(), 0x0600);
+        #[rustfmt::skip]
+        assert_eq!(code.code, &[
+            0x20, 0x0d, 0x06, 0xa9, 0x06, 0xa2, 0x10, 0xee, 0x10, 0x00, 0x4c, 0x00, 0x06,
+            0x86, 0x10, 0x60,
+        ]);
+        assert_eq!(linked.segments[1].start(), 0x0610);
+        assert_eq!(linked.segments[1].code, &[b'h', b'i', 0x10, 0x06]);
+        let value = |name: &str| linked.symbols.iter().find(|s| s.name == name).map(|s| s.value);
+        assert_eq!(value("print"), Some(0x060d));
+        assert_eq!(value("count"), Some(0x10));
+        assert_eq!(value("SCREEN"), Some(0x0400));
+        let mut map = Vec::new();
+        linked.write_map(&mut map).unwrap();
+        let map = String::from_utf8(map).unwrap();
+        assert!(map.contains("  CODE         060D-060F      3 B  lib.obj"));
+        assert!(map.contains("  060D  print                    lib.obj"));
+    }
+
+    #[test]
+    fn link_errors() {
+        let layout = read_layout("CODE $0600\nDATA *\n").unwrap();
+        assert!(matches!(link(&objects(), &layout), Err(AppError::UnplacedSegment(name)) if name == "ZP"));
+        let layout = read_layout("ZP $10\nCODE $0600 $060e\nDATA *\n").unwrap();
+        assert!(matches!(link(&objects(), &layout), Err(AppError::SegmentOverflow(name)) if name == "CODE"));
+        let objects = vec![objects().remove(0)];
+        assert!(matches!(link(&objects, &self::layout()), Err(AppError::UnresolvedImport(name)) if name == "print"));
+        let twice = vec![object("a", ".export x\nx: nop"), object("b", ".export x\nx: nop")];
+        assert!(matches!(link(&twice, &self::layout()), Err(AppError::DuplicateExport(name)) if name == "x"));
+    }
+}
@@ -2,6 +2,12 @@ pub struct Memory {
     data: [u8; Memory::SIZE],
 }
 
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Memory {
     pub const SIZE: usize = u16::MAX as usize + 1;
 
@@ -38,6 +44,37 @@ impl Memory {
     pub fn view(&self, first: u16, len: usize) -> &[u8] {
         &self.data[first as usize..(first as usize + len)]
     }
+
+    /// Fills the range (inclusive) with repeated pattern.
+    pub fn fill(&mut self, first: u16, last: u16, pattern: &[u8]) {
+        (first..=last).zip(pattern.iter().cycle()).for_each(|(a, p)| self[a] = *p);
+    }
+
+    /// Copies the range (inclusive) to `dest`, overlapping ranges are handled, bytes past the end of memory are dropped.
+    pub fn transfer(&mut self, first: u16, last: u16, dest: u16) {
+        let len = (last as usize + 1).saturating_sub(first as usize).min(Self::SIZE - dest as usize);
+        self.data.copy_within(first as usize..first as usize + len, dest as usize);
+    }
+
+    /// Addresses in the range (inclusive) holding other bytes than the range starting at `other`.
+    pub fn compare(&self, first: u16, last: u16, other: u16) -> Vec<u16> {
+        (first..=last)
+            .filter(|a| self[*a] != self[other.wrapping_add(a.wrapping_sub(first))])
+            .collect()
+    }
+
+    /// Addresses of the pattern found within the range (inclusive).
+    pub fn find(&self, first: u16, last: u16, pattern: &[u8]) -> Vec<u16> {
+        match pattern.is_empty() || first > last {
+            true => Vec::new(),
+            false => self.data[first as usize..=last as usize]
+                .windows(pattern.len())
+                .enumerate()
+                .filter(|(_, w)| *w == pattern)
+                .map(|(i, _)| first + i as u16)
+                .collect(),
+        }
+    }
 }
 
 impl std::ops::Index<u16> for Memory {
@@ -89,6 +126,21 @@ mod tests {
         assert_eq!(0xfa, mem[0x0000]);
     }
 
+    #[test]
+    fn fill_transfer_compare_find() {
+        let mut mem = Memory::new();
+        mem.fill(0x1000, 0x1004, &[1, 2]);
+        assert_eq!(mem.view(0x1000, 6), &[1, 2, 1, 2, 1, 0]);
+        mem.transfer(0x1000, 0x1003, 0x1002);
+        assert_eq!(mem.view(0x1000, 7), &[1, 2, 1, 2, 1, 2, 0]);
+        mem.transfer(0x1000, 0x1003, 0xfffe);
+        assert_eq!(mem.view(0xfffe, 2), &[1, 2]);
+        assert_eq!(mem[0x0000], 0);
+        assert_eq!(mem.compare(0x1000, 0x1005, 0x1002), &[0x1004, 0x1005]);
+        assert_eq!(mem.find(0x1000, 0x1006, &[2, 1]), &[0x1001, 0x1003]);
+        assert_eq!(mem.find(0x1000, 0x1002, &[2, 1, 2, 1]), &[] as &[u16]);
+    }
+
     #[test]
     fn endianness() {
         let mut mem = Memory::new();
@@ -0,0 +1,291 @@
+//! Relocatable object files written by `asm --object` and combined by `link`.
+//!
+//! The format is line oriented text, numbers are hexadecimal:
+//!
+//! ```text
+//! MO65X-OBJECT 1
+//! SEGMENT CODE 0012          segment name and size, segments are numbered from 0 in order of appearance
+//! DATA A9008D0002...         bytes of the last segment, up to 32 per line
+//! SEGMENT ZP 0002 BSS        reserved space only, no DATA lines follow
+//! IMPORT print               imported symbols are numbered from 0 in order of appearance
+//! EXPORT start 0 0000        name, segment (or - for absolute value) and value
+//! RELOC 0 0001 W S0          segment, offset, kind and target (S - segment, I - import)
+//! ```
+//!
+//! Kinds are `W` (word), `B` (byte), `L` (low byte of address) and `Hxx` (high byte, `xx` is the low byte
+//! of the unrelocated address needed to carry into the high byte).
+
+use crate::error::AppError;
+use std::io::{BufRead, Write};
+
+const MAGIC: &str = "MO65X-OBJECT 1";
+const DATA_LINE: usize = 32;
+
+/// What relocated value is relative to.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Target {
+    Segment(usize),
+    Import(usize),
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RelocKind {
+    Word,
+    Byte,
+    Low,
+    High(u8),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Relocation {
+    pub segment: usize,
+    pub offset: usize,
+    pub kind: RelocKind,
+    pub target: Target,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjectSegment {
+    pub name: String,
+    pub data: Vec<u8>,
+    /// Space reserved with `.res` only, it is not a part of the linked image.
+    pub reserved: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Export {
+    pub name: String,
+    pub value: u16,
+    /// Segment the value is an offset into, absolute value if `None`.
+    pub segment: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Object {
+    pub segments: Vec<ObjectSegment>,
+    pub imports: Vec<String>,
+    pub exports: Vec<Export>,
+    pub relocations: Vec<Relocation>,
+}
+
+impl Object {
+    pub fn write<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "{}", MAGIC)?;
+        for segment in &self.segments {
+            match segment.reserved {
+                true => writeln!(out, "SEGMENT {} {:04X} BSS", segment.name, segment.data.len())?,
+                false => {
+                    writeln!(out, "SEGMENT {} {:04X}", segment.name, segment.data.len())?;
+                    for chunk in segment.data.chunks(DATA_LINE) {
+                        let hex: String = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+                        writeln!(out, "DATA {}", hex)?;
+                    }
+                }
+            }
+        }
+        for name in &self.imports {
+            writeln!(out, "IMPORT {}", name)?;
+        }
+        for export in &self.exports {
+            let segment = export.segment.map_or(String::from("-"), |s| s.to_string());
+            writeln!(out, "EXPORT {} {} {:04X}", export.name, segment, export.value)?;
+        }
+        for r in &self.relocations {
+            let kind = match r.kind {
+                RelocKind::Word => String::from("W"),
+                RelocKind::Byte => String::from("B"),
+                RelocKind::Low => String::from("L"),
+                RelocKind::High(lo) => format!("H{:02X}", lo),
+            };
+            let target = match r.target {
+                Target::Segment(index) => format!("S{}", index),
+                Target::Import(index) => format!("I{}", index),
+            };
+            writeln!(out, "RELOC {} {:04X} {} {}", r.segment, r.offset, kind, target)?;
+        }
+        Ok(())
+    }
+
+    /// Data of each segment must add up to its declared size, which is at most 64 KiB.
+    pub fn read<R: BufRead>(input: R) -> Result<Object, AppError> {
+        let mut object = Object::default();
+        let mut declared = 0;
+        let complete = |object: &Object, declared: usize| object.segments.last().is_none_or(|s| s.data.len() == declared);
+        let mut lines = 0;
+        for (num, line) in input.lines().enumerate() {
+            lines = num + 1;
+            let line = line?;
+            let invalid = || AppError::InvalidObject(num + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if num == 0 {
+                match line.trim() == MAGIC {
+                    true => continue,
+                    false => return Err(invalid()),
+                }
+            }
+            match fields.as_slice() {
+                [] => {}
+                ["SEGMENT", name, size, rest @ ..] => {
+                    let size = usize::from_str_radix(size, 16)
+                        .ok()
+                        .filter(|size| *size <= 0x10000)
+                        .ok_or_else(invalid)?;
+                    if !complete(&object, declared) {
+                        return Err(invalid());
+                    }
+                    declared = size;
+                    let reserved = match rest {
+                        [] => false,
+                        ["BSS"] => true,
+                        _ => return Err(invalid()),
+                    };
+                    object.segments.push(ObjectSegment {
+                        name: String::from(*name),
+                        data: match reserved {
+                            true => vec![0; size],
+                            false => Vec::with_capacity(size),
+                        },
+                        reserved,
+                    });
+                }
+                ["DATA", hex] => {
+                    let segment = object.segments.last_mut().filter(|s| !s.reserved).ok_or_else(invalid)?;
+                    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+                        return Err(invalid());
+                    }
+                    for i in (0..hex.len()).step_by(2) {
+                        segment.data.push(u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid())?);
+                    }
+                }
+                ["IMPORT", name] => object.imports.push(String::from(*name)),
+                ["EXPORT", name, segment, value] => object.exports.push(Export {
+                    name: String::from(*name),
+                    value: u16::from_str_radix(value, 16).map_err(|_| invalid())?,
+                    segment: match *segment {
+                        "-" => None,
+                        index => Some(index.parse().ok().filter(|i| *i < object.segments.len()).ok_or_else(invalid)?),
+                    },
+                }),
+                ["RELOC", segment, offset, kind, target] => {
+                    let kind = match *kind {
+                        "W" => RelocKind::Word,
+                        "B" => RelocKind::Byte,
+                        "L" => RelocKind::Low,
+                        high if high.len() == 3 && high.starts_with('H') => {
+                            RelocKind::High(u8::from_str_radix(&high[1..], 16).map_err(|_| invalid())?)
+                        }
+                        _ => return Err(invalid()),
+                    };
+                    let index = |s: &str| s.get(1..).and_then(|i| i.parse().ok()).ok_or_else(invalid);
+                    let target = match target.chars().next() {
+                        Some('S') => Target::Segment(index(target)?),
+                        Some('I') => Target::Import(index(target)?),
+                        _ => return Err(invalid()),
+                    };
+                    let relocation = Relocation {
+                        segment: segment.parse().map_err(|_| invalid())?,
+                        offset: usize::from_str_radix(offset, 16).map_err(|_| invalid())?,
+                        kind,
+                        target,
+                    };
+                    // segments and imports referred to are defined above
+                    let len = if kind == RelocKind::Word { 2 } else { 1 };
+                    let size = object.segments.get(relocation.segment).map_or(0, |s| s.data.len());
+                    let known = match target {
+                        Target::Segment(index) => index < object.segments.len(),
+                        Target::Import(index) => index < object.imports.len(),
+                    };
+                    if relocation.offset + len > size || !known {
+                        return Err(invalid());
+                    }
+                    object.relocations.push(relocation);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        match complete(&object, declared) {
+            true => Ok(object),
+            false => Err(AppError::InvalidObject(lines)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object() -> Object {
+        Object {
+            segments: vec![
+                ObjectSegment {
+                    name: String::from("CODE"),
+                    data: vec![0x20, 0x00, 0x00, 0xa9, 0x00],
+                    reserved: false,
+                },
+                ObjectSegment {
+                    name: String::from("ZP"),
+                    data: vec![0; 2],
+                    reserved: true,
+                },
+            ],
+            imports: vec![String::from("print")],
+            exports: vec![
+                Export {
+                    name: String::from("start"),
+                    value: 0,
+                    segment: Some(0),
+                },
+                Export {
+                    name: String::from("SCREEN"),
+                    value: 0x0400,
+                    segment: None,
+                },
+            ],
+            relocations: vec![
+                Relocation {
+                    segment: 0,
+                    offset: 1,
+                    kind: RelocKind::Word,
+                    target: Target::Import(0),
+                },
+                Relocation {
+                    segment: 0,
+                    offset: 4,
+                    kind: RelocKind::High(0x12),
+                    target: Target::Segment(1),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut text = Vec::new();
+        object().write(&mut text).unwrap();
+        let lines: Vec<&str> = std::str::from_utf8(&text).unwrap().lines().collect();
+        assert_eq!(lines[1], "SEGMENT CODE 0005");
+        assert_eq!(lines[2], "DATA 200000A900");
+        assert_eq!(lines[3], "SEGMENT ZP 0002 BSS");
+        assert_eq!(lines[6], "EXPORT SCREEN - 0400");
+        assert_eq!(lines[8], "RELOC 0 0004 H12 S1");
+        assert_eq!(Object::read(text.as_slice()).unwrap(), object());
+    }
+
+    #[test]
+    fn invalid_objects() {
+        assert!(matches!(
+            Object::read("SEGMENT CODE 0001".as_bytes()),
+            Err(AppError::InvalidObject(1))
+        ));
+        let text = format!("{}\nSEGMENT CODE 0001\nDATA 0\n", MAGIC);
+        assert!(matches!(Object::read(text.as_bytes()), Err(AppError::InvalidObject(3))));
+        let text = format!("{}\nSEGMENT CODE 0001\nDATA 00\nRELOC 0 0000 W S0\n", MAGIC);
+        assert!(matches!(Object::read(text.as_bytes()), Err(AppError::InvalidObject(4))));
+        let text = format!("{}\nSEGMENT X FFFFFFFFFFFFFFFF BSS\n", MAGIC);
+        assert!(matches!(Object::read(text.as_bytes()), Err(AppError::InvalidObject(2))));
+        let text = format!("{}\nSEGMENT CODE 0002\nDATA 00\nSEGMENT DATA 0001\nDATA 00\n", MAGIC);
+        assert!(matches!(Object::read(text.as_bytes()), Err(AppError::InvalidObject(4))));
+        let text = format!("{}\nSEGMENT CODE 0001\nDATA 0000\n", MAGIC);
+        assert!(matches!(Object::read(text.as_bytes()), Err(AppError::InvalidObject(3))));
+    }
+}
@@ -1,6 +1,57 @@
 use super::addrmode::{AddrMode, AddrMode::*};
 use super::instruction::Instruction::{self, *};
 use crate::error::AppError;
+use std::str::FromStr;
+
+/// Processor variant selecting the set of available opcodes.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum CpuVariant {
+    /// Documented NMOS 6502 opcodes.
+    #[default]
+    Mos6502,
+    /// NMOS 6502 with stable undocumented opcodes.
+    Mos6502X,
+    Wdc65C02,
+    Csg65CE02,
+}
+
+impl FromStr for CpuVariant {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "6502" => Ok(CpuVariant::Mos6502),
+            "6502x" => Ok(CpuVariant::Mos6502X),
+            "65c02" => Ok(CpuVariant::Wdc65C02),
+            "65ce02" => Ok(CpuVariant::Csg65CE02),
+            _ => Err(AppError::InvalidCpu(String::from(s))),
+        }
+    }
+}
+
+impl CpuVariant {
+    /// Opcode tables in lookup order, entries of earlier tables hide later ones.
+    fn tables(&self) -> Vec<&'static BTreeMap<u8, Operation>> {
+        match self {
+            CpuVariant::Mos6502 => vec![&OPCODE_MAP],
+            CpuVariant::Mos6502X => vec![&ILLEGAL_OPCODE_MAP, &OPCODE_MAP],
+            CpuVariant::Wdc65C02 => vec![&CMOS_OPCODE_MAP, &OPCODE_MAP],
+            CpuVariant::Csg65CE02 => vec![&CE02_OPCODE_MAP, &CMOS_OPCODE_MAP, &OPCODE_MAP],
+        }
+    }
+
+    /// NMOS processors fetch the high byte of `JMP ($xxFF)` vector from `$xx00`.
+    pub fn has_jmp_bug(&self) -> bool {
+        matches!(self, CpuVariant::Mos6502 | CpuVariant::Mos6502X)
+    }
+
+    pub fn supports(&self, instruction: Instruction) -> bool {
+        self.tables()
+            .into_iter()
+            .flat_map(|t| t.iter())
+            .any(|(code, op)| op.instruction == instruction && Operation::decode(*self, *code).instruction == instruction)
+    }
+}
 
 #[derive(Debug)]
 pub struct Operation {
@@ -27,16 +78,53 @@ impl Operation {
         OPCODE_MAP.get(&code).unwrap_or(&OPCODE_KIL)
     }
 
+    /// Operation of given opcode as executed by given processor variant.
+    pub fn decode(cpu: CpuVariant, code: u8) -> &'static Operation {
+        cpu.tables().into_iter().find_map(|t| t.get(&code)).unwrap_or(&OPCODE_KIL)
+    }
+
     #[inline]
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> u8 {
         self.addrmode.len() + 1
     }
+
+    /// True when an extra cycle is added on page crossing (or a taken branch).
+    pub fn page_penalty(&self) -> bool {
+        match self.addrmode {
+            Relative | ZeroPageRelative => true,
+            AbsoluteX | AbsoluteY | IndirectIndexedY => matches!(self.instruction, Adc | Sbc | And | Ora | Eor | Cmp | Lda | Ldx | Ldy),
+            _ => false,
+        }
+    }
+
+    /// Base cycle count, marked with `*` when page crossing (or taken branch) costs extra cycles.
+    pub fn timing(&self) -> String {
+        match self.page_penalty() {
+            true => format!("{}*", self.cycles),
+            false => format!("{}", self.cycles),
+        }
+    }
 }
 
-pub fn find_opcode(instruction: Instruction, addrmode: AddrMode) -> Result<u8, AppError> {
-    OPCODE_MAP
+/// Instructions having given addressing mode in any processor variant.
+pub fn instructions_with(addrmode: AddrMode) -> Vec<Instruction> {
+    let mut instructions: Vec<Instruction> = [&OPCODE_MAP, &ILLEGAL_OPCODE_MAP, &CMOS_OPCODE_MAP, &CE02_OPCODE_MAP]
         .iter()
-        .find(|kv| kv.1.matches(instruction, addrmode))
+        .flat_map(|t| t.values())
+        .filter(|op| op.addrmode == addrmode)
+        .map(|op| op.instruction)
+        .collect();
+    instructions.sort();
+    instructions.dedup();
+    instructions
+}
+
+pub fn find_opcode(cpu: CpuVariant, instruction: Instruction, addrmode: AddrMode) -> Result<u8, AppError> {
+    cpu.tables()
+        .into_iter()
+        .flat_map(|t| t.iter())
+        .find(|kv| kv.1.matches(instruction, addrmode) && Operation::decode(cpu, *kv.0).matches(instruction, addrmode))
         .map(|kv| *kv.0)
         .ok_or(AppError::NoOpCode(instruction, addrmode))
 }
@@ -176,8 +264,8 @@ static OPCODE_MAP: LazyLock<BTreeMap<u8, Operation>> = LazyLock::new(|| {
     m.insert(0xd6, Operation::new(Dec, ZeroPageX, 6));
     m.insert(0xd8, Operation::new(Cld, Implied, 2));
     m.insert(0xd9, Operation::new(Cmp, AbsoluteY, 4));
-    m.insert(0xde, Operation::new(Cmp, AbsoluteX, 4));
-    m.insert(0xdf, Operation::new(Dec, AbsoluteX, 7));
+    m.insert(0xdd, Operation::new(Cmp, AbsoluteX, 4));
+    m.insert(0xde, Operation::new(Dec, AbsoluteX, 7));
     m.insert(0xe0, Operation::new(Cpx, Immediate, 2));
     m.insert(0xe1, Operation::new(Sbc, IndexedIndirectX, 6));
     m.insert(0xe4, Operation::new(Cpx, ZeroPage, 3));
@@ -201,6 +289,178 @@ static OPCODE_MAP: LazyLock<BTreeMap<u8, Operation>> = LazyLock::new(|| {
     m
 });
 
+/// Stable undocumented NMOS opcodes.
+static ILLEGAL_OPCODE_MAP: LazyLock<BTreeMap<u8, Operation>> = LazyLock::new(|| {
+    let mut m = BTreeMap::new();
+    m.insert(0x02, Operation::new(Kil, Implied, 0));
+    m.insert(0x03, Operation::new(Slo, IndexedIndirectX, 8));
+    m.insert(0x04, Operation::new(Nop, ZeroPage, 3));
+    m.insert(0x07, Operation::new(Slo, ZeroPage, 5));
+    m.insert(0x0b, Operation::new(Anc, Immediate, 2));
+    m.insert(0x0c, Operation::new(Nop, Absolute, 4));
+    m.insert(0x0f, Operation::new(Slo, Absolute, 6));
+    m.insert(0x13, Operation::new(Slo, IndirectIndexedY, 8));
+    m.insert(0x14, Operation::new(Nop, ZeroPageX, 4));
+    m.insert(0x17, Operation::new(Slo, ZeroPageX, 6));
+    m.insert(0x1b, Operation::new(Slo, AbsoluteY, 7));
+    m.insert(0x1c, Operation::new(Nop, AbsoluteX, 4));
+    m.insert(0x1f, Operation::new(Slo, AbsoluteX, 7));
+    m.insert(0x23, Operation::new(Rla, IndexedIndirectX, 8));
+    m.insert(0x27, Operation::new(Rla, ZeroPage, 5));
+    m.insert(0x2f, Operation::new(Rla, Absolute, 6));
+    m.insert(0x33, Operation::new(Rla, IndirectIndexedY, 8));
+    m.insert(0x37, Operation::new(Rla, ZeroPageX, 6));
+    m.insert(0x3b, Operation::new(Rla, AbsoluteY, 7));
+    m.insert(0x3f, Operation::new(Rla, AbsoluteX, 7));
+    m.insert(0x43, Operation::new(Sre, IndexedIndirectX, 8));
+    m.insert(0x47, Operation::new(Sre, ZeroPage, 5));
+    m.insert(0x4b, Operation::new(Alr, Immediate, 2));
+    m.insert(0x4f, Operation::new(Sre, Absolute, 6));
+    m.insert(0x53, Operation::new(Sre, IndirectIndexedY, 8));
+    m.insert(0x57, Operation::new(Sre, ZeroPageX, 6));
+    m.insert(0x5b, Operation::new(Sre, AbsoluteY, 7));
+    m.insert(0x5f, Operation::new(Sre, AbsoluteX, 7));
+    m.insert(0x63, Operation::new(Rra, IndexedIndirectX, 8));
+    m.insert(0x67, Operation::new(Rra, ZeroPage, 5));
+    m.insert(0x6b, Operation::new(Arr, Immediate, 2));
+    m.insert(0x6f, Operation::new(Rra, Absolute, 6));
+    m.insert(0x73, Operation::new(Rra, IndirectIndexedY, 8));
+    m.insert(0x77, Operation::new(Rra, ZeroPageX, 6));
+    m.insert(0x7b, Operation::new(Rra, AbsoluteY, 7));
+    m.insert(0x7f, Operation::new(Rra, AbsoluteX, 7));
+    m.insert(0x80, Operation::new(Nop, Immediate, 2));
+    m.insert(0x83, Operation::new(Sax, IndexedIndirectX, 6));
+    m.insert(0x87, Operation::new(Sax, ZeroPage, 3));
+    m.insert(0x8f, Operation::new(Sax, Absolute, 4));
+    m.insert(0x97, Operation::new(Sax, ZeroPageY, 4));
+    m.insert(0xa3, Operation::new(Lax, IndexedIndirectX, 6));
+    m.insert(0xa7, Operation::new(Lax, ZeroPage, 3));
+    m.insert(0xaf, Operation::new(Lax, Absolute, 4));
+    m.insert(0xb3, Operation::new(Lax, IndirectIndexedY, 5));
+    m.insert(0xb7, Operation::new(Lax, ZeroPageY, 4));
+    m.insert(0xbb, Operation::new(Las, AbsoluteY, 4));
+    m.insert(0xbf, Operation::new(Lax, AbsoluteY, 4));
+    m.insert(0xc3, Operation::new(Dcp, IndexedIndirectX, 8));
+    m.insert(0xc7, Operation::new(Dcp, ZeroPage, 5));
+    m.insert(0xcb, Operation::new(Sbx, Immediate, 2));
+    m.insert(0xcf, Operation::new(Dcp, Absolute, 6));
+    m.insert(0xd3, Operation::new(Dcp, IndirectIndexedY, 8));
+    m.insert(0xd7, Operation::new(Dcp, ZeroPageX, 6));
+    m.insert(0xdb, Operation::new(Dcp, AbsoluteY, 7));
+    m.insert(0xdf, Operation::new(Dcp, AbsoluteX, 7));
+    m.insert(0xe3, Operation::new(Isc, IndexedIndirectX, 8));
+    m.insert(0xe7, Operation::new(Isc, ZeroPage, 5));
+    m.insert(0xef, Operation::new(Isc, Absolute, 6));
+    m.insert(0xf3, Operation::new(Isc, IndirectIndexedY, 8));
+    m.insert(0xf7, Operation::new(Isc, ZeroPageX, 6));
+    m.insert(0xfb, Operation::new(Isc, AbsoluteY, 7));
+    m.insert(0xff, Operation::new(Isc, AbsoluteX, 7));
+    m
+});
+
+/// Opcodes added or changed by 65C02, including Rockwell/WDC bit instructions.
+static CMOS_OPCODE_MAP: LazyLock<BTreeMap<u8, Operation>> = LazyLock::new(|| {
+    let mut m = BTreeMap::new();
+    m.insert(0x04, Operation::new(Tsb, ZeroPage, 5));
+    m.insert(0x07, Operation::new(Rmb0, ZeroPage, 5));
+    m.insert(0x0c, Operation::new(Tsb, Absolute, 6));
+    m.insert(0x0f, Operation::new(Bbr0, ZeroPageRelative, 5));
+    m.insert(0x12, Operation::new(Ora, ZeroPageIndirect, 5));
+    m.insert(0x14, Operation::new(Trb, ZeroPage, 5));
+    m.insert(0x17, Operation::new(Rmb1, ZeroPage, 5));
+    m.insert(0x1a, Operation::new(Inc, Implied, 2));
+    m.insert(0x1c, Operation::new(Trb, Absolute, 6));
+    m.insert(0x1f, Operation::new(Bbr1, ZeroPageRelative, 5));
+    m.insert(0x27, Operation::new(Rmb2, ZeroPage, 5));
+    m.insert(0x2f, Operation::new(Bbr2, ZeroPageRelative, 5));
+    m.insert(0x32, Operation::new(And, ZeroPageIndirect, 5));
+    m.insert(0x34, Operation::new(Bit, ZeroPageX, 4));
+    m.insert(0x37, Operation::new(Rmb3, ZeroPage, 5));
+    m.insert(0x3a, Operation::new(Dec, Implied, 2));
+    m.insert(0x3c, Operation::new(Bit, AbsoluteX, 4));
+    m.insert(0x3f, Operation::new(Bbr3, ZeroPageRelative, 5));
+    m.insert(0x47, Operation::new(Rmb4, ZeroPage, 5));
+    m.insert(0x4f, Operation::new(Bbr4, ZeroPageRelative, 5));
+    m.insert(0x52, Operation::new(Eor, ZeroPageIndirect, 5));
+    m.insert(0x57, Operation::new(Rmb5, ZeroPage, 5));
+    m.insert(0x5a, Operation::new(Phy, Implied, 3));
+    m.insert(0x5f, Operation::new(Bbr5, ZeroPageRelative, 5));
+    m.insert(0x64, Operation::new(Stz, ZeroPage, 3));
+    m.insert(0x67, Operation::new(Rmb6, ZeroPage, 5));
+    m.insert(0x6f, Operation::new(Bbr6, ZeroPageRelative, 5));
+    m.insert(0x72, Operation::new(Adc, ZeroPageIndirect, 5));
+    m.insert(0x74, Operation::new(Stz, ZeroPageX, 4));
+    m.insert(0x77, Operation::new(Rmb7, ZeroPage, 5));
+    m.insert(0x7a, Operation::new(Ply, Implied, 4));
+    m.insert(0x7c, Operation::new(Jmp, AbsoluteIndexedIndirect, 6));
+    m.insert(0x7f, Operation::new(Bbr7, ZeroPageRelative, 5));
+    m.insert(0x80, Operation::new(Bra, Relative, 3));
+    m.insert(0x87, Operation::new(Smb0, ZeroPage, 5));
+    m.insert(0x89, Operation::new(Bit, Immediate, 2));
+    m.insert(0x8f, Operation::new(Bbs0, ZeroPageRelative, 5));
+    m.insert(0x92, Operation::new(Sta, ZeroPageIndirect, 5));
+    m.insert(0x97, Operation::new(Smb1, ZeroPage, 5));
+    m.insert(0x9c, Operation::new(Stz, Absolute, 4));
+    m.insert(0x9e, Operation::new(Stz, AbsoluteX, 5));
+    m.insert(0x9f, Operation::new(Bbs1, ZeroPageRelative, 5));
+    m.insert(0xa7, Operation::new(Smb2, ZeroPage, 5));
+    m.insert(0xaf, Operation::new(Bbs2, ZeroPageRelative, 5));
+    m.insert(0xb2, Operation::new(Lda, ZeroPageIndirect, 5));
+    m.insert(0xb7, Operation::new(Smb3, ZeroPage, 5));
+    m.insert(0xbf, Operation::new(Bbs3, ZeroPageRelative, 5));
+    m.insert(0xc7, Operation::new(Smb4, ZeroPage, 5));
+    m.insert(0xcb, Operation::new(Wai, Implied, 3));
+    m.insert(0xcf, Operation::new(Bbs4, ZeroPageRelative, 5));
+    m.insert(0xd2, Operation::new(Cmp, ZeroPageIndirect, 5));
+    m.insert(0xd7, Operation::new(Smb5, ZeroPage, 5));
+    m.insert(0xda, Operation::new(Phx, Implied, 3));
+    m.insert(0xdb, Operation::new(Stp, Implied, 3));
+    m.insert(0xdf, Operation::new(Bbs5, ZeroPageRelative, 5));
+    m.insert(0xe7, Operation::new(Smb6, ZeroPage, 5));
+    m.insert(0xef, Operation::new(Bbs6, ZeroPageRelative, 5));
+    m.insert(0xf2, Operation::new(Sbc, ZeroPageIndirect, 5));
+    m.insert(0xf7, Operation::new(Smb7, ZeroPage, 5));
+    m.insert(0xfa, Operation::new(Plx, Implied, 4));
+    m.insert(0xff, Operation::new(Bbs7, ZeroPageRelative, 5));
+    m
+});
+
+/// Opcodes added or changed by 65CE02, word branches and stack relative addressing are not supported.
+static CE02_OPCODE_MAP: LazyLock<BTreeMap<u8, Operation>> = LazyLock::new(|| {
+    let mut m = BTreeMap::new();
+    m.insert(0x02, Operation::new(Cle, Implied, 2));
+    m.insert(0x03, Operation::new(See, Implied, 2));
+    m.insert(0x0b, Operation::new(Tsy, Implied, 1));
+    m.insert(0x1b, Operation::new(Inz, Implied, 1));
+    m.insert(0x22, Operation::new(Jsr, Indirect, 5));
+    m.insert(0x23, Operation::new(Jsr, AbsoluteIndexedIndirect, 5));
+    m.insert(0x2b, Operation::new(Tys, Implied, 1));
+    m.insert(0x3b, Operation::new(Dez, Implied, 1));
+    m.insert(0x42, Operation::new(Neg, Implied, 2));
+    m.insert(0x43, Operation::new(Asr, Implied, 2));
+    m.insert(0x44, Operation::new(Asr, ZeroPage, 4));
+    m.insert(0x4b, Operation::new(Taz, Implied, 1));
+    m.insert(0x54, Operation::new(Asr, ZeroPageX, 5));
+    m.insert(0x5b, Operation::new(Tab, Implied, 1));
+    m.insert(0x6b, Operation::new(Tza, Implied, 1));
+    m.insert(0x7b, Operation::new(Tba, Implied, 1));
+    m.insert(0x8b, Operation::new(Sty, AbsoluteX, 4));
+    m.insert(0x9b, Operation::new(Stx, AbsoluteY, 4));
+    m.insert(0xa3, Operation::new(Ldz, Immediate, 2));
+    m.insert(0xab, Operation::new(Ldz, Absolute, 4));
+    m.insert(0xbb, Operation::new(Ldz, AbsoluteX, 4));
+    m.insert(0xc2, Operation::new(Cpz, Immediate, 2));
+    m.insert(0xc3, Operation::new(Dew, ZeroPage, 5));
+    m.insert(0xcb, Operation::new(Asw, Absolute, 7));
+    m.insert(0xd4, Operation::new(Cpz, ZeroPage, 3));
+    m.insert(0xdb, Operation::new(Phz, Implied, 3));
+    m.insert(0xdc, Operation::new(Cpz, Absolute, 4));
+    m.insert(0xe3, Operation::new(Inw, ZeroPage, 5));
+    m.insert(0xeb, Operation::new(Row, Absolute, 7));
+    m.insert(0xfb, Operation::new(Plz, Implied, 3));
+    m
+});
+
 static OPCODE_KIL: Operation = Operation::new(Kil, Implied, 0);
 
 #[cfg(test)]
@@ -242,6 +502,36 @@ mod tests {
         assert_eq!(op.cycles, 0);
     }
 
+    #[test]
+    fn test_page_penalty() {
+        assert!(Operation::get(0xbd).page_penalty());
+        assert!(Operation::get(0xd0).page_penalty());
+        assert!(!Operation::get(0x9d).page_penalty());
+        assert!(!Operation::get(0xad).page_penalty());
+    }
+
+    #[test]
+    fn test_cpu_variants() {
+        assert_eq!("65C02".parse::<CpuVariant>().unwrap(), CpuVariant::Wdc65C02);
+        assert!(matches!("z80".parse::<CpuVariant>(), Err(AppError::InvalidCpu(_))));
+        assert_eq!(find_opcode(CpuVariant::Mos6502X, Lax, ZeroPage).unwrap(), 0xa7);
+        assert!(find_opcode(CpuVariant::Mos6502, Lax, ZeroPage).is_err());
+        assert_eq!(find_opcode(CpuVariant::Wdc65C02, Lda, ZeroPageIndirect).unwrap(), 0xb2);
+        assert_eq!(find_opcode(CpuVariant::Wdc65C02, Stp, Implied).unwrap(), 0xdb);
+        assert!(find_opcode(CpuVariant::Csg65CE02, Stp, Implied).is_err());
+        assert!(!CpuVariant::Csg65CE02.supports(Wai));
+        assert!(CpuVariant::Csg65CE02.supports(Bbr3));
+        assert_eq!(Operation::decode(CpuVariant::Csg65CE02, 0xdb).instruction, Phz);
+        assert_eq!(Operation::decode(CpuVariant::Mos6502X, 0xff).instruction, Isc);
+        assert_eq!(Operation::decode(CpuVariant::Mos6502, 0xff).instruction, Kil);
+    }
+
+    #[test]
+    fn test_cmp_dec_absolute_x() {
+        assert!(Operation::get(0xdd).matches(Cmp, AbsoluteX));
+        assert!(Operation::get(0xde).matches(Dec, AbsoluteX));
+    }
+
     #[test]
     fn test_supported_opcode() {
         let op = Operation::get(0xf0);
@@ -0,0 +1,283 @@
+use crate::error::AppError;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{BufRead, Write},
+    str::FromStr,
+    sync::LazyLock,
+};
+
+static VICE_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^al\s+(?:C:)?([0-9a-f]{1,4})\s+\.?(\S+)$").unwrap());
+static PLAIN_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^(\S+)\s*=\s*\$([0-9a-f]{1,4})$").unwrap());
+static TREE_HEADER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^symbol table \(\d+ items\):$").unwrap());
+static TREE_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"^( *)(?:"(\S+)" = ([0-9A-F]{4})|(\S+)::)$"#).unwrap());
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Label,
+    Local,
+    Constant,
+}
+
+impl SymbolKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymbolKind::Label => "label",
+            SymbolKind::Local => "local",
+            SymbolKind::Constant => "constant",
+        }
+    }
+}
+
+/// Symbol defined at `line` of the source `file`, included files have their own line numbers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    pub value: i32,
+    pub kind: SymbolKind,
+    #[serde(default)]
+    pub line: Option<usize>,
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JsonSymbols {
+    symbols: Vec<Symbol>,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SymbolFormat {
+    Vice,
+    Plain,
+    Json,
+}
+
+impl FromStr for SymbolFormat {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "vice" => Ok(SymbolFormat::Vice),
+            "plain" => Ok(SymbolFormat::Plain),
+            "json" => Ok(SymbolFormat::Json),
+            _ => Err(AppError::InvalidFormat(String::from(s))),
+        }
+    }
+}
+
+/// Name accepted by VICE monitor, scope separators and dots of locals become underscores.
+fn vice_name(name: &str) -> String {
+    name.replace("::", "__").replace('.', "_")
+}
+
+/// Writes symbols sorted by name.
+pub fn write_symbols<W: Write>(out: &mut W, symbols: &[Symbol], format: SymbolFormat) -> std::io::Result<()> {
+    let mut sorted: Vec<&Symbol> = symbols.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+    match format {
+        SymbolFormat::Vice => sorted
+            .iter()
+            .try_for_each(|s| writeln!(out, "al C:{:04X} .{}", s.value as u16, vice_name(&s.name))),
+        SymbolFormat::Plain => sorted
+            .iter()
+            .try_for_each(|s| writeln!(out, "{} = ${:04X}", s.name, s.value as u16)),
+        SymbolFormat::Json => {
+            writeln!(out, "{{")?;
+            writeln!(out, "  \"symbols\": [")?;
+            for (i, symbol) in sorted.iter().enumerate() {
+                let json = serde_json::to_string(symbol)?;
+                writeln!(out, "    {}{}", json, if i + 1 < sorted.len() { "," } else { "" })?;
+            }
+            writeln!(out, "  ]")?;
+            writeln!(out, "}}")
+        }
+    }
+}
+
+/// Reads symbols written in any of the supported formats, VICE and plain formats do not tell the symbol kind.
+/// Output of `asm -s` is accepted as well, lines before its symbol table are skipped.
+pub fn read_symbols<R: BufRead>(input: R) -> Result<Vec<Symbol>, AppError> {
+    let lines = input.lines().collect::<Result<Vec<String>, _>>()?;
+    if lines
+        .iter()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| line.trim_start().starts_with('{'))
+    {
+        let json: JsonSymbols = serde_json::from_str(&lines.join("\n")).map_err(|e| AppError::InvalidSymbols(e.line()))?;
+        return Ok(json.symbols);
+    }
+    if let Some(header) = lines.iter().position(|line| TREE_HEADER.is_match(line.trim_end())) {
+        return read_symbol_tree(&lines, header + 1);
+    }
+    let mut symbols = Vec::new();
+    for (num, line) in lines.iter().enumerate() {
+        let line = line.trim().trim_end_matches(',');
+        let symbol = |name: &str, value: i32, kind: SymbolKind| Symbol {
+            name: String::from(name),
+            value,
+            kind,
+            line: None,
+            file: None,
+        };
+        if let Some(c) = VICE_LINE.captures(line) {
+            symbols.push(symbol(&c[2], i32::from_str_radix(&c[1], 16).unwrap(), SymbolKind::Label));
+        } else if let Some(c) = PLAIN_LINE.captures(line) {
+            symbols.push(symbol(&c[1], i32::from_str_radix(&c[2], 16).unwrap(), SymbolKind::Label));
+        } else if !(line.is_empty() || line.starts_with(';')) {
+            return Err(AppError::InvalidSymbols(num + 1));
+        }
+    }
+    Ok(symbols)
+}
+
+/// Scopes and locals are nested by indentation, each level is 2 spaces deeper.
+fn read_symbol_tree(lines: &[String], first: usize) -> Result<Vec<Symbol>, AppError> {
+    let mut symbols = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    for (num, line) in lines.iter().enumerate().skip(first) {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let c = TREE_LINE.captures(line).ok_or(AppError::InvalidSymbols(num + 1))?;
+        let depth = c[1].len() / 2;
+        if depth > path.len() {
+            return Err(AppError::InvalidSymbols(num + 1));
+        }
+        path.truncate(depth);
+        path.push(String::from(c.get(2).or_else(|| c.get(4)).unwrap().as_str()));
+        if let Some(value) = c.get(3) {
+            let name = path
+                .iter()
+                .fold(String::new(), |name, part| match name.is_empty() || part.starts_with('.') {
+                    true => name + part,
+                    false => name + "::" + part,
+                });
+            symbols.push(Symbol {
+                kind: if name.contains('.') { SymbolKind::Local } else { SymbolKind::Label },
+                name,
+                value: i32::from_str_radix(value.as_str(), 16).unwrap(),
+                line: None,
+                file: None,
+            });
+        }
+    }
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols() -> Vec<Symbol> {
+        vec![
+            Symbol {
+                name: String::from("start"),
+                value: 0x0600,
+                kind: SymbolKind::Label,
+                line: Some(3),
+                file: Some(String::from("demo \"1\".asm")),
+            },
+            Symbol {
+                name: String::from("start.loop"),
+                value: 0x0602,
+                kind: SymbolKind::Local,
+                line: Some(4),
+                file: Some(String::from("lib \"1\".inc")),
+            },
+            Symbol {
+                name: String::from("SCREEN"),
+                value: 0x0200,
+                kind: SymbolKind::Constant,
+                line: None,
+                file: None,
+            },
+        ]
+    }
+
+    fn write(format: SymbolFormat) -> String {
+        let mut out = Vec::new();
+        write_symbols(&mut out, &symbols(), format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!("VICE".parse::<SymbolFormat>().unwrap(), SymbolFormat::Vice);
+        assert!(matches!("xml".parse::<SymbolFormat>(), Err(AppError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn vice_format() {
+        assert_eq!(
+            write(SymbolFormat::Vice),
+            "al C:0200 .SCREEN\nal C:0600 .start\nal C:0602 .start_loop\n"
+        );
+        assert_eq!(vice_name("io::print.loop"), "io__print_loop");
+    }
+
+    #[test]
+    fn plain_format() {
+        assert_eq!(write(SymbolFormat::Plain), "SCREEN = $0200\nstart = $0600\nstart.loop = $0602\n");
+    }
+
+    #[test]
+    fn read_back() {
+        for format in [SymbolFormat::Plain, SymbolFormat::Json] {
+            let symbols = read_symbols(write(format).as_bytes()).unwrap();
+            let names: Vec<(&str, i32)> = symbols.iter().map(|s| (s.name.as_str(), s.value)).collect();
+            assert_eq!(names, &[("SCREEN", 0x0200), ("start", 0x0600), ("start.loop", 0x0602)]);
+        }
+        let json = read_symbols(write(SymbolFormat::Json).as_bytes()).unwrap();
+        assert_eq!(json[0].kind, SymbolKind::Constant);
+        assert_eq!((json[2].file.as_deref(), json[2].line), (Some("lib \"1\".inc"), Some(4)));
+        let vice = read_symbols(write(SymbolFormat::Vice).as_bytes()).unwrap();
+        assert_eq!(vice[2].name, "start_loop");
+        assert!(matches!(
+            read_symbols("{\n  \"symbols\": [\n    {\"name\": \"start\", \"value\": \"0600\"}\n  ]\n}".as_bytes()),
+            Err(AppError::InvalidSymbols(3))
+        ));
+        assert!(matches!(
+            read_symbols("al C:0600 .start\nstart: lda".as_bytes()),
+            Err(AppError::InvalidSymbols(2))
+        ));
+    }
+
+    #[test]
+    fn symbol_tree() {
+        let text = "source file \"a.asm\", assembling ...\nsymbol table (4 items):\n\"SCREEN\" = 0200\nio::\n  \"print\" = 0610\n    \".loop\" = 0612\n\"start\" = 0600\n";
+        let symbols = read_symbols(text.as_bytes()).unwrap();
+        let names: Vec<(&str, i32)> = symbols.iter().map(|s| (s.name.as_str(), s.value)).collect();
+        assert_eq!(
+            names,
+            &[
+                ("SCREEN", 0x0200),
+                ("io::print", 0x0610),
+                ("io::print.loop", 0x0612),
+                ("start", 0x0600)
+            ]
+        );
+        assert_eq!(symbols[2].kind, SymbolKind::Local);
+        assert!(matches!(
+            read_symbols("symbol table (1 items):\n    \"x\" = 0600\n".as_bytes()),
+            Err(AppError::InvalidSymbols(2))
+        ));
+    }
+
+    #[test]
+    fn json_format() {
+        let json = write(SymbolFormat::Json);
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(
+            lines[2],
+            "    {\"name\":\"SCREEN\",\"value\":512,\"kind\":\"constant\",\"line\":null,\"file\":null},"
+        );
+        assert_eq!(
+            lines[4],
+            "    {\"name\":\"start.loop\",\"value\":1538,\"kind\":\"local\",\"line\":4,\"file\":\"lib \\\"1\\\".inc\"}"
+        );
+    }
+}